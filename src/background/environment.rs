@@ -0,0 +1,153 @@
+use super::*;
+use crate::image::hdr::HdrImage;
+
+use std::{io, path::Path};
+
+/// A high dynamic range photo of the surroundings, in latitude-longitude
+/// (equirectangular) form: the top and bottom rows are straight up (+y) and down (-y),
+/// and the columns run around the y axis, starting from -x as for a `Sphere`
+///
+/// As a light, directions are sampled in proportion to the luminance of the
+/// map, so small bright features such as the sun are found efficiently
+pub struct EnvironmentMap {
+    image: HdrImage,
+    // The normalised cumulative sampling weights of the texels
+    cdf: Vec<f64>,
+}
+
+impl EnvironmentMap {
+    pub fn new(image: HdrImage) -> Self {
+        assert!(image.width > 0 && image.height > 0);
+        // Rows near the poles cover less solid angle, so are sampled less
+        let weight = |i: usize, texel: Vector| {
+            let theta = PI * ((i / image.width) as f64 + 0.5) / image.height as f64;
+            (0.2126 * texel.x + 0.7152 * texel.y + 0.0722 * texel.z).max(0.0) * theta.sin()
+        };
+        let mut cdf: Vec<f64> = image.texels
+            .iter()
+            .enumerate()
+            .scan(0.0, |total, (i, texel)| {
+                *total += weight(i, *texel);
+                Some(*total)
+            })
+            .collect();
+        let total = cdf.last().copied().unwrap_or_default();
+        if total > 0.0 {
+            cdf.iter_mut().for_each(|c| *c /= total);
+        } else {
+            // A black map is sampled uniformly
+            cdf.iter_mut().enumerate().for_each(|(i, c)| *c = (i + 1) as f64 / image.texels.len() as f64);
+        }
+        Self { image, cdf }
+    }
+
+    /// Load the map from a Radiance RGBE (.hdr) or Portable FloatMap (.pfm) file
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Ok(Self::new(HdrImage::open(path)?))
+    }
+
+    /// Returns the coordinates of `direction` in [0, 1] x [0, pi]: the fraction
+    /// of the way around the y axis, and the angle from straight up
+    fn coordinates(direction: UnitVector) -> (f64, f64) {
+        let u = (f64::atan2(-direction.z, direction.x) + PI) / (2.0 * PI);
+        (u, direction.y.clamp(-1.0, 1.0).acos())
+    }
+
+    fn texel_index(&self, u: f64, theta: f64) -> usize {
+        let x = ((u * self.image.width as f64) as usize).min(self.image.width - 1);
+        let y = ((theta / PI * self.image.height as f64) as usize).min(self.image.height - 1);
+        y * self.image.width + x
+    }
+
+    /// The density per unit solid angle of sampling a direction at `theta` within texel `i`
+    fn texel_pdf(&self, i: usize, theta: f64) -> f64 {
+        let probability = self.cdf[i] - if i > 0 { self.cdf[i - 1] } else { 0.0 };
+        let texels = self.image.texels.len() as f64;
+        // Mapping the unit square onto the sphere stretches areas by 2 pi^2 sin theta
+        probability * texels / (2.0 * PI * PI * theta.sin())
+    }
+}
+
+impl Background for EnvironmentMap {
+    fn radiance(&self, direction: UnitVector) -> Vector {
+        let (u, theta) = Self::coordinates(direction);
+        self.image.texels[self.texel_index(u, theta)]
+    }
+
+    fn light(&self) -> Option<&dyn Light> {
+        Some(self)
+    }
+}
+
+impl Light for EnvironmentMap {
+    fn sample(&self, _point: Point, rng: &mut dyn RandomStream) -> Option<LightSample> {
+        let target = rng.next_uniform();
+        let i = self.cdf.partition_point(|c| *c <= target).min(self.cdf.len() - 1);
+        let (x, y) = (i % self.image.width, i / self.image.width);
+        let u = (x as f64 + rng.next_uniform()) / self.image.width as f64;
+        let theta = PI * (y as f64 + rng.next_uniform()) / self.image.height as f64;
+        let phi = 2.0 * PI * u - PI;
+        let pdf = self.texel_pdf(i, theta);
+        if !pdf.is_finite() || pdf <= 0.0 {
+            return None
+        }
+        Some(LightSample {
+            direction: UnitVector::from(Vector::new(theta.sin() * phi.cos(), theta.cos(), -theta.sin() * phi.sin())),
+            distance: f64::INFINITY,
+            radiance: self.image.texels[i],
+            pdf,
+        })
+    }
+
+    fn pdf(&self, _point: Point, direction: UnitVector) -> f64 {
+        let (u, theta) = Self::coordinates(direction);
+        self.texel_pdf(self.texel_index(u, theta), theta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A dim 8x4 map with one bright texel
+    fn map_with_sun() -> EnvironmentMap {
+        let mut texels = vec![Vector::new(0.1, 0.1, 0.1); 32];
+        texels[8 + 5] = Vector::new(1000.0, 1000.0, 900.0);
+        EnvironmentMap::new(HdrImage::new(8, 4, texels))
+    }
+
+    #[test]
+    fn rows_run_from_top_to_bottom() {
+        let texels = (0..4).map(|y| Vector::new(y as f64, 0.0, 0.0)).collect();
+        let map = EnvironmentMap::new(HdrImage::new(1, 4, texels));
+        assert_eq!(map.radiance(UnitVector::from(Vector::new(0.0, 1.0, 0.0))).x, 0.0);
+        assert_eq!(map.radiance(UnitVector::from(Vector::new(1.0, 0.1, 0.0))).x, 1.0);
+        assert_eq!(map.radiance(UnitVector::from(Vector::new(0.0, -1.0, 0.0))).x, 3.0);
+    }
+
+    #[test]
+    fn samples_favour_bright_texels_with_matching_pdf() {
+        let map = map_with_sun();
+        let samples = 1000;
+        let bright = (0..samples).filter(|_| {
+            let sample = map.sample(Point::zero(), &mut rand::thread_rng()).unwrap();
+            assert!((map.pdf(Point::zero(), sample.direction) - sample.pdf).abs() < 1e-6 * sample.pdf);
+            assert_eq!(map.radiance(sample.direction), sample.radiance);
+            sample.radiance.x > 1.0
+        }).count();
+        assert!(bright > 9 * samples / 10);
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        let map = map_with_sun();
+        let steps = 1000;
+        let step = |i: usize| (i as f64 + 0.5) / steps as f64;
+        let integral: f64 = (0..steps).flat_map(|i| (0..steps).map(move |j| (i, j))).map(|(i, j)| {
+            let (y, phi) = (2.0 * step(i) - 1.0, 2.0 * PI * step(j));
+            let r = (1.0 - y.powi(2)).sqrt();
+            map.pdf(Point::zero(), UnitVector::from(Vector::new(r * phi.cos(), y, r * phi.sin())))
+        }).sum::<f64>() * 4.0 * PI / (steps * steps) as f64;
+        assert!((integral - 1.0).abs() < 0.01, "integral {}", integral);
+    }
+}
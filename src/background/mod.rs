@@ -0,0 +1,90 @@
+pub mod environment;
+pub mod sky;
+
+use crate::{
+    geometry::{
+        Point,
+        Vector,
+        UnitVector,
+        uniform_sphere,
+    },
+    light::{Light, LightSample},
+    random::RandomStream,
+};
+
+use core::f64::consts::PI;
+
+/// The light arriving from infinitely far away, seen by rays which escape the scene
+pub trait Background {
+    /// Returns the radiance seen looking in `direction`
+    fn radiance(&self, direction: UnitVector) -> Vector;
+    /// Returns `self` as a `Light` which can be sampled directly, if it is worth
+    /// sampling (e.g. it is bright and doesn't vary smoothly)
+    fn light(&self) -> Option<&dyn Light> {
+        None
+    }
+}
+
+/// A background of the same colour in every direction, such as an overcast sky
+pub struct ConstantBackground {
+    radiance: Vector,
+}
+
+impl ConstantBackground {
+    pub fn new(radiance: Vector) -> Self {
+        Self { radiance }
+    }
+}
+
+impl Background for ConstantBackground {
+    fn radiance(&self, _direction: UnitVector) -> Vector {
+        self.radiance
+    }
+
+    fn light(&self) -> Option<&dyn Light> {
+        Some(self)
+    }
+}
+
+/// Samples all directions uniformly
+impl Light for ConstantBackground {
+    fn sample(&self, _point: Point, rng: &mut dyn RandomStream) -> Option<LightSample> {
+        Some(LightSample {
+            direction: UnitVector::from(uniform_sphere(rng.next_uniform(), rng.next_uniform())),
+            distance: f64::INFINITY,
+            radiance: self.radiance,
+            pdf: 1.0 / (4.0 * PI),
+        })
+    }
+
+    fn pdf(&self, _point: Point, _direction: UnitVector) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+}
+
+/// A background blending linearly from one colour straight
+/// down (-y) to another straight up (+y)
+pub struct GradientBackground {
+    bottom: Vector,
+    top: Vector,
+}
+
+impl GradientBackground {
+    pub fn new(bottom: Vector, top: Vector) -> Self {
+        Self { bottom, top }
+    }
+}
+
+/// A white horizon under a pale blue sky
+impl Default for GradientBackground {
+    fn default() -> Self {
+        Self::new(Vector::new(1.0, 1.0, 1.0), Vector::new(0.5, 0.7, 1.0))
+    }
+}
+
+impl Background for GradientBackground {
+    fn radiance(&self, direction: UnitVector) -> Vector {
+        let a = (direction.y + 1.0) / 2.0;
+        (1.0 - a) * self.bottom + a * self.top
+    }
+}
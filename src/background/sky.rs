@@ -0,0 +1,212 @@
+use super::*;
+
+/// The angle subtended by the sun's radius, in radians
+const SUN_ANGULAR_RADIUS: f64 = 0.00465;
+/// The radiance of the sun's disk, before extinction by the atmosphere, relative to that of
+/// the zenith sky. This gives a clear midday sun about as much light as the rest of the sky
+/// (rather than the several times of reality), suiting scenes without exposure control
+const SUN_RADIANCE: f64 = 1e5;
+/// The fraction of light samples aimed at the sun, with the rest spread over the sky
+const SUN_SAMPLING_PROBABILITY: f64 = 0.5;
+
+/// Preetham, Shirley and Smits' (1999) analytic model of the clear daytime sky,
+/// with the sun's disk. Its radiance is relative to that of the zenith, which is one
+pub struct PreethamSky {
+    // Points towards the sun
+    sun_direction: UnitVector,
+    // The angle of the sun from the zenith
+    sun_theta: f64,
+    // The coefficients of the distribution function for luminance Y and chromaticities x and y
+    coefficients: [[f64; 5]; 3],
+    // The zenith's luminance and chromaticities, luminance scaled to one
+    zenith: [f64; 3],
+    sun_radiance: Vector,
+}
+
+impl PreethamSky {
+    /// Create a sky lit by the sun at `elevation` above the horizon (between 0 and pi / 2) and
+    /// `azimuth` clockwise from -z when viewed from above (both in radians). The `turbidity`,
+    /// between 2 (very clear) and 10 (hazy), measures the haze in the atmosphere
+    pub fn new(elevation: f64, azimuth: f64, turbidity: f64) -> Self {
+        assert!((0.0..=PI / 2.0).contains(&elevation));
+        assert!((2.0..=10.0).contains(&turbidity));
+        let t = turbidity;
+        let sun_theta = PI / 2.0 - elevation;
+        let sun_direction = UnitVector::from(Vector::new(
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            -elevation.cos() * azimuth.cos(),
+        ));
+        let coefficients = [
+            [0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251, 0.1206 * t - 2.5771, -0.0670 * t + 0.3703],
+            [-0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125, -0.0641 * t - 0.8989, -0.0033 * t + 0.0452],
+            [-0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102, -0.0441 * t - 1.6537, -0.0109 * t + 0.0529],
+        ];
+        let zenith_chromaticity = |m: [[f64; 4]; 3]| {
+            let angles = [sun_theta.powi(3), sun_theta.powi(2), sun_theta, 1.0];
+            let row = |r: [f64; 4]| r.iter().zip(angles).map(|(a, b)| a * b).sum::<f64>();
+            t * t * row(m[0]) + t * row(m[1]) + row(m[2])
+        };
+        let zenith = [
+            1.0,
+            zenith_chromaticity([
+                [0.00166, -0.00375, 0.00209, 0.0],
+                [-0.02903, 0.06377, -0.03202, 0.00394],
+                [0.11693, -0.21196, 0.06052, 0.25886],
+            ]),
+            zenith_chromaticity([
+                [0.00275, -0.00610, 0.00317, 0.0],
+                [-0.04214, 0.08970, -0.04153, 0.00516],
+                [0.15346, -0.26756, 0.06670, 0.26688],
+            ]),
+        ];
+        Self {
+            sun_direction,
+            sun_theta,
+            coefficients,
+            zenith,
+            sun_radiance: SUN_RADIANCE * sun_transmittance(sun_theta, turbidity),
+        }
+    }
+
+    /// The radiance of the sky alone (without the sun) looking in `direction`
+    fn sky_radiance(&self, direction: UnitVector) -> Vector {
+        // Below the horizon, continue the horizon's colour
+        let cos_theta = direction.y.max(1e-3);
+        let cos_gamma = direction.dot(self.sun_direction.to_vector()).clamp(-1.0, 1.0);
+        let distribution = |[a, b, c, d, e]: [f64; 5], cos_theta: f64, cos_gamma: f64| {
+            (1.0 + a * (b / cos_theta).exp()) * (1.0 + c * (d * cos_gamma.acos()).exp() + e * cos_gamma.powi(2))
+        };
+        let [luminance, x, y] = core::array::from_fn(|i| {
+            self.zenith[i] * distribution(self.coefficients[i], cos_theta, cos_gamma)
+                / distribution(self.coefficients[i], 1.0, self.sun_theta.cos())
+        });
+        // From xyY to XYZ, and then to linear sRGB
+        let (cie_x, cie_z) = (x * luminance / y, (1.0 - x - y) * luminance / y);
+        Vector::new(
+            3.2406 * cie_x - 1.5372 * luminance - 0.4986 * cie_z,
+            -0.9689 * cie_x + 1.8758 * luminance + 0.0415 * cie_z,
+            0.0557 * cie_x - 0.2040 * luminance + 1.0570 * cie_z,
+        ).map(|c| c.max(0.0))
+    }
+
+    fn in_sun(&self, direction: UnitVector) -> bool {
+        direction.dot(self.sun_direction.to_vector()) >= SUN_ANGULAR_RADIUS.cos()
+    }
+}
+
+/// The fraction of sunlight (per colour channel) reaching the ground through the
+/// atmosphere, from Rayleigh scattering by air and Mie scattering by haze
+fn sun_transmittance(sun_theta: f64, turbidity: f64) -> Vector {
+    // Kasten and Young's relative optical air mass
+    let air_mass = 1.0 / (sun_theta.cos() + 0.50572 * (96.07995 - sun_theta.to_degrees()).powf(-1.6364));
+    // Angstrom's turbidity coefficient
+    let beta = 0.04608 * turbidity - 0.04586;
+    // Representative wavelengths of the red, green and blue channels, in micrometres
+    Vector::new(0.65, 0.55, 0.45).map(|lambda| {
+        let optical_depth = 0.008735 * lambda.powf(-4.08) + beta * lambda.powf(-1.3);
+        (-air_mass * optical_depth).exp()
+    })
+}
+
+impl Background for PreethamSky {
+    fn radiance(&self, direction: UnitVector) -> Vector {
+        let sky = self.sky_radiance(direction);
+        if self.in_sun(direction) {
+            sky + self.sun_radiance
+        } else {
+            sky
+        }
+    }
+
+    fn light(&self) -> Option<&dyn Light> {
+        Some(self)
+    }
+}
+
+/// Samples either the sun's disk or, to find the rest of the sky, all directions uniformly
+impl Light for PreethamSky {
+    fn sample(&self, point: Point, rng: &mut dyn RandomStream) -> Option<LightSample> {
+        let direction = if rng.next_uniform() < SUN_SAMPLING_PROBABILITY {
+            let cos_theta = 1.0 - rng.next_uniform() * (1.0 - SUN_ANGULAR_RADIUS.cos());
+            let sin_theta = (1.0 - cos_theta.powi(2)).max(0.0).sqrt();
+            let phi = 2.0 * PI * rng.next_uniform();
+            let (u, v) = self.sun_direction.orthonormal_basis();
+            UnitVector::from(sin_theta * (phi.cos() * u + phi.sin() * v) + cos_theta * self.sun_direction)
+        } else {
+            UnitVector::from(uniform_sphere(rng.next_uniform(), rng.next_uniform()))
+        };
+        Some(LightSample {
+            direction,
+            distance: f64::INFINITY,
+            radiance: self.radiance(direction),
+            pdf: self.pdf(point, direction),
+        })
+    }
+
+    fn pdf(&self, _point: Point, direction: UnitVector) -> f64 {
+        let sun = if self.in_sun(direction) {
+            1.0 / (2.0 * PI * (1.0 - SUN_ANGULAR_RADIUS.cos()))
+        } else {
+            0.0
+        };
+        SUN_SAMPLING_PROBABILITY * sun + (1.0 - SUN_SAMPLING_PROBABILITY) / (4.0 * PI)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn luminance(v: Vector) -> f64 {
+        0.2126 * v.x + 0.7152 * v.y + 0.0722 * v.z
+    }
+
+    fn direction(elevation: f64, azimuth: f64) -> UnitVector {
+        UnitVector::from(Vector::new(
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            -elevation.cos() * azimuth.cos(),
+        ))
+    }
+
+    #[test]
+    fn zenith_has_unit_luminance() {
+        let sky = PreethamSky::new(0.6, 1.0, 3.0);
+        let zenith = sky.radiance(UnitVector::from(Vector::new(0.0, 1.0, 0.0)));
+        assert!((luminance(zenith) - 1.0).abs() < 1e-3);
+        // A clear sky is blue
+        assert!(zenith.z > zenith.x);
+    }
+
+    #[test]
+    fn sky_is_brightest_around_sun() {
+        let sky = PreethamSky::new(0.3, 0.5, 2.5);
+        let near_sun = luminance(sky.radiance(direction(0.35, 0.5)));
+        let opposite = luminance(sky.radiance(direction(0.35, 0.5 + PI)));
+        assert!(near_sun > 2.0 * opposite);
+        assert!(sky.radiance(direction(0.3, 0.5)) > 100.0);
+    }
+
+    #[test]
+    fn haze_and_low_sun_redden_sunlight() {
+        let high = sun_transmittance(0.2, 2.0);
+        let low = sun_transmittance(1.5, 2.0);
+        let hazy = sun_transmittance(0.2, 8.0);
+        assert!(low < high && hazy < high);
+        assert!(low.x / low.z > high.x / high.z);
+    }
+
+    #[test]
+    fn samples_find_sun_with_matching_pdf() {
+        let sky = PreethamSky::new(0.8, 2.0, 4.0);
+        let samples = 1000;
+        let in_sun = (0..samples).filter(|_| {
+            let sample = sky.sample(Point::zero(), &mut rand::thread_rng()).unwrap();
+            assert_eq!(sample.pdf, sky.pdf(Point::zero(), sample.direction));
+            assert_eq!(sample.radiance, sky.radiance(sample.direction));
+            sky.in_sun(sample.direction)
+        }).count();
+        assert!((400..600).contains(&in_sun));
+    }
+}
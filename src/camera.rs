@@ -6,23 +6,77 @@ use crate::{
     geometry::{
         Point,
         Vector,
+        UnitVector,
         Ray,
         Interval,
         IntervalBounds,
     },
+    integrator::{Integrator, path::PathTracer},
+    film::Film,
+    filter::{Filter, BoxFilter},
     surface::SurfaceSet,
+    random::RandomStream,
+    sampler::{Sampler, IndependentSampler, PixelSample},
+    spectrum::luminance,
 };
 
-use std::{fs::File, io, iter, path::Path};
+use std::{fs::File, io, iter, path::{Path, PathBuf}, rc::Rc};
+
+/// The number of each sample's dimensions used to place the camera ray within its pixel
+const CAMERA_DIMENSIONS: u32 = 2;
+
+/// The brightness below which adaptive sampling measures a pixel's error against this
+/// instead, so that nearly black pixels aren't sampled to a vanishingly small error
+const MIN_BRIGHTNESS: f64 = 1e-3;
+
+/// The bounds on the number of samples per pixel taken by adaptive sampling, and the
+/// error relative to a pixel's brightness at which it's taken to have converged
+#[derive(Debug, Clone, Copy)]
+struct AdaptiveSampling {
+    min_samples: u32,
+    max_samples: u32,
+    error_threshold: f64,
+}
+
+impl AdaptiveSampling {
+    fn converged(&self, brightness: &RunningStatistics) -> bool {
+        brightness.count >= 2
+            && brightness.standard_error() <= self.error_threshold * brightness.mean.max(MIN_BRIGHTNESS)
+    }
+}
+
+/// The mean and variance of a pixel's samples so far, by Welford's algorithm
+#[derive(Debug, Default)]
+struct RunningStatistics {
+    count: u32,
+    mean: f64,
+    // The sum of squared deviations from the mean
+    squared_deviations: f64,
+}
+
+impl RunningStatistics {
+    fn add(&mut self, value: f64) {
+        self.count += 1;
+        let deviation = value - self.mean;
+        self.mean += deviation / self.count as f64;
+        self.squared_deviations += deviation * (value - self.mean);
+    }
+
+    /// The standard deviation of the mean, estimated from at least two samples
+    fn standard_error(&self) -> f64 {
+        let count = self.count as f64;
+        (self.squared_deviations / (count - 1.0) / count).sqrt()
+    }
+}
 
 pub struct Camera {
     // Measured in pixels
     image_width: u16,
     image_height: u16,
     // Measured in our coord system
-    _viewport_width: f64,
-    _viewport_height: f64,
-    _focal_length: f64,
+    viewport_width: f64,
+    viewport_height: f64,
+    focal_length: f64,
     // Additional random samples per pixel
     antialiasing: u8,
     // The Camera's location
@@ -32,13 +86,26 @@ pub struct Camera {
     pixel_delta_v: Vector,
     // The viewport's top-left pixel
     pixel00: Point,
-    // The maximum number of ray bounces
-    max_ray_bounces: u8,
+    // Estimates the light arriving along each ray
+    integrator: Box<dyn Integrator>,
+    // Weighs each sample's contribution to the pixels around it
+    filter: Box<dyn Filter>,
+    // Supplies the numbers behind each sample
+    sampler: Rc<dyn Sampler>,
+    // Whether the last of a fixed number of samples passes through the pixel's centre, as in
+    // the original independent sampling, rather than being placed by the sampler like the rest
+    centre_sample: bool,
+    // Chooses the sampler's numbers, so that renders with the same seed are identical
+    seed: u64,
+    // Replaces the fixed number of samples per pixel
+    adaptive_sampling: Option<AdaptiveSampling>,
+    // Where to write the number of samples taken in each pixel
+    sample_heatmap: Option<PathBuf>,
 }
 
 impl Camera {
     pub fn new(image_width: u16, image_height: u16, viewport_width: f64,
-        viewport_height: f64, focal_length: f64, antialiasing: u8, max_ray_bounces: u8) -> Self
+        viewport_height: f64, focal_length: f64, antialiasing: u8) -> Self
     {
         let eye_point = Point {
             x: 0.0,
@@ -70,38 +137,208 @@ impl Camera {
         Self {
             image_width,
             image_height,
-            _viewport_width: viewport_width,
-            _viewport_height: viewport_height,
-            _focal_length: focal_length,
+            viewport_width,
+            viewport_height,
+            focal_length,
             antialiasing,
             eye_point,
             pixel_delta_u,
             pixel_delta_v,
             pixel00,
-            max_ray_bounces,
+            integrator: Box::new(PathTracer::default()),
+            filter: Box::new(BoxFilter::default()),
+            sampler: Rc::new(IndependentSampler::new()),
+            centre_sample: true,
+            seed: rand::random(),
+            adaptive_sampling: None,
+            sample_heatmap: None,
         }
     }
 
+    /// Use `integrator` to find the light arriving along camera rays,
+    /// in place of the default `PathTracer`
+    pub fn with_integrator(mut self, integrator: impl Integrator + 'static) -> Self {
+        self.integrator = Box::new(integrator);
+        self
+    }
+
+    /// Reconstruct pixels from the samples around them weighted by `filter`, in place of the
+    /// default `BoxFilter`, which averages the samples within each pixel alone
+    pub fn with_filter(mut self, filter: impl Filter + 'static) -> Self {
+        self.filter = Box::new(filter);
+        self
+    }
+
+    /// Take the numbers behind each sample from `sampler`, in place of the default
+    /// `IndependentSampler`. Every sample is then placed within its pixel by the sampler
+    pub fn with_sampler(mut self, sampler: impl Sampler + 'static) -> Self {
+        self.sampler = Rc::new(sampler);
+        self.centre_sample = false;
+        self
+    }
+
+    /// Render with the numbers chosen by `seed`, in place of a random seed. Every number
+    /// behind an image is then fixed by the seed, pixel, sample and dimension, so renders
+    /// of the same world with the same seed are identical
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Take between `min_samples` and `max_samples` samples in each pixel, in place of the
+    /// fixed `antialiasing`, stopping once the standard error of the pixel's brightness is
+    /// within `error_threshold` of the brightness itself. Flat regions such as the sky then
+    /// stop early, leaving the time for noisy ones. Only light found along the pixel's own
+    /// rays (rather than splatted there) counts towards its error
+    pub fn with_adaptive_sampling(mut self, min_samples: u32, max_samples: u32, error_threshold: f64) -> Self {
+        assert!(0 < min_samples && min_samples <= max_samples);
+        self.adaptive_sampling = Some(AdaptiveSampling {
+            min_samples,
+            max_samples,
+            error_threshold,
+        });
+        self
+    }
+
+    /// Also write the number of samples taken in each pixel to `file_name`, as a greyscale
+    /// image from black (no samples) to white (the most samples any pixel may take)
+    pub fn with_sample_heatmap(mut self, file_name: &Path) -> Self {
+        self.sample_heatmap = Some(file_name.to_path_buf());
+        self
+    }
+
     pub fn render(&self, world: &SurfaceSet, file_name: &Path) -> io::Result<()> {
-        let vector_generator = |x: u16, y: u16| {
-            let direct_ray = self.build_ray(x, y, Interval::empty());
-            let diffusion = Interval::new(-0.5, 0.5, IntervalBounds::Closed);
-            let vector_sum: Vector = (0..self.antialiasing)
-                .map(|_| self.build_ray(x, y, diffusion))
-                .chain(iter::once(direct_ray))
-                .map(|ray| ray_colour(&world, ray, self.max_ray_bounces))
-                .sum();
-            vector_sum / (self.antialiasing as f64 + 1.0)
+        self.render_passes(world, file_name, &[])
+    }
+
+    /// Render the image to `file_name`, together with auxiliary `passes` (such as
+    /// `AovIntegrator`s), each written to its own file. Every pass sees the same camera rays
+    pub fn render_passes(&self, world: &SurfaceSet, file_name: &Path, passes: &[(&dyn Integrator, &Path)]) -> io::Result<()> {
+        let integrators: Vec<&dyn Integrator> = iter::once(self.integrator.as_ref())
+            .chain(passes.iter().map(|(integrator, _)| *integrator))
+            .collect();
+        let pixel_count = self.image_width as usize * self.image_height as usize;
+        let (min_samples, max_samples) = match self.adaptive_sampling {
+            Some(adaptive_sampling) => (adaptive_sampling.min_samples, adaptive_sampling.max_samples),
+            None => (self.antialiasing as u32 + 1, self.antialiasing as u32 + 1),
         };
-        let image = Image::from_vectors(self.image_height, self.image_width, &vector_generator, true);
+        let mut films: Vec<Film> = integrators.iter().map(|_| Film::new(self.image_width, self.image_height)).collect();
+        let mut splats = Vec::new();
+        let mut sample_counts = Vec::with_capacity(pixel_count);
+        for y in 0..self.image_height {
+            for x in 0..self.image_width {
+                // Each sample's numbers start from the same dimension for every integrator
+                let sample = |index: u32, dimension: u32| {
+                    PixelSample::new(self.sampler.clone(), self.seed, (x, y), index, max_samples, dimension)
+                };
+                let mut brightness = RunningStatistics::default();
+                let mut count = 0;
+                let converged = |brightness: &RunningStatistics| {
+                    self.adaptive_sampling.is_some_and(|adaptive_sampling| adaptive_sampling.converged(brightness))
+                };
+                while count < max_samples && (count < min_samples || !converged(&brightness)) {
+                    let position = self.sample_position(x, y, count, max_samples);
+                    let ray = self.build_ray(position);
+                    for (i, (integrator, film)) in integrators.iter().zip(films.iter_mut()).enumerate() {
+                        let radiance = integrator.camera_radiance(world, self, ray, &mut splats, None, &mut sample(count, CAMERA_DIMENSIONS));
+                        film.add_sample(self.filter.as_ref(), position, radiance);
+                        // Only the image's own light decides when the pixel has converged
+                        if i == 0 {
+                            brightness.add(luminance(integrator.pixel_rgb(radiance)));
+                        }
+                        for splat in splats.drain(..) {
+                            film.add_splat(splat.pixel, splat.radiance);
+                        }
+                    }
+                    count += 1;
+                }
+                sample_counts.push(count);
+            }
+        }
+        // Splats may land anywhere, so are shared by the mean number of samples per pixel
+        let mean_samples = sample_counts.iter().map(|count| *count as f64).sum::<f64>() / pixel_count as f64;
+        let file_names = iter::once(file_name).chain(passes.iter().map(|(_, file_name)| *file_name));
+        for ((integrator, film), file_name) in integrators.iter().zip(films).zip(file_names) {
+            // Filters with negative lobes can ring below zero by sharp edges
+            let vector_generator = |x: u16, y: u16| {
+                integrator.pixel_rgb(film.pixel(x, y, mean_samples)).map(|component| component.max(0.0))
+            };
+            self.write_image(file_name, &vector_generator, integrator.gamma_correct())?;
+        }
+        if let Some(file_name) = &self.sample_heatmap {
+            let heat = |x: u16, y: u16| {
+                let heat = sample_counts[y as usize * self.image_width as usize + x as usize] as f64 / max_samples as f64;
+                Vector::new(heat, heat, heat)
+            };
+            self.write_image(file_name, &heat, false)?;
+        }
+        Ok(())
+    }
+
+    fn write_image(&self, file_name: &Path, vector_generator: &impl Fn(u16, u16) -> Vector, gamma_correct: bool) -> io::Result<()> {
+        let image = Image::from_vectors(self.image_height, self.image_width, vector_generator, gamma_correct);
         let mut ppm_formatter = PPMFormatter::new(true);
         let mut f = File::create(file_name)?;
         image.write_to_file(&mut f, &mut ppm_formatter)
     }
 
-    fn build_ray(&self, x: u16, y: u16, sample_space: Interval) -> Ray {
-        let x = (x as f64) + sample_space.min() + sample_space.size() * rand::random::<f64>();
-        let y = (y as f64) + sample_space.min() + sample_space.size() * rand::random::<f64>();
+    pub(crate) fn eye_point(&self) -> Point {
+        self.eye_point
+    }
+
+    /// The seed of the camera's renders, for integrators with randomness of their own
+    pub(crate) fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The pixel whose camera rays leave the eye in `direction`, if any
+    pub(crate) fn pixel(&self, direction: UnitVector) -> Option<(u16, u16)> {
+        if direction.z >= 0.0 {
+            return None
+        }
+        // Where the ray meets the viewport, in pixels from its top-left corner
+        let offset = self.eye_point + direction * (self.focal_length / -direction.z)
+            - (self.pixel00 - (self.pixel_delta_u + self.pixel_delta_v) / 2.0);
+        let x = offset.dot(self.pixel_delta_u) / self.pixel_delta_u.l2_norm_squared();
+        let y = offset.dot(self.pixel_delta_v) / self.pixel_delta_v.l2_norm_squared();
+        let inside = |p: f64, size: u16| (0.0..size as f64).contains(&p);
+        (inside(x, self.image_width) && inside(y, self.image_height)).then_some((x as u16, y as u16))
+    }
+
+    /// The density, per unit solid angle, of camera rays leaving the eye in `direction`,
+    /// taking them to be spread evenly over the viewport. This is also the camera's
+    /// response to light arriving from `direction`, as a sum over the pixels
+    pub(crate) fn direction_pdf(&self, direction: UnitVector) -> f64 {
+        if self.pixel(direction).is_none() {
+            return 0.0
+        }
+        let cos_theta = -direction.z;
+        self.focal_length.powi(2) / (self.viewport_width * self.viewport_height * cos_theta.powi(3))
+    }
+
+    /// The camera ray through the point (`u`, `v`) of the viewport, where
+    /// each lies in [0, 1) from the viewport's top-left corner
+    pub(crate) fn viewport_ray(&self, u: f64, v: f64) -> Ray {
+        self.build_ray((u * self.image_width as f64 - 0.5, v * self.image_height as f64 - 0.5))
+    }
+
+    /// Where sample `index` of pixel (`x`, `y`) is taken, in pixels, as placed
+    /// within the pixel by the first `CAMERA_DIMENSIONS` of the sampler's numbers
+    fn sample_position(&self, x: u16, y: u16, index: u32, samples_per_pixel: u32) -> (f64, f64) {
+        // With a fixed number of independent samples, the last passes through the pixel's centre
+        let sample_space = if self.centre_sample && self.adaptive_sampling.is_none() && index == self.antialiasing as u32 {
+            Interval::empty()
+        } else {
+            Interval::new(-0.5, 0.5, IntervalBounds::Closed)
+        };
+        let mut stream = PixelSample::new(self.sampler.clone(), self.seed, (x, y), index, samples_per_pixel, 0);
+        let x = (x as f64) + sample_space.min() + sample_space.size() * stream.next_uniform();
+        let y = (y as f64) + sample_space.min() + sample_space.size() * stream.next_uniform();
+        (x, y)
+    }
+
+    /// The camera ray through `position`, in pixels, where pixel (x, y) is centred on (x, y)
+    fn build_ray(&self, (x, y): (f64, f64)) -> Ray {
         Ray::from_two_points(
             self.eye_point,
             self.pixel00 + x * self.pixel_delta_u + y * self.pixel_delta_v
@@ -110,23 +347,56 @@ impl Camera {
 
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::stratified::StratifiedSampler;
+
+    #[test]
+    fn camera_rays_leave_through_their_pixels() {
+        let camera = Camera::new(40, 30, 4.0, 3.0, 1.5, 0);
+        for (x, y) in [(0, 0), (39, 29), (17, 3)] {
+            for index in 0..10 {
+                assert_eq!(camera.pixel(camera.build_ray(camera.sample_position(x, y, index, 10)).direction), Some((x, y)));
+            }
+        }
+        assert_eq!(camera.pixel(UnitVector::from(Vector::new(0.0, 0.0, 1.0))), None);
+        assert_eq!(camera.pixel(UnitVector::from(Vector::new(1.0, 0.0, -0.1))), None);
+    }
+
+    #[test]
+    fn only_independent_sampling_keeps_a_centre_sample() {
+        let camera = Camera::new(4, 4, 1.0, 1.0, 1.0, 3);
+        assert_eq!(camera.sample_position(2, 1, 3, 4), (2.0, 1.0));
+        // The stratified sampler places one sample in each quarter of the pixel's width
+        let camera = camera.with_sampler(StratifiedSampler::new());
+        let mut strata: Vec<u32> = (0..4).map(|index| ((camera.sample_position(2, 1, index, 4).0 - 1.5) * 4.0) as u32).collect();
+        strata.sort();
+        assert_eq!(strata, [0, 1, 2, 3]);
+    }
 
-fn ray_colour(world: &SurfaceSet, ray: Ray, max_ray_bounces: u8) -> Vector {
-    if max_ray_bounces == 0 {
-        return Vector::zero()
-    }
-    let intersection = world
-        .intersection(ray, Interval::new(0.001, f64::MAX, IntervalBounds::Open));
-    if intersection.is_none() {
-        let a = (ray.direction.normalise().y + 1.0) / 2.0;
-        return (1.0 - a) * Vector::new(1.0, 1.0, 1.0) + a * Vector::new(0.5, 0.7, 1.0)
-    }
-    let intersection = intersection.unwrap();
-    let point = ray.at(intersection.t);
-    let surface = intersection.surfaces[0];
-    let scattered_ray = match surface.scatter(point, ray) {
-        Some(sr) => sr,
-        None => return Vector::zero(),
-    };
-    scattered_ray.attenuation * ray_colour(world, scattered_ray.ray, max_ray_bounces - 1)
+    #[test]
+    fn direction_pdf_integrates_to_one() {
+        // Integrate over the hemisphere in front of the camera on a grid in (cos theta, phi)
+        let camera = Camera::new(40, 30, 4.0, 3.0, 1.5, 0);
+        let steps = 1000;
+        let step = |i: usize| (i as f64 + 0.5) / steps as f64;
+        let integral: f64 = (0..steps).flat_map(|i| (0..steps).map(move |j| (i, j))).map(|(i, j)| {
+            let (cos_theta, phi) = (step(i), 2.0 * core::f64::consts::PI * step(j));
+            let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
+            camera.direction_pdf(UnitVector::from(Vector::new(sin_theta * phi.cos(), sin_theta * phi.sin(), -cos_theta)))
+        }).sum::<f64>() * 2.0 * core::f64::consts::PI / (steps * steps) as f64;
+        assert!((integral - 1.0).abs() < 0.01, "integral {}", integral);
+    }
+
+    #[test]
+    fn running_statistics_match_sample_variance() {
+        let values = [0.5, 1.5, 2.0, 4.0, 7.0];
+        let mut statistics = RunningStatistics::default();
+        values.iter().for_each(|value| statistics.add(*value));
+        let mean = values.iter().sum::<f64>() / 5.0;
+        let variance = values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / 4.0;
+        assert!((statistics.mean - mean).abs() < 1e-12);
+        assert!((statistics.standard_error() - (variance / 5.0).sqrt()).abs() < 1e-12);
+    }
 }
@@ -0,0 +1,112 @@
+use crate::{
+    geometry::Vector,
+    filter::Filter,
+};
+
+/// The image being rendered, built up from samples weighted by a reconstruction filter.
+/// Each sample counts towards every pixel within the filter's radius of it, whichever
+/// pixel it was taken for, so that pixels are smoothed across their borders
+pub(crate) struct Film {
+    width: u16,
+    height: u16,
+    // The filter-weighted sum of the samples counting towards each pixel
+    weighted_sums: Vec<Vector>,
+    // The sum of their weights
+    weights: Vec<f64>,
+    // Light found reaching each pixel from elsewhere, such as by tracing light paths
+    splats: Vec<Vector>,
+}
+
+impl Film {
+    pub(crate) fn new(width: u16, height: u16) -> Self {
+        let pixel_count = width as usize * height as usize;
+        Self {
+            width,
+            height,
+            weighted_sums: vec![Vector::zero(); pixel_count],
+            weights: vec![0.0; pixel_count],
+            splats: vec![Vector::zero(); pixel_count],
+        }
+    }
+
+    fn index(&self, x: u16, y: u16) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+
+    /// Add a sample of `radiance` taken at `position`, in pixels, where pixel
+    /// (x, y) is centred on (x, y), to every pixel within `filter`'s reach
+    pub(crate) fn add_sample(&mut self, filter: &dyn Filter, position: (f64, f64), radiance: Vector) {
+        let radius = filter.radius();
+        // The pixels whose centres lie within the radius, clamped to the image
+        let range = |centre: f64, size: u16| {
+            let min = (centre - radius).ceil().max(0.0) as i32;
+            let max = (centre + radius).floor().min(size as f64 - 1.0) as i32;
+            (min..=max).map(|i| i as u16)
+        };
+        for y in range(position.1, self.height) {
+            for x in range(position.0, self.width) {
+                let weight = filter.evaluate(position.0 - x as f64, position.1 - y as f64);
+                if weight != 0.0 {
+                    let index = self.index(x, y);
+                    self.weighted_sums[index] = self.weighted_sums[index] + weight * radiance;
+                    self.weights[index] += weight;
+                }
+            }
+        }
+    }
+
+    /// Add `radiance` to `pixel`, outside of its filtered samples
+    pub(crate) fn add_splat(&mut self, pixel: (u16, u16), radiance: Vector) {
+        let index = self.index(pixel.0, pixel.1);
+        self.splats[index] = self.splats[index] + radiance;
+    }
+
+    /// The value of pixel (`x`, `y`): the weighted mean of its samples,
+    /// plus its splats shared by `splat_samples`
+    pub(crate) fn pixel(&self, x: u16, y: u16, splat_samples: f64) -> Vector {
+        let index = self.index(x, y);
+        let mean = if self.weights[index] != 0.0 {
+            self.weighted_sums[index] / self.weights[index]
+        } else {
+            Vector::zero()
+        };
+        mean + self.splats[index] / splat_samples
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::{BoxFilter, TentFilter};
+
+    #[test]
+    fn box_filter_keeps_samples_in_their_pixel() {
+        let mut film = Film::new(2, 1);
+        film.add_sample(&BoxFilter::default(), (-0.5, 0.0), Vector::new(1.0, 1.0, 1.0));
+        film.add_sample(&BoxFilter::default(), (0.4, 0.2), Vector::new(3.0, 3.0, 3.0));
+        film.add_sample(&BoxFilter::default(), (0.5, -0.3), Vector::new(4.0, 4.0, 4.0));
+        assert_eq!(film.pixel(0, 0, 1.0), Vector::new(2.0, 2.0, 2.0));
+        assert_eq!(film.pixel(1, 0, 1.0), Vector::new(4.0, 4.0, 4.0));
+    }
+
+    #[test]
+    fn wide_filters_spread_samples_across_pixels() {
+        let mut film = Film::new(3, 2);
+        // A quarter of the way from pixel (1, 0) to pixel (2, 0)
+        film.add_sample(&TentFilter::new(1.0), (1.25, 0.0), Vector::new(1.0, 1.0, 1.0));
+        film.add_sample(&TentFilter::new(1.0), (2.0, 0.0), Vector::new(2.0, 2.0, 2.0));
+        assert_eq!(film.pixel(0, 0, 1.0), Vector::zero());
+        assert_eq!(film.pixel(1, 0, 1.0), Vector::new(1.0, 1.0, 1.0));
+        // Weighted 0.25 and 1
+        assert!((film.pixel(2, 0, 1.0).x - (0.25 + 2.0) / 1.25).abs() < 1e-12);
+        assert_eq!(film.pixel(1, 1, 1.0), Vector::zero());
+    }
+
+    #[test]
+    fn splats_are_shared_by_samples() {
+        let mut film = Film::new(1, 1);
+        film.add_sample(&BoxFilter::default(), (0.0, 0.0), Vector::new(1.0, 1.0, 1.0));
+        film.add_splat((0, 0), Vector::new(4.0, 4.0, 4.0));
+        assert_eq!(film.pixel(0, 0, 2.0), Vector::new(3.0, 3.0, 3.0));
+    }
+}
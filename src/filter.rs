@@ -0,0 +1,270 @@
+use core::f64::consts::PI;
+
+/// Weighs how much each sample counts towards the pixels around it, by the sample's offset
+/// from a pixel's centre (measured in pixels). A pixel's value is the weighted mean of the
+/// samples within the filter's radius, so only the filter's shape matters, not its scale.
+/// Wider filters blur away aliasing, while those with negative lobes keep edges sharp
+pub trait Filter {
+    /// The distance beyond which the filter is zero, along each axis
+    fn radius(&self) -> f64;
+
+    /// The weight of a sample offset by (`x`, `y`) from a pixel's centre
+    fn evaluate(&self, x: f64, y: f64) -> f64;
+}
+
+/// Weighs every sample within the radius equally. With the default radius of half a pixel,
+/// each sample counts towards its own pixel alone
+#[derive(Debug, Clone, Copy)]
+pub struct BoxFilter {
+    radius: f64,
+}
+
+impl BoxFilter {
+    pub fn new(radius: f64) -> Self {
+        assert!(radius > 0.0);
+        Self {
+            radius,
+        }
+    }
+}
+
+impl Default for BoxFilter {
+    fn default() -> Self {
+        Self::new(0.5)
+    }
+}
+
+impl Filter for BoxFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        // Half-open, so that a sample on the border between two pixels counts towards just one
+        let inside = |offset: f64| (-self.radius..self.radius).contains(&offset);
+        if inside(x) && inside(y) { 1.0 } else { 0.0 }
+    }
+}
+
+/// Weighs samples falling linearly from the pixel's centre to zero at the radius
+#[derive(Debug, Clone, Copy)]
+pub struct TentFilter {
+    radius: f64,
+}
+
+impl TentFilter {
+    pub fn new(radius: f64) -> Self {
+        assert!(radius > 0.0);
+        Self {
+            radius,
+        }
+    }
+}
+
+impl Default for TentFilter {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl Filter for TentFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        let tent = |offset: f64| (self.radius - offset.abs()).max(0.0);
+        tent(x) * tent(y)
+    }
+}
+
+/// A Gaussian with standard deviation `sigma`, lowered by its value at the
+/// radius so that it falls to zero there rather than being cut off
+#[derive(Debug, Clone, Copy)]
+pub struct GaussianFilter {
+    radius: f64,
+    sigma: f64,
+}
+
+impl GaussianFilter {
+    pub fn new(radius: f64, sigma: f64) -> Self {
+        assert!(radius > 0.0 && sigma > 0.0);
+        Self {
+            radius,
+            sigma,
+        }
+    }
+
+    fn gaussian(&self, offset: f64) -> f64 {
+        (-offset.powi(2) / (2.0 * self.sigma.powi(2))).exp()
+    }
+}
+
+impl Default for GaussianFilter {
+    fn default() -> Self {
+        Self::new(1.5, 0.5)
+    }
+}
+
+impl Filter for GaussianFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        let gaussian = |offset: f64| (self.gaussian(offset) - self.gaussian(self.radius)).max(0.0);
+        gaussian(x) * gaussian(y)
+    }
+}
+
+/// The cubic filters of Mitchell and Netravali ("Reconstruction Filters in Computer Graphics",
+/// 1988), a family traded between blurring and ringing by `b` and `c`. Their recommended
+/// b = c = 1/3 is the default. The cubic spans two of its own units either side of the
+/// centre, which are stretched to the radius
+#[derive(Debug, Clone, Copy)]
+pub struct MitchellFilter {
+    radius: f64,
+    b: f64,
+    c: f64,
+}
+
+impl MitchellFilter {
+    pub fn new(radius: f64, b: f64, c: f64) -> Self {
+        assert!(radius > 0.0);
+        Self {
+            radius,
+            b,
+            c,
+        }
+    }
+
+    /// The cubic at `x`, which lies in [0, 2] where it's non-zero
+    fn mitchell(&self, x: f64) -> f64 {
+        let (b, c) = (self.b, self.c);
+        let value = if x < 1.0 {
+            (12.0 - 9.0 * b - 6.0 * c) * x.powi(3) + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2) + (6.0 - 2.0 * b)
+        } else if x < 2.0 {
+            (-b - 6.0 * c) * x.powi(3) + (6.0 * b + 30.0 * c) * x.powi(2)
+                + (-12.0 * b - 48.0 * c) * x + (8.0 * b + 24.0 * c)
+        } else {
+            0.0
+        };
+        value / 6.0
+    }
+}
+
+impl Default for MitchellFilter {
+    fn default() -> Self {
+        Self::new(2.0, 1.0 / 3.0, 1.0 / 3.0)
+    }
+}
+
+impl Filter for MitchellFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        let mitchell = |offset: f64| self.mitchell(2.0 * offset.abs() / self.radius);
+        mitchell(x) * mitchell(y)
+    }
+}
+
+/// A sinc windowed by a sinc stretched to the radius, the closest of these
+/// filters to the ideal sinc, at the price of ringing around sharp edges
+#[derive(Debug, Clone, Copy)]
+pub struct LanczosFilter {
+    radius: f64,
+}
+
+impl LanczosFilter {
+    pub fn new(radius: f64) -> Self {
+        assert!(radius > 0.0);
+        Self {
+            radius,
+        }
+    }
+}
+
+impl Default for LanczosFilter {
+    fn default() -> Self {
+        Self::new(3.0)
+    }
+}
+
+impl Filter for LanczosFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        let lanczos = |offset: f64| if offset.abs() < self.radius {
+            sinc(offset) * sinc(offset / self.radius)
+        } else {
+            0.0
+        };
+        lanczos(x) * lanczos(y)
+    }
+}
+
+/// The normalised sinc, sin(πx) / πx
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        return 1.0
+    }
+    (PI * x).sin() / (PI * x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filters() -> [Box<dyn Filter>; 5] {
+        [
+            Box::new(BoxFilter::default()),
+            Box::new(TentFilter::default()),
+            Box::new(GaussianFilter::default()),
+            Box::new(MitchellFilter::default()),
+            Box::new(LanczosFilter::default()),
+        ]
+    }
+
+    #[test]
+    fn filters_vanish_beyond_their_radius() {
+        for filter in filters() {
+            let radius = filter.radius();
+            assert!(filter.evaluate(0.0, 0.0) > 0.0);
+            for offset in [radius, radius + 0.1, 2.0 * radius] {
+                assert_eq!(filter.evaluate(offset, 0.0), 0.0);
+                assert_eq!(filter.evaluate(0.0, offset), 0.0);
+                assert_eq!(filter.evaluate(-offset - 0.1, 0.0), 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn filters_are_continuous_at_their_radius() {
+        for filter in filters().into_iter().skip(1) {
+            let radius = filter.radius();
+            assert!(filter.evaluate(radius - 1e-6, 0.0).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn mitchell_filter_integrates_to_one() {
+        // Its 1D cubic integrates to one over its two units either side of the centre
+        let filter = MitchellFilter::new(2.0, 1.0 / 3.0, 1.0 / 3.0);
+        let steps = 10000;
+        let integral = (0..steps).map(|i| {
+            let x = -2.0 + 4.0 * (i as f64 + 0.5) / steps as f64;
+            filter.mitchell(2.0 * x.abs() / filter.radius) * 4.0 / steps as f64
+        }).sum::<f64>();
+        assert!((integral - 1.0).abs() < 1e-6, "{}", integral);
+    }
+
+    #[test]
+    fn sharpening_filters_have_negative_lobes() {
+        assert!(MitchellFilter::default().evaluate(1.5, 0.0) < 0.0);
+        assert!(LanczosFilter::default().evaluate(1.5, 0.0) < 0.0);
+    }
+}
@@ -0,0 +1,115 @@
+use super::*;
+
+/// An axis-aligned box in 3D space
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BoundingBox {
+    min: Point,
+    max: Point,
+}
+
+impl BoundingBox {
+    /// Create the smallest `BoundingBox` containing both corners
+    pub fn new(a: Point, b: Point) -> Self {
+        Self {
+            min: Point::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            max: Point::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+        }
+    }
+
+    pub fn min(&self) -> Point {
+        self.min
+    }
+
+    pub fn max(&self) -> Point {
+        self.max
+    }
+
+    /// Tests if `point` lies within the (closed) box
+    /// # Example
+    /// ```
+    /// use ray_tracing::{BoundingBox, Point};
+    /// let bounds = BoundingBox::new(Point::new(0.0, 0.0, 0.0), Point::new(1.0, 2.0, 3.0));
+    /// assert!(bounds.contains(Point::new(1.0, 1.0, 1.0)));
+    /// assert!(!bounds.contains(Point::new(1.0, 1.0, 4.0)));
+    /// ```
+    pub fn contains(&self, point: Point) -> bool {
+        self.min <= point && point <= self.max
+    }
+
+    /// Maps `point` into the box's local coordinates, where the box spans [0, 1]^3
+    pub fn to_local(&self, point: Point) -> Point {
+        let size = self.max - self.min;
+        let offset = point - self.min;
+        Point::new(offset.x / size.x, offset.y / size.y, offset.z / size.z)
+    }
+
+    /// Returns the sub-`Interval` of `time_interval` during which `ray` lies within
+    /// the box, or None if the ray misses the box in that interval
+    pub fn clip(&self, ray: Ray, time_interval: Interval) -> Option<Interval> {
+        let slabs = [
+            (ray.origin.x, ray.direction.x, self.min.x, self.max.x),
+            (ray.origin.y, ray.direction.y, self.min.y, self.max.y),
+            (ray.origin.z, ray.direction.z, self.min.z, self.max.z),
+        ];
+        let mut t_min = time_interval.min();
+        let mut t_max = time_interval.max();
+        for (origin, direction, low, high) in slabs {
+            let inverse = 1.0 / direction;
+            let t0 = (low - origin) * inverse;
+            let t1 = (high - origin) * inverse;
+            let (t0, t1) = if inverse < 0.0 {
+                (t1, t0)
+            } else {
+                (t0, t1)
+            };
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max <= t_min {
+                return None
+            }
+        }
+        Some(Interval::new(t_min, t_max, time_interval.bounds()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_cube() -> BoundingBox {
+        BoundingBox::new(Point::new(1.0, 1.0, 1.0), Point::new(0.0, 0.0, 0.0))
+    }
+
+    #[test]
+    fn corners_are_ordered() {
+        let bounds = unit_cube();
+        assert_eq!(bounds.min(), Point::new(0.0, 0.0, 0.0));
+        assert_eq!(bounds.max(), Point::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn clip_through_box() {
+        let ray = Ray::new(Point::new(-1.0, 0.5, 0.5), UnitVector::from(Vector::new(1.0, 0.0, 0.0)));
+        let clipped = unit_cube()
+            .clip(ray, Interval::positive_reals(IntervalBounds::Open))
+            .unwrap();
+        assert_eq!(clipped.min(), 1.0);
+        assert_eq!(clipped.max(), 2.0);
+    }
+
+    #[test]
+    fn clip_from_inside_box() {
+        let ray = Ray::new(Point::new(0.5, 0.5, 0.5), UnitVector::from(Vector::new(0.0, -1.0, 0.0)));
+        let clipped = unit_cube()
+            .clip(ray, Interval::positive_reals(IntervalBounds::Open))
+            .unwrap();
+        assert_eq!(clipped.min(), 0.0);
+        assert_eq!(clipped.max(), 0.5);
+    }
+
+    #[test]
+    fn clip_missing_box() {
+        let ray = Ray::new(Point::new(-1.0, 2.0, 0.5), UnitVector::from(Vector::new(1.0, 0.0, 0.0)));
+        assert_eq!(unit_cube().clip(ray, Interval::positive_reals(IntervalBounds::Open)), None);
+    }
+}
@@ -0,0 +1,51 @@
+use super::*;
+
+/// A right-handed orthonormal basis, for moving vectors
+/// between world space and a local (e.g. shading) space
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Frame {
+    tangent: Vector,
+    bitangent: Vector,
+    normal: Vector,
+}
+
+impl Frame {
+    /// Create an arbitrary frame whose local z axis is `normal`
+    pub fn from_normal(normal: UnitVector) -> Self {
+        let (tangent, bitangent) = normal.orthonormal_basis();
+        Self {
+            tangent: tangent.to_vector(),
+            bitangent: bitangent.to_vector(),
+            normal: normal.to_vector(),
+        }
+    }
+
+    /// Express the world space `v` in local coordinates
+    pub fn to_local(self, v: Vector) -> Vector {
+        Vector::new(v.dot(self.tangent), v.dot(self.bitangent), v.dot(self.normal))
+    }
+
+    /// Express the local `v` in world coordinates
+    pub fn to_world(self, v: Vector) -> Vector {
+        v.x * self.tangent + v.y * self.bitangent + v.z * self.normal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let frame = Frame::from_normal(UnitVector::from(Vector::new(-1.0, 2.0, 0.5)));
+        let v = Vector::new(0.3, -4.0, 2.0);
+        assert!((frame.to_world(frame.to_local(v)) - v).l2_norm() < 1e-12);
+    }
+
+    #[test]
+    fn normal_is_local_z() {
+        let n = UnitVector::from(Vector::new(1.0, 1.0, 1.0));
+        let local = Frame::from_normal(n).to_local(n.to_vector());
+        assert!((local - Vector::new(0.0, 0.0, 1.0)).l2_norm() < 1e-12);
+    }
+}
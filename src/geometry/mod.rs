@@ -1,7 +1,11 @@
 mod vector;
+mod bounding_box;
+mod frame;
 pub mod shape;
 
 pub use vector::*;
+pub use bounding_box::BoundingBox;
+pub use frame::Frame;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Ray {
@@ -26,6 +30,20 @@ impl Ray {
     }
 }
 
+/// A point in the 2D parametrisation of a `Shape`'s surface,
+/// with `u` and `v` each lying in [0, 1]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TextureCoordinates {
+    pub u: f64,
+    pub v: f64,
+}
+
+impl TextureCoordinates {
+    pub fn new(u: f64, v: f64) -> Self {
+        Self { u, v }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum IntervalBounds {
     Open,
@@ -117,3 +135,18 @@ impl Interval {
         }
     }
 }
+
+/// Maps two uniform random numbers in [0, 1) to a direction uniformly distributed over the sphere
+pub(crate) fn uniform_sphere(u1: f64, u2: f64) -> Vector {
+    let z = 1.0 - 2.0 * u1;
+    let r = (1.0 - z.powi(2)).max(0.0).sqrt();
+    let phi = 2.0 * core::f64::consts::PI * u2;
+    Vector::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+/// Maps two uniform random numbers in [0, 1) to a point uniformly distributed over the unit disc
+pub(crate) fn uniform_disc(u1: f64, u2: f64) -> (f64, f64) {
+    let r = u1.sqrt();
+    let phi = 2.0 * core::f64::consts::PI * u2;
+    (r * phi.cos(), r * phi.sin())
+}
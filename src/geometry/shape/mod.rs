@@ -1,23 +1,121 @@
 pub mod sphere;
+pub mod quad;
 
-use crate::geometry::{UnitVector, Point, Ray, Interval};
+use crate::{
+    geometry::{UnitVector, Vector, Point, Ray, Interval, TextureCoordinates},
+    random::RandomStream,
+};
 
 /// The trait all renderable surfaces must implement
 pub trait Shape {
-    /// Determines the first time (if any) at which the
-    /// `Ray` intersects this `Surface` in the `time_interval`
-    fn intersection(&self, ray: Ray, time_interval: Interval) -> Option<f64>;
-    /// Given a `Point` on the `Surface`, return the unit vector normal
-    /// to the `Surface` at that `Point`. The normal should point out of
-    /// the object defined by this surface (where this makes sense)
-    /// ## Undefined behaviour
-    /// This trait imposes no guarantees on the method's behaviour
-    /// when passed a `Point` which does not lie on the `Surface`
-    fn outwards_normal(&self, point: Point) -> UnitVector;
-    /// Identical to `outwards_normal`, except the unit vector must point
-    /// "against" the incident ray, rather than out of the surface
-    fn normal_against_ray(&self, point: Point, ray: Ray) -> UnitVector {
-        let n = self.outwards_normal(point);
-        UnitVector::from(- n.dot(ray.direction.to_vector()).signum() * n)
+    /// Determines the first time (if any) at which the `Ray` intersects this
+    /// `Surface` in the `time_interval`, along with the local geometry there
+    fn intersection(&self, ray: Ray, time_interval: Interval) -> Option<HitRecord>;
+
+    /// Samples a point on the shape as seen from `origin`, e.g. to aim a shadow ray at
+    /// it. Returns the hit of the ray from `origin` to the point, and the density of
+    /// its direction per unit solid angle, or None if the shape can't be sampled
+    fn sample(&self, _origin: Point, _rng: &mut dyn RandomStream) -> Option<(HitRecord, f64)> {
+        None
+    }
+
+    /// The density, per unit solid angle, with which `sample`
+    /// picks `direction` as seen from `origin`
+    fn pdf(&self, _origin: Point, _direction: UnitVector) -> f64 {
+        0.0
+    }
+
+    /// Samples a point uniformly by area over the shape, e.g. to emit light from it.
+    /// Returns its hit by a ray arriving from outside along the normal, and the
+    /// density per unit area, or None if the shape can't be sampled
+    fn sample_surface(&self, _rng: &mut dyn RandomStream) -> Option<(HitRecord, f64)> {
+        None
+    }
+}
+
+/// The local geometry of a `Shape` at the point a `Ray` intersects it
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HitRecord {
+    /// The time at which the ray hits the `Shape`
+    pub t: f64,
+    pub point: Point,
+    /// The true normal to the `Shape`, pointing out of the object
+    /// defined by the surface (where this makes sense)
+    pub geometric_normal: UnitVector,
+    /// The normal used for shading, which may deviate from the
+    /// `geometric_normal` (e.g. under normal mapping)
+    pub shading_normal: UnitVector,
+    pub uv: TextureCoordinates,
+    /// Together with the `shading_normal`, the tangent and bitangent form a
+    /// right-handed orthonormal basis, with the tangent following increasing u
+    pub tangent: UnitVector,
+    pub bitangent: UnitVector,
+    /// The rates of change of `point` with u and v, or zero if the `Shape` doesn't
+    /// provide them (see `with_derivatives`)
+    pub dpdu: Vector,
+    pub dpdv: Vector,
+    /// True iff the ray hits the outside of the `Shape`, as opposed to the inside
+    pub front_face: bool,
+}
+
+impl HitRecord {
+    /// Create a `HitRecord` whose shading normal is the geometric normal
+    /// # Parameters
+    /// 1. `ray` - the incident ray
+    /// 1. `t` - the time at which `ray` hits the `Shape`
+    /// 1. `outwards_normal` - the normal to the `Shape`, pointing out of the object
+    /// 1. `uv` - the texture coordinates of the point of intersection
+    /// 1. `tangent` - the direction of increasing u, which need not be of unit
+    ///    length nor exactly perpendicular to the normal. If it is parallel to the
+    ///    normal (e.g. zero) an arbitrary tangent is chosen instead
+    pub fn new(ray: Ray, t: f64, outwards_normal: UnitVector, uv: TextureCoordinates, tangent: Vector) -> Self {
+        let mut record = Self {
+            t,
+            point: ray.at(t),
+            geometric_normal: outwards_normal,
+            shading_normal: outwards_normal,
+            uv,
+            tangent: outwards_normal,
+            bitangent: outwards_normal,
+            dpdu: Vector::zero(),
+            dpdv: Vector::zero(),
+            front_face: ray.direction.dot(outwards_normal.to_vector()) < 0.0,
+        };
+        record.set_shading_frame(outwards_normal, tangent);
+        record
+    }
+
+    /// Record the rates of change of the hit point with the texture coordinates, by
+    /// which texture space slopes (such as those of a `BumpMap`) become world space ones
+    pub fn with_derivatives(mut self, dpdu: Vector, dpdv: Vector) -> Self {
+        self.dpdu = dpdu;
+        self.dpdv = dpdv;
+        self
+    }
+
+    /// Replace the shading normal, re-orthogonalising the tangent frame
+    /// around it, with `tangent` as a hint for the new tangent direction
+    pub fn set_shading_frame(&mut self, shading_normal: UnitVector, tangent: Vector) {
+        let n = shading_normal.to_vector();
+        let tangent = tangent - n * n.dot(tangent);
+        let (tangent, bitangent) = if tangent.l2_norm_squared() > 1e-16 {
+            let tangent = UnitVector::from(tangent);
+            (tangent, UnitVector::from(n.cross(tangent.to_vector())))
+        } else {
+            shading_normal.orthonormal_basis()
+        };
+        self.shading_normal = shading_normal;
+        self.tangent = tangent;
+        self.bitangent = bitangent;
+    }
+
+    /// Returns the shading normal, flipped if necessary
+    /// to point against the incident ray
+    pub fn rebound_normal(&self) -> UnitVector {
+        if self.front_face {
+            self.shading_normal
+        } else {
+            UnitVector::from(-1.0 * self.shading_normal)
+        }
     }
 }
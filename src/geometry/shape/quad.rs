@@ -0,0 +1,159 @@
+use super::*;
+use crate::{geometry::IntervalBounds, random::RandomStream};
+
+/// A parallelogram, spanning `corner` + a`u` + b`v` for a and b in [0, 1]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Quad {
+    corner: Point,
+    u: Vector,
+    v: Vector,
+    // u x v, which is normal to the quad with length its area
+    normal: Vector,
+}
+
+impl Quad {
+    /// Create a quad whose front face is the side towards which `u` x `v` points
+    pub fn new(corner: Point, u: Vector, v: Vector) -> Self {
+        let normal = u.cross(v);
+        assert!(normal.l2_norm_squared() > 0.0);
+        Self {
+            corner,
+            u,
+            v,
+            normal,
+        }
+    }
+
+    pub fn area(&self) -> f64 {
+        self.normal.l2_norm()
+    }
+
+    /// The density per unit solid angle, as seen from a point at `distance`, of
+    /// sampling a point on the quad uniformly by area, in the direction `direction`
+    fn solid_angle_pdf(&self, direction: Vector, distance: f64) -> f64 {
+        let cosine = direction.dot(self.normal).abs() / self.area();
+        distance.powi(2) / (cosine * self.area())
+    }
+}
+
+impl Shape for Quad {
+    fn intersection(&self, ray: Ray, time_interval: Interval) -> Option<HitRecord> {
+        let denominator = self.normal.dot(ray.direction.to_vector());
+        if denominator.abs() < 1e-12 {
+            return None
+        }
+        let t = self.normal.dot(self.corner - ray.origin) / denominator;
+        if !time_interval.contains(t) {
+            return None
+        }
+        // Express the hit point in the (u, v) basis of the plane
+        let offset = ray.at(t) - self.corner;
+        let w = self.normal / self.normal.l2_norm_squared();
+        let (a, b) = (w.dot(offset.cross(self.v)), w.dot(self.u.cross(offset)));
+        let unit = Interval::new(0.0, 1.0, IntervalBounds::Closed);
+        if !unit.contains(a) || !unit.contains(b) {
+            return None
+        }
+        Some(HitRecord::new(
+            ray,
+            t,
+            UnitVector::from(self.normal),
+            TextureCoordinates::new(a, b),
+            self.u,
+        ).with_derivatives(self.u, self.v))
+    }
+
+    /// Samples uniformly by area
+    fn sample(&self, origin: Point, rng: &mut dyn RandomStream) -> Option<(HitRecord, f64)> {
+        let point = self.corner + rng.next_uniform() * self.u + rng.next_uniform() * self.v;
+        let offset = point - origin;
+        let distance = offset.l2_norm();
+        let direction = UnitVector::from(offset);
+        if distance <= 0.0 || direction.dot(self.normal).abs() < 1e-12 {
+            return None
+        }
+        let hit = self.intersection(Ray::new(origin, direction), Interval::new(0.0, 2.0 * distance, IntervalBounds::Open))?;
+        Some((hit, self.solid_angle_pdf(direction.to_vector(), hit.t)))
+    }
+
+    fn pdf(&self, origin: Point, direction: UnitVector) -> f64 {
+        self.intersection(Ray::new(origin, direction), Interval::positive_reals(IntervalBounds::Open))
+            .map_or(0.0, |hit| self.solid_angle_pdf(direction.to_vector(), hit.t))
+    }
+
+    fn sample_surface(&self, rng: &mut dyn RandomStream) -> Option<(HitRecord, f64)> {
+        let point = self.corner + rng.next_uniform() * self.u + rng.next_uniform() * self.v;
+        let normal = self.normal.normalise();
+        let ray = Ray::new(point + normal, UnitVector::from(-1.0 * normal));
+        let hit = self.intersection(ray, Interval::new(0.5, 1.5, IntervalBounds::Open))?;
+        Some((hit, 1.0 / self.area()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::f64::consts::PI;
+
+    fn unit_square() -> Quad {
+        Quad::new(Point::new(0.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0))
+    }
+
+    #[test]
+    fn intersection_inside_and_outside() {
+        let down = UnitVector::from(Vector::new(0.0, 0.0, -1.0));
+        let window = Interval::positive_reals(IntervalBounds::Open);
+        let hit = unit_square().intersection(Ray::new(Point::new(0.25, 0.75, 2.0), down), window).unwrap();
+        assert_eq!(hit.t, 2.0);
+        assert_eq!(hit.uv, TextureCoordinates::new(0.25, 0.75));
+        assert!(hit.front_face);
+        assert_eq!(hit.tangent.to_vector(), Vector::new(1.0, 0.0, 0.0));
+        assert_eq!(unit_square().intersection(Ray::new(Point::new(1.5, 0.5, 2.0), down), window), None);
+    }
+
+    #[test]
+    fn parallel_ray_misses() {
+        let ray = Ray::new(Point::new(0.5, 0.5, 0.0), UnitVector::from(Vector::new(1.0, 0.0, 0.0)));
+        assert_eq!(unit_square().intersection(ray, Interval::positive_reals(IntervalBounds::Open)), None);
+    }
+
+    #[test]
+    fn samples_lie_on_quad_with_matching_pdf() {
+        let quad = unit_square();
+        let origin = Point::new(0.2, -0.5, 1.5);
+        for _ in 0..100 {
+            let (hit, pdf) = quad.sample(origin, &mut rand::thread_rng()).unwrap();
+            assert!(hit.point.z.abs() < 1e-12);
+            let direction = UnitVector::from(hit.point - origin);
+            assert!((quad.pdf(origin, direction) - pdf).abs() < 1e-9 * pdf);
+        }
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        // Integrate over the hemisphere facing the quad on a grid in (cos theta, phi)
+        let quad = unit_square();
+        let origin = Point::new(0.5, 0.5, 0.5);
+        let steps = 1000;
+        let step = |i: usize| (i as f64 + 0.5) / steps as f64;
+        let integral: f64 = (0..steps).flat_map(|i| (0..steps).map(move |j| (i, j))).map(|(i, j)| {
+            let (cos_theta, phi) = (step(i), 2.0 * PI * step(j));
+            let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
+            let direction = UnitVector::from(Vector::new(sin_theta * phi.cos(), sin_theta * phi.sin(), -cos_theta));
+            quad.pdf(origin, direction)
+        }).sum::<f64>() * 2.0 * PI / (steps * steps) as f64;
+        assert!((integral - 1.0).abs() < 0.01, "integral {}", integral);
+    }
+
+    #[test]
+    fn surface_samples_face_outwards() {
+        let quad = Quad::new(Point::new(1.0, 0.0, 0.0), Vector::new(0.0, 2.0, 0.0), Vector::new(0.0, 0.0, 3.0));
+        for _ in 0..100 {
+            let (hit, pdf) = quad.sample_surface(&mut rand::thread_rng()).unwrap();
+            assert!((hit.point.x - 1.0).abs() < 1e-12);
+            assert!(hit.front_face);
+            assert_eq!(hit.geometric_normal.to_vector(), Vector::new(1.0, 0.0, 0.0));
+            assert_eq!(pdf, 1.0 / 6.0);
+        }
+    }
+}
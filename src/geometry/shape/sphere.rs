@@ -1,5 +1,10 @@
 use super::*;
+use crate::{
+    geometry::{IntervalBounds, uniform_sphere},
+    random::RandomStream,
+};
 
+use core::f64::consts::PI;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Sphere {
@@ -15,10 +20,51 @@ impl Sphere {
             radius,
         }
     }
+
+    /// Given a `Point` on the sphere, return the unit normal pointing out of the sphere
+    pub fn outwards_normal(&self, point: Point) -> UnitVector {
+        UnitVector::from(point - self.center)
+    }
+
+    /// Given a `Point` on the sphere, return its texture coordinates:
+    /// `u` is the angle around the y axis (starting from -x), and
+    /// `v` the angle from the bottom (-y) to the top (+y) of the sphere
+    pub fn texture_coordinates(&self, point: Point) -> TextureCoordinates {
+        let n = self.outwards_normal(point);
+        TextureCoordinates::new(
+            (f64::atan2(-n.z, n.x) + PI) / (2.0 * PI),
+            f64::acos(-n.y) / PI,
+        )
+    }
+
+    /// The rates of change with u and v of the point with outwards normal `n`, as
+    /// parameterised by `texture_coordinates`, except at the poles where they're undefined
+    fn derivatives(&self, n: UnitVector) -> Option<(Vector, Vector)> {
+        // The radius of the circle of latitude through the point, on the unit sphere
+        let latitude_radius = n.x.hypot(n.z);
+        if latitude_radius < 1e-12 {
+            return None
+        }
+        let dpdu = 2.0 * PI * self.radius * Vector::new(n.z, 0.0, -n.x);
+        let dpdv = PI * self.radius * Vector::new(
+            -n.x * n.y / latitude_radius,
+            latitude_radius,
+            -n.z * n.y / latitude_radius,
+        );
+        Some((dpdu, dpdv))
+    }
+
+    /// The density per unit solid angle at `origin` of sampling
+    /// the point of `hit` uniformly by area over the sphere
+    fn area_pdf(&self, origin: Point, hit: HitRecord) -> f64 {
+        let offset = hit.point - origin;
+        let cosine = offset.normalise().dot(hit.geometric_normal.to_vector()).abs();
+        offset.l2_norm_squared() / (cosine * 4.0 * PI * self.radius.powi(2))
+    }
 }
 
 impl Shape for Sphere {
-    fn intersection(&self, ray: Ray, time_interval: Interval) -> Option<f64> {
+    fn intersection(&self, ray: Ray, time_interval: Interval) -> Option<HitRecord> {
         let oc = self.center - ray.origin;
         let a = ray.direction.l2_norm_squared();
         let h = ray.direction.dot(oc);
@@ -28,21 +74,73 @@ impl Shape for Sphere {
             return None
         }
         let discriminant_sqrt = discriminant.sqrt();
-        [-1.0, 1.0].into_iter()
+        let t = [-1.0, 1.0].into_iter()
             .map(|s| (h + s * discriminant_sqrt) / a)
-            .filter(|t| time_interval.contains(*t))
-            .next()
+            .find(|t| time_interval.contains(*t))?;
+        let point = ray.at(t);
+        let n = self.outwards_normal(point);
+        let hit = HitRecord::new(
+            ray,
+            t,
+            n,
+            self.texture_coordinates(point),
+            Vector::new(n.z, 0.0, -n.x),
+        );
+        Some(match self.derivatives(n) {
+            Some((dpdu, dpdv)) => hit.with_derivatives(dpdu, dpdv),
+            None => hit,
+        })
     }
 
-    fn outwards_normal(&self, point: Point) -> UnitVector {
-        UnitVector::from(point - self.center)
+    /// From outside, samples the cone of directions subtended by the sphere uniformly,
+    /// and from inside, samples the sphere uniformly by area
+    fn sample(&self, origin: Point, rng: &mut dyn RandomStream) -> Option<(HitRecord, f64)> {
+        let axis = self.center - origin;
+        let sin2_max = self.radius.powi(2) / axis.l2_norm_squared();
+        let window = Interval::positive_reals(IntervalBounds::Open);
+        if sin2_max >= 1.0 {
+            let point = self.center + self.radius * uniform_sphere(rng.next_uniform(), rng.next_uniform());
+            let hit = self.intersection(Ray::from_two_points(origin, point), window)?;
+            return Some((hit, self.area_pdf(origin, hit)))
+        }
+        let cos_max = (1.0 - sin2_max).sqrt();
+        let cos_theta = 1.0 - rng.next_uniform() * sin2_max / (1.0 + cos_max);
+        let sin_theta = (1.0 - cos_theta.powi(2)).max(0.0).sqrt();
+        let phi = 2.0 * PI * rng.next_uniform();
+        let w = UnitVector::from(axis);
+        let (u, v) = w.orthonormal_basis();
+        let direction = UnitVector::from(sin_theta * (phi.cos() * u + phi.sin() * v) + cos_theta * w);
+        let hit = self.intersection(Ray::new(origin, direction), window)?;
+        Some((hit, cone_pdf(sin2_max, cos_max)))
     }
+
+    fn pdf(&self, origin: Point, direction: UnitVector) -> f64 {
+        let sin2_max = self.radius.powi(2) / (self.center - origin).l2_norm_squared();
+        match self.intersection(Ray::new(origin, direction), Interval::positive_reals(IntervalBounds::Open)) {
+            None => 0.0,
+            Some(hit) if sin2_max >= 1.0 => self.area_pdf(origin, hit),
+            Some(_) => cone_pdf(sin2_max, (1.0 - sin2_max).sqrt()),
+        }
+    }
+
+    fn sample_surface(&self, rng: &mut dyn RandomStream) -> Option<(HitRecord, f64)> {
+        let normal = uniform_sphere(rng.next_uniform(), rng.next_uniform());
+        let point = self.center + self.radius * normal;
+        let ray = Ray::new(point + normal, UnitVector::from(-1.0 * normal));
+        let hit = self.intersection(ray, Interval::new(0.5, 1.5, IntervalBounds::Open))?;
+        Some((hit, 1.0 / (4.0 * PI * self.radius.powi(2))))
+    }
+}
+
+/// The density of directions uniform over a cone of half-angle theta, given
+/// sin^2 theta and cos theta (avoiding cancellation for narrow cones)
+fn cone_pdf(sin2_max: f64, cos_max: f64) -> f64 {
+    1.0 / (2.0 * PI * sin2_max / (1.0 + cos_max))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::geometry::{Vector, IntervalBounds};
 
     #[test]
     #[should_panic(expected = "assertion failed: radius > 0.0")]
@@ -77,7 +175,7 @@ mod tests {
             1.0,
         );
         assert_eq!(
-            sphere.intersection(ray, Interval::positive_reals(IntervalBounds::Open)),
+            sphere.intersection(ray, Interval::positive_reals(IntervalBounds::Open)).map(|hit| hit.t),
             Some(2.0)
         );
     }
@@ -93,7 +191,7 @@ mod tests {
             1.0,
         );
         assert_eq!(
-            sphere.intersection(ray, Interval::positive_reals(IntervalBounds::Open)),
+            sphere.intersection(ray, Interval::positive_reals(IntervalBounds::Open)).map(|hit| hit.t),
             Some(1.0)
         );
     }
@@ -109,7 +207,7 @@ mod tests {
             1.0,
         );
         let window = Interval::new(1.0, 3.0, IntervalBounds::LeftOpenRightClosed);
-        assert_eq!(sphere.intersection(ray, window), Some(3.0));
+        assert_eq!(sphere.intersection(ray, window).map(|hit| hit.t), Some(3.0));
     }
 
     #[test]
@@ -134,4 +232,102 @@ mod tests {
         assert_eq!(sphere.outwards_normal(point).dot(e2), 0.0);
         assert_eq!(sphere.outwards_normal(point).dot(e3), 0.0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn texture_coordinates_at_poles_and_equator() {
+        let sphere = Sphere::new(
+            Point::new(0.0, 1.0, 0.0),
+            2.0,
+        );
+        assert_eq!(sphere.texture_coordinates(Point::new(0.0, -1.0, 0.0)).v, 0.0);
+        assert_eq!(sphere.texture_coordinates(Point::new(0.0, 3.0, 0.0)).v, 1.0);
+        let uv = sphere.texture_coordinates(Point::new(2.0, 1.0, 0.0));
+        assert_eq!(uv, TextureCoordinates::new(0.5, 0.5));
+    }
+
+    #[test]
+    fn hit_from_inside_is_back_face() {
+        let ray = Ray {
+            origin: Point::new(0.0, 0.0, 0.0),
+            direction: UnitVector::from(Vector::new(0.0, 0.0, 1.0)),
+        };
+        let sphere = Sphere::new(
+            Point::new(0.0, 0.0, 0.0),
+            2.0,
+        );
+        let hit = sphere.intersection(ray, Interval::positive_reals(IntervalBounds::Open)).unwrap();
+        assert!(!hit.front_face);
+        assert_eq!(hit.point, Point::new(0.0, 0.0, 2.0));
+        assert_eq!(hit.rebound_normal().to_vector(), Vector::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn tangent_frame_follows_texture_coordinates() {
+        let ray = Ray {
+            origin: Point::new(3.0, 0.0, 0.0),
+            direction: UnitVector::from(Vector::new(-1.0, 0.0, 0.0)),
+        };
+        let sphere = Sphere::new(
+            Point::new(0.0, 0.0, 0.0),
+            1.0,
+        );
+        let hit = sphere.intersection(ray, Interval::positive_reals(IntervalBounds::Open)).unwrap();
+        assert!(hit.front_face);
+        assert_eq!(hit.tangent.to_vector(), Vector::new(0.0, 0.0, -1.0));
+        assert_eq!(hit.bitangent.to_vector(), Vector::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn derivatives_follow_texture_coordinates() {
+        let sphere = Sphere::new(Point::new(1.0, 0.0, 0.0), 2.0);
+        let ray = Ray::new(Point::new(1.5, 0.7, 5.0), UnitVector::from(Vector::new(0.0, 0.0, -1.0)));
+        let hit = sphere.intersection(ray, Interval::positive_reals(IntervalBounds::Open)).unwrap();
+        let step = 1e-6;
+        for (derivative, du, dv) in [(hit.dpdu, step, 0.0), (hit.dpdv, 0.0, step)] {
+            let uv = sphere.texture_coordinates(hit.point + step * derivative);
+            assert!((uv.u - hit.uv.u - du).abs() < 1e-9 && (uv.v - hit.uv.v - dv).abs() < 1e-9);
+        }
+    }
+
+    /// Integrates the sampling density over all directions from `origin`
+    fn integrate_pdf(sphere: &Sphere, origin: Point) -> f64 {
+        let steps = 1000;
+        let step = |i: usize| (i as f64 + 0.5) / steps as f64;
+        (0..steps).flat_map(|i| (0..steps).map(move |j| (i, j))).map(|(i, j)| {
+            let (z, phi) = (2.0 * step(i) - 1.0, 2.0 * PI * step(j));
+            let r = (1.0 - z.powi(2)).sqrt();
+            sphere.pdf(origin, UnitVector::from(Vector::new(r * phi.cos(), r * phi.sin(), z)))
+        }).sum::<f64>() * 4.0 * PI / (steps * steps) as f64
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        let sphere = Sphere::new(Point::new(0.0, 0.0, 2.0), 1.0);
+        assert!((integrate_pdf(&sphere, Point::zero()) - 1.0).abs() < 0.01);
+        assert!((integrate_pdf(&sphere, Point::new(0.3, 0.0, 2.2)) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn samples_are_visible_with_matching_pdf() {
+        let sphere = Sphere::new(Point::new(0.0, 0.0, 3.0), 0.5);
+        let origin = Point::new(0.1, 0.2, 0.0);
+        for _ in 0..100 {
+            let (hit, pdf) = sphere.sample(origin, &mut rand::thread_rng()).unwrap();
+            assert!(hit.front_face);
+            assert!(((hit.point - sphere.center).l2_norm() - 0.5).abs() < 1e-9);
+            let direction = UnitVector::from(hit.point - origin);
+            assert!((sphere.pdf(origin, direction) - pdf).abs() < 1e-9 * pdf);
+        }
+    }
+
+    #[test]
+    fn surface_samples_face_outwards() {
+        let sphere = Sphere::new(Point::new(1.0, 2.0, 3.0), 0.1);
+        for _ in 0..100 {
+            let (hit, pdf) = sphere.sample_surface(&mut rand::thread_rng()).unwrap();
+            assert!(((hit.point - Point::new(1.0, 2.0, 3.0)).l2_norm() - 0.1).abs() < 1e-12);
+            assert!(hit.front_face);
+            assert!((pdf - 1.0 / (0.04 * PI)).abs() < 1e-9);
+        }
+    }
+}
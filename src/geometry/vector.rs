@@ -1,7 +1,7 @@
+
 use core::f64::consts::PI;
 use std::{
     cmp::Ordering,
-    convert::identity,
     iter::Sum,
     ops::{Add, Deref, Div, Mul, Sub}
 };
@@ -166,7 +166,7 @@ impl Sub<f64> for Vector {
     type Output = Self;
 
     fn sub(self, rhs: f64) -> Self::Output {
-        self + rhs * -1.0
+        self + -rhs
     }
 }
 
@@ -262,15 +262,13 @@ impl PartialOrd for Vector {
     fn le(&self, other: &Self) -> bool {
         [self.x, self.y, self.z].into_iter()
             .zip([other.x, other.y, other.z])
-            .map(|(s, o)| s <= o)
-            .all(identity)
+            .all(|(s, o)| s <= o)
     }
     
     fn ge(&self, other: &Self) -> bool {
         [self.x, self.y, self.z].into_iter()
             .zip([other.x, other.y, other.z])
-            .map(|(s, o)| s >= o)
-            .all(identity)
+            .all(|(s, o)| s >= o)
     }    
 }
 
@@ -373,6 +371,28 @@ impl UnitVector {
         let rot = 2.0 * PI * rand::random::<f64>();
         Self::new(incline, rot)
     }
+
+    /// Returns two unit vectors which, together with self, form a
+    /// right-handed orthonormal basis
+    /// # Example
+    /// ```
+    /// use ray_tracing::{Vector, UnitVector};
+    /// let n = UnitVector::from(Vector::new(1.0, 2.0, 3.0));
+    /// let (t, b) = n.orthonormal_basis();
+    /// assert!(t.dot(n.to_vector()).abs() < 1e-12);
+    /// assert!(b.dot(n.to_vector()).abs() < 1e-12);
+    /// assert!((t.cross(b.to_vector()) - n.to_vector()).l2_norm() < 1e-12);
+    /// ```
+    pub fn orthonormal_basis(self) -> (UnitVector, UnitVector) {
+        // Duff et al. (2017) "Building an Orthonormal Basis, Revisited"
+        let n = self.0;
+        let sign = 1.0_f64.copysign(n.z);
+        let a = -1.0 / (sign + n.z);
+        let b = n.x * n.y * a;
+        let tangent = Vector::new(1.0 + sign * n.x * n.x * a, sign * b, -sign * n.x);
+        let bitangent = Vector::new(b, sign + n.y * n.y * a, -n.y);
+        (Self(tangent), Self(bitangent))
+    }
 }
 
 
@@ -624,7 +644,7 @@ mod tests {
     #[test]
     fn vector_eq_vector() {
         let a = Vector::new(1.0, 2.0, 3.0);
-        let b = a.clone();
+        let b = a;
         assert_eq!(a, b);
     }
 
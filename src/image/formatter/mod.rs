@@ -1,9 +1,21 @@
 pub mod ppm;
+pub mod png;
 
 use crate::image::Image;
 
+use std::io;
+
 pub trait ImageFormatter {
     fn get_bytes(&mut self, image: Image) -> impl Iterator<Item = Vec<u8>>;
 
     fn len(&self, image: &Image) -> u64;
 }
+
+/// The inverse of an `ImageFormatter`, parsing an encoded image
+pub trait ImageDecoder {
+    fn decode(&self, bytes: &[u8]) -> io::Result<Image<'static>>;
+}
+
+pub(crate) fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
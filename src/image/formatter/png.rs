@@ -0,0 +1,287 @@
+use crate::image::{
+    Pixel,
+    Image,
+    formatter::{ImageDecoder, invalid_data},
+};
+
+use std::io;
+
+const SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+/// Decodes non-interlaced PNGs of any colour type and bit depth.
+/// Any alpha channel is discarded, and 16 bit samples are truncated to 8 bits
+pub struct PNGDecoder;
+
+impl PNGDecoder {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for PNGDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Header {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    colour_type: u8,
+}
+
+impl Header {
+    fn channels(&self) -> io::Result<usize> {
+        let (channels, valid_depths): (usize, &[u8]) = match self.colour_type {
+            0 => (1, &[1, 2, 4, 8, 16]),
+            2 => (3, &[8, 16]),
+            3 => (1, &[1, 2, 4, 8]),
+            4 => (2, &[8, 16]),
+            6 => (4, &[8, 16]),
+            _ => return Err(invalid_data("PNG has an unknown colour type")),
+        };
+        if !valid_depths.contains(&self.bit_depth) {
+            return Err(invalid_data("PNG has an invalid bit depth for its colour type"))
+        }
+        Ok(channels)
+    }
+}
+
+impl ImageDecoder for PNGDecoder {
+    fn decode(&self, bytes: &[u8]) -> io::Result<Image<'static>> {
+        if bytes.get(..8) != Some(&SIGNATURE[..]) {
+            return Err(invalid_data("missing PNG signature"))
+        }
+        let mut header = None;
+        let mut palette = Vec::new();
+        let mut compressed = Vec::new();
+        let mut cursor = 8;
+        loop {
+            let (chunk_type, data) = next_chunk(bytes, &mut cursor)?;
+            match chunk_type {
+                b"IHDR" => header = Some(parse_header(data)?),
+                b"PLTE" => palette = data.chunks_exact(3).map(|c| Pixel::new(c[0], c[1], c[2])).collect(),
+                b"IDAT" => compressed.extend_from_slice(data),
+                b"IEND" => break,
+                _ => {},
+            }
+        }
+        let header = header.ok_or_else(|| invalid_data("PNG has no IHDR chunk"))?;
+        let channels = header.channels()?;
+        let (height, width) = match (u16::try_from(header.height), u16::try_from(header.width)) {
+            (Ok(h), Ok(w)) => (h, w),
+            _ => return Err(invalid_data("PNG is too large")),
+        };
+        let raw = miniz_oxide::inflate::decompress_to_vec_zlib(&compressed)
+            .map_err(|_| invalid_data("PNG image data is corrupt"))?;
+        let bits_per_pixel = channels * header.bit_depth as usize;
+        let stride = (width as usize * bits_per_pixel).div_ceil(8);
+        let scanlines = unfilter(&raw, height as usize, stride, bits_per_pixel.div_ceil(8))?;
+        // Sub-byte greyscale samples are scaled up to the full range
+        let grey_scale = match header.bit_depth {
+            1 | 2 | 4 => 255 / ((1 << header.bit_depth) - 1),
+            _ => 1,
+        };
+        let mut pixels = Vec::with_capacity(height as usize * width as usize);
+        for scanline in scanlines.chunks_exact(stride) {
+            let samples = unpack(scanline, header.bit_depth, width as usize * channels);
+            for sample in samples.chunks_exact(channels) {
+                pixels.push(match header.colour_type {
+                    0 | 4 => {
+                        let grey = sample[0] * grey_scale;
+                        Pixel::new(grey, grey, grey)
+                    },
+                    3 => *palette.get(sample[0] as usize)
+                        .ok_or_else(|| invalid_data("PNG palette index out of range"))?,
+                    _ => Pixel::new(sample[0], sample[1], sample[2]),
+                });
+            }
+        }
+        Ok(Image::from_vec(height, width, pixels))
+    }
+}
+
+/// Returns the type and data of the chunk at `cursor`, advancing past it
+fn next_chunk<'a>(bytes: &'a [u8], cursor: &mut usize) -> io::Result<(&'a [u8], &'a [u8])> {
+    let truncated = || invalid_data("PNG ended unexpectedly");
+    let length = bytes.get(*cursor..*cursor + 4).ok_or_else(truncated)?;
+    let length = u32::from_be_bytes([length[0], length[1], length[2], length[3]]) as usize;
+    let body = bytes.get(*cursor + 4..*cursor + 8 + length).ok_or_else(truncated)?;
+    let crc = bytes.get(*cursor + 8 + length..*cursor + 12 + length).ok_or_else(truncated)?;
+    if crc32(body).to_be_bytes() != crc {
+        return Err(invalid_data("PNG chunk has an invalid CRC"))
+    }
+    *cursor += 12 + length;
+    Ok((&body[..4], &body[4..]))
+}
+
+fn parse_header(data: &[u8]) -> io::Result<Header> {
+    if data.len() != 13 {
+        return Err(invalid_data("PNG IHDR chunk has the wrong length"))
+    }
+    if data[12] != 0 {
+        return Err(io::Error::new(io::ErrorKind::Unsupported, "interlaced PNGs are not supported"))
+    }
+    Ok(Header {
+        width: u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+        height: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+        bit_depth: data[8],
+        colour_type: data[9],
+    })
+}
+
+/// Reverses the per-scanline filters, returning the concatenated scanlines
+fn unfilter(raw: &[u8], height: usize, stride: usize, pixel_bytes: usize) -> io::Result<Vec<u8>> {
+    if raw.len() != height * (stride + 1) {
+        return Err(invalid_data("PNG image data has the wrong length"))
+    }
+    let mut out = vec![0; height * stride];
+    for row in 0..height {
+        let filter = raw[row * (stride + 1)];
+        let line = &raw[row * (stride + 1) + 1..(row + 1) * (stride + 1)];
+        for i in 0..stride {
+            let left = if i >= pixel_bytes { out[row * stride + i - pixel_bytes] } else { 0 };
+            let up = if row > 0 { out[(row - 1) * stride + i] } else { 0 };
+            let up_left = if row > 0 && i >= pixel_bytes { out[(row - 1) * stride + i - pixel_bytes] } else { 0 };
+            let predictor = match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => return Err(invalid_data("PNG scanline has an unknown filter")),
+            };
+            out[row * stride + i] = line[i].wrapping_add(predictor);
+        }
+    }
+    Ok(out)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Splits a scanline into `count` samples, each widened (or truncated) to 8 bits
+fn unpack(scanline: &[u8], bit_depth: u8, count: usize) -> Vec<u8> {
+    match bit_depth {
+        8 => scanline[..count].to_vec(),
+        16 => scanline.chunks_exact(2).take(count).map(|s| s[0]).collect(),
+        _ => {
+            let per_byte = 8 / bit_depth as usize;
+            let mask = (1u8 << bit_depth) - 1;
+            (0..count)
+                .map(|i| {
+                    let shift = 8 - bit_depth as usize * (i % per_byte + 1);
+                    (scanline[i / per_byte] >> shift) & mask
+                })
+                .collect()
+        },
+    }
+}
+
+/// The CRC-32 used by PNG chunks (ISO 3309)
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(u32::MAX, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u32, |c, _| {
+            if c & 1 == 1 {
+                0xedb88320 ^ (c >> 1)
+            } else {
+                c >> 1
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(chunk_type: &[u8], data: &[u8]) -> Vec<u8> {
+        let body = [chunk_type, data].concat();
+        [&(data.len() as u32).to_be_bytes()[..], &body, &crc32(&body).to_be_bytes()].concat()
+    }
+
+    fn png(width: u32, height: u32, bit_depth: u8, colour_type: u8, extra: &[u8], raw: &[u8]) -> Vec<u8> {
+        let header = [&width.to_be_bytes()[..], &height.to_be_bytes(), &[bit_depth, colour_type, 0, 0, 0]].concat();
+        [
+            &SIGNATURE[..],
+            &chunk(b"IHDR", &header),
+            extra,
+            &chunk(b"IDAT", &miniz_oxide::deflate::compress_to_vec_zlib(raw, 6)),
+            &chunk(b"IEND", &[]),
+        ].concat()
+    }
+
+    #[test]
+    fn crc_of_iend() {
+        assert_eq!(crc32(b"IEND"), 0xae426082);
+    }
+
+    #[test]
+    fn decode_rgb_with_filters() {
+        // Row 0 is unfiltered, row 1 uses the Up filter and row 2 the Sub filter
+        let raw = [
+            0, 10, 20, 30, 40, 50, 60,
+            2, 1, 1, 1, 1, 1, 1,
+            1, 5, 5, 5, 1, 2, 3,
+        ];
+        let pixels = PNGDecoder::new().decode(&png(2, 3, 8, 2, &[], &raw)).unwrap().collect();
+        assert_eq!(pixels, vec![
+            Pixel::new(10, 20, 30), Pixel::new(40, 50, 60),
+            Pixel::new(11, 21, 31), Pixel::new(41, 51, 61),
+            Pixel::new(5, 5, 5), Pixel::new(6, 7, 8),
+        ]);
+    }
+
+    #[test]
+    fn decode_paeth_and_average_filters() {
+        let raw = [
+            0, 100, 200,
+            3, 10, 20,
+            4, 0, 0,
+        ];
+        let pixels = PNGDecoder::new().decode(&png(2, 3, 8, 0, &[], &raw)).unwrap().collect();
+        let grey = |g| Pixel::new(g, g, g);
+        assert_eq!(pixels, vec![grey(100), grey(200), grey(60), grey(150), grey(60), grey(150)]);
+    }
+
+    #[test]
+    fn decode_low_bit_depth_palette() {
+        let palette = chunk(b"PLTE", &[255, 0, 0, 0, 255, 0, 0, 0, 255]);
+        let raw = [0, 0b00_01_10_00];
+        let pixels = PNGDecoder::new().decode(&png(4, 1, 2, 3, &palette, &raw)).unwrap().collect();
+        assert_eq!(pixels, vec![
+            Pixel::new(255, 0, 0), Pixel::new(0, 255, 0), Pixel::new(0, 0, 255), Pixel::new(255, 0, 0),
+        ]);
+    }
+
+    #[test]
+    fn decode_one_bit_greyscale() {
+        let raw = [0, 0b1010_0000];
+        let pixels = PNGDecoder::new().decode(&png(3, 1, 1, 0, &[], &raw)).unwrap().collect();
+        assert_eq!(pixels, vec![Pixel::new(255, 255, 255), Pixel::black(), Pixel::new(255, 255, 255)]);
+    }
+
+    #[test]
+    fn decode_rgba_discards_alpha() {
+        let raw = [0, 1, 2, 3, 4];
+        let pixels = PNGDecoder::new().decode(&png(1, 1, 8, 6, &[], &raw)).unwrap().collect();
+        assert_eq!(pixels, vec![Pixel::new(1, 2, 3)]);
+    }
+
+    #[test]
+    fn corrupt_crc_fails() {
+        let mut bytes = png(1, 1, 8, 0, &[], &[0, 0]);
+        bytes[30] ^= 1;
+        assert_eq!(PNGDecoder::new().decode(&bytes).err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -1,10 +1,10 @@
 use crate::image::{
     Pixel,
     Image,
-    formatter::ImageFormatter,
+    formatter::{ImageFormatter, ImageDecoder, invalid_data},
 };
 
-use std::iter;
+use std::{io, iter};
 
 pub struct PPMFormatter {
     ascii_mode: bool,
@@ -53,6 +53,75 @@ impl ImageFormatter for PPMFormatter {
     
 }
 
+impl ImageDecoder for PPMFormatter {
+    /// Decodes both ASCII (P3) and binary (P6) PPMs, regardless of `ascii_mode`
+    fn decode(&self, bytes: &[u8]) -> io::Result<Image<'static>> {
+        let mut cursor = 0;
+        let magic_number = next_token(bytes, &mut cursor)?;
+        let ascii_mode = match magic_number {
+            b"P3" => true,
+            b"P6" => false,
+            _ => return Err(invalid_data("not a P3 or P6 PPM")),
+        };
+        let width = parse_number(next_token(bytes, &mut cursor)?)?;
+        let height = parse_number(next_token(bytes, &mut cursor)?)?;
+        let max_value = parse_number(next_token(bytes, &mut cursor)?)?;
+        if max_value == 0 {
+            return Err(invalid_data("PPM maximum value must be positive"))
+        }
+        let count = 3 * width as usize * height as usize;
+        let samples: Vec<u16> = if ascii_mode {
+            (0..count)
+                .map(|_| parse_number(next_token(bytes, &mut cursor)?))
+                .collect::<io::Result<_>>()?
+        } else {
+            // Exactly one whitespace byte separates the header from the raster
+            let raster = bytes.get(cursor + 1..).unwrap_or_default();
+            if max_value < 256 {
+                raster.iter().take(count).map(|b| *b as u16).collect()
+            } else {
+                raster.chunks_exact(2).take(count).map(|b| u16::from_be_bytes([b[0], b[1]])).collect()
+            }
+        };
+        if samples.len() != count {
+            return Err(invalid_data("PPM raster is truncated"))
+        }
+        let scale = |sample: u16| (sample.min(max_value) as u32 * 255 / max_value as u32) as u8;
+        let pixels = samples
+            .chunks_exact(3)
+            .map(|rgb| Pixel::new(scale(rgb[0]), scale(rgb[1]), scale(rgb[2])))
+            .collect();
+        Ok(Image::from_vec(height, width, pixels))
+    }
+}
+
+/// Returns the next whitespace separated token at or after `cursor`, skipping
+/// comments, and leaves `cursor` on the byte immediately after the token
+fn next_token<'a>(bytes: &'a [u8], cursor: &mut usize) -> io::Result<&'a [u8]> {
+    loop {
+        match bytes.get(*cursor) {
+            Some(b'#') => while bytes.get(*cursor).is_some_and(|b| *b != b'\n') {
+                *cursor += 1;
+            },
+            Some(b) if b.is_ascii_whitespace() => *cursor += 1,
+            Some(_) => break,
+            None => return Err(invalid_data("PPM ended unexpectedly")),
+        }
+    }
+    let start = *cursor;
+    while bytes.get(*cursor).is_some_and(|b| !b.is_ascii_whitespace()) {
+        *cursor += 1;
+    }
+    Ok(&bytes[start..*cursor])
+}
+
+fn parse_number(token: &[u8]) -> io::Result<u16> {
+    std::str::from_utf8(token)
+        .ok()
+        .and_then(|t| t.parse().ok())
+        .ok_or_else(|| invalid_data("PPM contains an invalid number"))
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -75,6 +144,29 @@ mod tests {
         assert_eq!(header, Some(b"P3\n2 1\n255\n".to_vec()));
     }
 
+    #[test]
+    fn decode_ascii_with_comments() {
+        let bytes = b"P3\n# a comment\n2 1 # trailing\n15\n15 0 0\n0 15 5\n";
+        let pixels = PPMFormatter::new(true).decode(bytes).unwrap().collect();
+        assert_eq!(pixels, vec![Pixel::new(255, 0, 0), Pixel::new(0, 255, 85)]);
+    }
+
+    #[test]
+    fn decode_round_trips_binary_mode() {
+        let colour = |col, row| Pixel::new(col as u8, row as u8, 200);
+        let mut f = PPMFormatter::new(false);
+        let bytes: Vec<u8> = f.get_bytes(Image::from_pixels(2, 3, &colour)).flatten().collect();
+        let decoded = f.decode(&bytes).unwrap();
+        assert_eq!((decoded.height, decoded.width), (2, 3));
+        assert_eq!(decoded.collect(), Image::from_pixels(2, 3, &colour).collect());
+    }
+
+    #[test]
+    fn decode_truncated_fails() {
+        let error = PPMFormatter::new(false).decode(b"P6\n2 2\n255\n\x00\x00").err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
     #[test]
     fn ascii_mode_ends_with_newline() {
         let mut f = PPMFormatter::new(true);
@@ -0,0 +1,260 @@
+use crate::{
+    geometry::Vector,
+    image::formatter::invalid_data,
+};
+
+use std::{fs, io, path::Path};
+
+/// A high dynamic range image of linear radiance values, as used for
+/// environment maps, stored row by row from the top-left
+#[derive(Debug, Clone, PartialEq)]
+pub struct HdrImage {
+    pub width: usize,
+    pub height: usize,
+    pub texels: Vec<Vector>,
+}
+
+impl HdrImage {
+    pub fn new(width: usize, height: usize, texels: Vec<Vector>) -> Self {
+        assert_eq!(texels.len(), width * height);
+        Self {
+            width,
+            height,
+            texels,
+        }
+    }
+
+    /// Load the image from a Radiance RGBE (.hdr) or Portable FloatMap (.pfm) file
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        let bytes = fs::read(path)?;
+        match extension.as_deref() {
+            Some("hdr") => Self::decode_rgbe(&bytes),
+            Some("pfm") => Self::decode_pfm(&bytes),
+            _ => Err(io::Error::new(io::ErrorKind::Unsupported, "unrecognised HDR image file extension")),
+        }
+    }
+
+    pub fn texel(&self, x: usize, y: usize) -> Vector {
+        self.texels[y * self.width + x]
+    }
+
+    /// Decodes a Radiance RGBE image, with flat or (new-style) run-length encoded
+    /// scanlines, in the standard "-Y height +X width" orientation
+    pub fn decode_rgbe(bytes: &[u8]) -> io::Result<Self> {
+        let mut lines = bytes.split(|b| *b == b'\n');
+        let mut cursor = 0;
+        let mut next_line = || {
+            let line = lines.next().ok_or_else(|| invalid_data("Radiance header ended unexpectedly"))?;
+            cursor += line.len() + 1;
+            Ok::<_, io::Error>(String::from_utf8_lossy(line).into_owned())
+        };
+        if !next_line()?.starts_with("#?") {
+            return Err(invalid_data("missing Radiance signature"))
+        }
+        loop {
+            let line = next_line()?;
+            if line.is_empty() {
+                break
+            }
+            if line.starts_with("FORMAT=") && line != "FORMAT=32-bit_rle_rgbe" {
+                return Err(io::Error::new(io::ErrorKind::Unsupported, "only RGBE Radiance images are supported"))
+            }
+        }
+        let resolution = next_line()?;
+        let (height, width) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+            ["-Y", height, "+X", width] => match (height.parse::<usize>(), width.parse::<usize>()) {
+                (Ok(height), Ok(width)) => (height, width),
+                _ => return Err(invalid_data("Radiance image has an invalid resolution")),
+            },
+            _ => return Err(io::Error::new(io::ErrorKind::Unsupported, "unsupported Radiance image orientation")),
+        };
+        let count = width.checked_mul(height).ok_or_else(|| invalid_data("Radiance image is too large"))?;
+        let mut data = bytes.get(cursor..).unwrap_or_default();
+        // Run-length encoding packs at most 127 pixels into two bytes per channel, which bounds
+        // the pixels the data can hold, however many the header claims
+        let mut texels = Vec::with_capacity(count.min(data.len().saturating_mul(127) / 8));
+        for _ in 0..height {
+            let scanline = read_scanline(&mut data, width)?;
+            texels.extend(scanline.chunks_exact(4).map(|rgbe| {
+                if rgbe[3] == 0 {
+                    return Vector::zero()
+                }
+                let scale = 2f64.powi(rgbe[3] as i32 - 136);
+                Vector::new(rgbe[0] as f64, rgbe[1] as f64, rgbe[2] as f64) * scale
+            }));
+        }
+        Ok(Self::new(width, height, texels))
+    }
+
+    /// Decodes a colour ("PF") or greyscale ("Pf") Portable FloatMap
+    pub fn decode_pfm(bytes: &[u8]) -> io::Result<Self> {
+        // The header is the magic number, width, height and scale separated
+        // by whitespace, and ended by a single whitespace character
+        let mut tokens = Vec::new();
+        let mut cursor = 0;
+        while tokens.len() < 4 {
+            while bytes.get(cursor).is_some_and(u8::is_ascii_whitespace) {
+                cursor += 1;
+            }
+            let start = cursor;
+            while bytes.get(cursor).is_some_and(|b| !b.is_ascii_whitespace()) {
+                cursor += 1;
+            }
+            if start == cursor {
+                return Err(invalid_data("PFM header ended unexpectedly"))
+            }
+            tokens.push(String::from_utf8_lossy(&bytes[start..cursor]).into_owned());
+        }
+        cursor += 1;
+        let channels = match tokens[0].as_str() {
+            "PF" => 3,
+            "Pf" => 1,
+            _ => return Err(invalid_data("missing PFM signature")),
+        };
+        let parse = |token: &str| token.parse::<usize>().map_err(|_| invalid_data("PFM has an invalid size"));
+        let (width, height) = (parse(&tokens[1])?, parse(&tokens[2])?);
+        let scale: f64 = tokens[3].parse().map_err(|_| invalid_data("PFM has an invalid scale"))?;
+        if width == 0 || height == 0 {
+            return Err(invalid_data("PFM has an invalid size"))
+        }
+        let end = width.checked_mul(height)
+            .and_then(|count| count.checked_mul(4 * channels))
+            .and_then(|size| size.checked_add(cursor))
+            .ok_or_else(|| invalid_data("PFM is too large"))?;
+        let data = bytes.get(cursor..end).ok_or_else(|| invalid_data("PFM ended unexpectedly"))?;
+        let samples: Vec<f64> = data
+            .chunks_exact(4)
+            .map(|b| {
+                let b = [b[0], b[1], b[2], b[3]];
+                let sample = if scale < 0.0 { f32::from_le_bytes(b) } else { f32::from_be_bytes(b) };
+                sample as f64
+            })
+            .collect();
+        // Rows are stored from the bottom up
+        let texels = samples
+            .chunks_exact(channels * width)
+            .rev()
+            .flat_map(|row| row.chunks_exact(channels))
+            .map(|c| if channels == 3 { Vector::new(c[0], c[1], c[2]) } else { Vector::new(c[0], c[0], c[0]) })
+            .collect();
+        Ok(Self::new(width, height, texels))
+    }
+}
+
+/// Reads the RGBE bytes of a scanline of `width` pixels from the start of `data`, advancing past it
+fn read_scanline(data: &mut &[u8], width: usize) -> io::Result<Vec<u8>> {
+    let truncated = || invalid_data("Radiance image data ended unexpectedly");
+    let header = data.get(..4).ok_or_else(truncated)?;
+    let run_length_encoded = (8..0x8000).contains(&width)
+        && header[0] == 2 && header[1] == 2 && header[2] & 0x80 == 0;
+    if !run_length_encoded {
+        let size = width.checked_mul(4).ok_or_else(truncated)?;
+        let scanline = data.get(..size).ok_or_else(truncated)?.to_vec();
+        *data = &data[size..];
+        return Ok(scanline)
+    }
+    if ((header[2] as usize) << 8 | header[3] as usize) != width {
+        return Err(invalid_data("Radiance scanline has the wrong width"))
+    }
+    *data = &data[4..];
+    // Each channel is encoded separately, as runs and literal spans
+    let mut channels: Vec<Vec<u8>> = (0..4).map(|_| Vec::with_capacity(width)).collect();
+    for channel in channels.iter_mut() {
+        while channel.len() < width {
+            let count = *data.first().ok_or_else(truncated)? as usize;
+            if count > 128 {
+                let value = *data.get(1).ok_or_else(truncated)?;
+                channel.extend(std::iter::repeat_n(value, count - 128));
+                *data = &data[2..];
+            } else {
+                if count == 0 {
+                    return Err(invalid_data("Radiance scanline has an empty span"))
+                }
+                channel.extend_from_slice(data.get(1..1 + count).ok_or_else(truncated)?);
+                *data = &data[1 + count..];
+            }
+        }
+        if channel.len() != width {
+            return Err(invalid_data("Radiance scanline overruns its width"))
+        }
+    }
+    Ok((0..width).flat_map(|i| channels.iter().map(move |c| c[i])).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgbe_header(width: usize, height: usize) -> Vec<u8> {
+        format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", height, width).into_bytes()
+    }
+
+    #[test]
+    fn decode_flat_rgbe() {
+        // 128 in the mantissa with exponent 129 is 1.0
+        let bytes = [rgbe_header(2, 1), vec![128, 64, 0, 129, 0, 0, 0, 0]].concat();
+        let image = HdrImage::decode_rgbe(&bytes).unwrap();
+        assert_eq!(image.texels, vec![Vector::new(1.0, 0.5, 0.0), Vector::zero()]);
+    }
+
+    #[test]
+    fn decode_run_length_encoded_rgbe() {
+        let width = 8;
+        let scanline = [
+            vec![2, 2, 0, width as u8],
+            // Red: a run of 8
+            vec![128 + 8, 128],
+            // Green: a literal span of 8
+            vec![8, 0, 16, 32, 48, 64, 80, 96, 112],
+            // Blue: two runs of 4
+            vec![128 + 4, 0, 128 + 4, 64],
+            // Exponent: a run of 8
+            vec![128 + 8, 130],
+        ].concat();
+        let bytes = [rgbe_header(width, 1), scanline].concat();
+        let image = HdrImage::decode_rgbe(&bytes).unwrap();
+        assert_eq!(image.texel(0, 0), Vector::new(2.0, 0.0, 0.0));
+        assert_eq!(image.texel(7, 0), Vector::new(2.0, 1.75, 1.0));
+    }
+
+    #[test]
+    fn decode_pfm_both_endiannesses() {
+        let floats = [1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let little: Vec<u8> = floats.iter().flat_map(|f| f.to_le_bytes()).collect();
+        let big: Vec<u8> = floats.iter().flat_map(|f| f.to_be_bytes()).collect();
+        for (scale, data) in [("-1.0", little), ("1.0", big)] {
+            let bytes = [format!("PF\n1 2\n{}\n", scale).into_bytes(), data].concat();
+            let image = HdrImage::decode_pfm(&bytes).unwrap();
+            // The bottom row comes first
+            assert_eq!(image.texels, vec![Vector::new(4.0, 5.0, 6.0), Vector::new(1.0, 2.0, 3.0)]);
+        }
+    }
+
+    #[test]
+    fn decode_greyscale_pfm() {
+        let bytes = [b"Pf 1 1 -1\n".to_vec(), 0.25f32.to_le_bytes().to_vec()].concat();
+        assert_eq!(HdrImage::decode_pfm(&bytes).unwrap().texels, vec![Vector::new(0.25, 0.25, 0.25)]);
+    }
+
+    #[test]
+    fn truncated_pfm_fails() {
+        let bytes = b"PF\n2 2\n-1\n\0\0\0\0".to_vec();
+        assert_eq!(HdrImage::decode_pfm(&bytes).err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn oversized_headers_fail() {
+        let rgbe = [rgbe_header(usize::MAX, 2), vec![0; 8]].concat();
+        assert_eq!(HdrImage::decode_rgbe(&rgbe).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        let rgbe = [rgbe_header(1 << 20, 1 << 20), vec![0; 8]].concat();
+        assert_eq!(HdrImage::decode_rgbe(&rgbe).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        let pfm = format!("PF\n{} {}\n-1\n\0\0\0\0", usize::MAX, 3).into_bytes();
+        assert_eq!(HdrImage::decode_pfm(&pfm).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        let pfm = b"PF\n0 2\n-1\n".to_vec();
+        assert_eq!(HdrImage::decode_pfm(&pfm).err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -1,4 +1,5 @@
 pub mod formatter;
+pub mod hdr;
 
 use formatter::ImageFormatter;
 
@@ -32,6 +33,19 @@ impl Pixel {
     }
 }
 
+impl From<Pixel> for Vector {
+    /// Represent the Pixel as a Vector with elements between 0.0 and 1.0
+    /// # Example
+    /// ```
+    /// use ray_tracing::{Pixel, Vector};
+    /// let v = Vector::from(Pixel::new(255, 0, 51));
+    /// assert_eq!(v, Vector::new(1.0, 0.0, 0.2));
+    /// ```
+    fn from(value: Pixel) -> Self {
+        Vector::new(value.red as f64, value.green as f64, value.blue as f64) / 255.0
+    }
+}
+
 impl From<Pixel> for u32 {
     /// Represent the Pixel as a u32 by storing the 3
     /// bytes consecutively (with a leading zero byte)
@@ -63,6 +77,16 @@ pub struct Image<'a> {
 
 
 impl<'a> Image<'a> {
+    /// Create an `Image` from its `Pixel`s, listed row by row from the top-left
+    pub fn from_vec(height: u16, width: u16, pixels: Vec<Pixel>) -> Self {
+        assert_eq!(pixels.len(), height as usize * width as usize);
+        Self {
+            height,
+            width,
+            pixels: Box::new(pixels.into_iter()),
+        }
+    }
+
     /// Create an `Image` from a colour generator closure
     /// `colour`'s first argument is the column `Pixel` index (i.e. in the horizontal direction)
     /// `colour`'s second argument is the row `Pixel` index (i.e. in the vertical direction)
@@ -0,0 +1,104 @@
+use super::*;
+use crate::{
+    geometry::{Frame, UnitVector},
+    surface::cosine_hemisphere,
+};
+
+/// Renders every surface as white clay lit evenly from all around, so that only
+/// the occlusion of each first hit by nearby geometry is seen. Rays finding
+/// nothing are white
+pub struct AmbientOcclusion {
+    // Geometry further than this from a hit doesn't occlude it
+    max_distance: f64,
+    // The number of occlusion rays cast from each hit
+    samples: u32,
+}
+
+impl AmbientOcclusion {
+    /// Create an ambient occlusion integrator casting a single occlusion ray from each
+    /// hit, which counts as occluded if it meets a surface within `max_distance`
+    pub fn new(max_distance: f64) -> Self {
+        Self {
+            max_distance,
+            samples: 1,
+        }
+    }
+
+    /// Cast `samples` occlusion rays from each hit, for less noise per camera ray
+    pub fn with_samples(self, samples: u32) -> Self {
+        assert!(samples > 0);
+        Self {
+            samples,
+            ..self
+        }
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn radiance(&self, world: &SurfaceSet, ray: Ray, _wavelength: Option<f64>, rng: &mut dyn RandomStream) -> Vector {
+        let hit = match world.intersection(ray, Interval::new(0.001, f64::MAX, IntervalBounds::Open), rng) {
+            Some(intersection) => intersection.hit,
+            None => return Vector::new(1.0, 1.0, 1.0),
+        };
+        // Cosine-weighted directions make the fraction of unoccluded rays an estimate
+        // of the cosine-weighted visibility, without weighting each ray
+        let frame = Frame::from_normal(hit.rebound_normal());
+        let reach = Interval::new(0.001, self.max_distance, IntervalBounds::Open);
+        let unoccluded = (0..self.samples)
+            .filter(|_| {
+                let direction = UnitVector::from(frame.to_world(cosine_hemisphere(rng.next_uniform(), rng.next_uniform())));
+                world.intersection(Ray::new(hit.point, direction), reach, rng).is_none()
+            })
+            .count();
+        let visibility = unoccluded as f64 / self.samples as f64;
+        Vector::new(visibility, visibility, visibility)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        geometry::{Point, shape::quad::Quad},
+        surface::{UniformSurface, lambertian::Lambertian},
+    };
+
+    fn floor() -> Box<UniformSurface<Quad, Lambertian<Vector>>> {
+        Box::new(UniformSurface::new(
+            Quad::new(Point::new(-50.0, -50.0, 0.0), Vector::new(100.0, 0.0, 0.0), Vector::new(0.0, 100.0, 0.0)),
+            Lambertian::new(Vector::new(0.5, 0.5, 0.5)),
+        ))
+    }
+
+    #[test]
+    fn open_floor_is_unoccluded() {
+        let mut world = SurfaceSet::new();
+        world.add(floor());
+        let ray = Ray::new(Point::new(0.0, 0.0, 1.0), UnitVector::from(Vector::new(0.0, 0.0, -1.0)));
+        let integrator = AmbientOcclusion::new(10.0).with_samples(16);
+        assert_eq!(integrator.radiance(&world, ray, None, &mut rand::thread_rng()), Vector::new(1.0, 1.0, 1.0));
+        let up = Ray::new(Point::new(0.0, 0.0, 1.0), UnitVector::from(Vector::new(0.0, 0.0, 1.0)));
+        assert_eq!(integrator.radiance(&world, up, None, &mut rand::thread_rng()), Vector::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn ceiling_occludes_within_max_distance() {
+        // A strip of ceiling at height 1 and half-width 1 blocks the directions within
+        // 45 degrees of upright across it. Cosine-weighted directions project uniformly onto
+        // the unit disk, and those blocked onto an ellipse covering 1 / sqrt 2 of it
+        let mut world = SurfaceSet::new();
+        world.add(floor());
+        world.add(Box::new(UniformSurface::new(
+            Quad::new(Point::new(-1.0, -50.0, 1.0), Vector::new(0.0, 100.0, 0.0), Vector::new(2.0, 0.0, 0.0)),
+            Lambertian::new(Vector::new(0.5, 0.5, 0.5)),
+        )));
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.5), UnitVector::from(Vector::new(0.0, 0.0, -1.0)));
+        let integrator = AmbientOcclusion::new(100.0).with_samples(20000);
+        let visibility = integrator.radiance(&world, ray, None, &mut rand::thread_rng()).x;
+        let expected = 1.0 - core::f64::consts::FRAC_1_SQRT_2;
+        assert!((visibility - expected).abs() < 0.02, "{} vs {}", visibility, expected);
+        // Too far away to count
+        let integrator = AmbientOcclusion::new(0.5).with_samples(16);
+        assert_eq!(integrator.radiance(&world, ray, None, &mut rand::thread_rng()), Vector::new(1.0, 1.0, 1.0));
+    }
+}
@@ -0,0 +1,135 @@
+use super::*;
+
+use core::f64::consts::PI;
+
+/// An arbitrary output variable: a property of the first surface seen along a ray
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aov {
+    /// The shading normal, mapped from [-1, 1] to [0, 1] in each axis
+    Normal,
+    /// The distance `t` to the surface, mapped to `t / (1 + t)` so that
+    /// nearby surfaces are dark and the background is white
+    Depth,
+    /// The colour of the surface, whatever the light: the fraction of light it scatters
+    /// when lit and seen head on, such as its texture or base colour
+    Albedo,
+    /// The position of the hit, as the fractional part of each coordinate,
+    /// which tiles space with unit cubes of colour
+    Position,
+    /// A colour identifying the surface by its position in the `SurfaceSet`
+    SurfaceId,
+}
+
+/// Renders an `Aov` in place of the light, to see what the camera rays find.
+/// Rays which find nothing are black, except for `Aov::Depth`
+pub struct AovIntegrator {
+    aov: Aov,
+}
+
+impl AovIntegrator {
+    pub fn new(aov: Aov) -> Self {
+        Self {
+            aov,
+        }
+    }
+}
+
+impl Integrator for AovIntegrator {
+    fn radiance(&self, world: &SurfaceSet, ray: Ray, wavelength: Option<f64>, rng: &mut dyn RandomStream) -> Vector {
+        let intersection = match world.intersection(ray, Interval::new(0.001, f64::MAX, IntervalBounds::Open), rng) {
+            Some(intersection) => intersection,
+            None if self.aov == Aov::Depth => return Vector::new(1.0, 1.0, 1.0),
+            None => return Vector::zero(),
+        };
+        let hit = &intersection.hit;
+        match self.aov {
+            Aov::Normal => (hit.shading_normal.to_vector() + 1.0) / 2.0,
+            Aov::Depth => {
+                let depth = hit.t / (1.0 + hit.t);
+                Vector::new(depth, depth, depth)
+            },
+            Aov::Albedo => intersection.surfaces[0].albedo(hit, wavelength),
+            Aov::Position => hit.point.map(|c| c - c.floor()),
+            Aov::SurfaceId => id_colour(intersection.index),
+        }
+    }
+
+    fn gamma_correct(&self) -> bool {
+        false
+    }
+}
+
+/// A bright colour for `id`, with the hues of consecutive ids far apart
+fn id_colour(id: usize) -> Vector {
+    // Stepping by the golden ratio spreads the hues evenly around the colour wheel
+    let hue = (id as f64 * 0.618_033_988_749_895).fract();
+    Vector::new(hue, hue + 1.0 / 3.0, hue + 2.0 / 3.0).map(|c| 0.5 + 0.5 * (2.0 * PI * c).cos())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        geometry::{Point, UnitVector, shape::{quad::Quad, sphere::Sphere}},
+        surface::{UniformSurface, lambertian::Lambertian, metal::Metal, principled::Principled, microfacet::RoughConductor},
+    };
+
+    fn world() -> SurfaceSet {
+        let mut world = SurfaceSet::new();
+        world.add(Box::new(UniformSurface::new(
+            Quad::new(Point::new(-5.0, -5.0, -2.0), Vector::new(10.0, 0.0, 0.0), Vector::new(0.0, 10.0, 0.0)),
+            Lambertian::new(Vector::new(0.2, 0.4, 0.6)),
+        )));
+        world.add(Box::new(UniformSurface::new(
+            Sphere::new(Point::new(3.0, 0.0, 0.0), 1.0),
+            Metal::new(Vector::new(0.9, 0.5, 0.1)),
+        )));
+        world
+    }
+
+    fn radiance(aov: Aov, ray: Ray) -> Vector {
+        AovIntegrator::new(aov).radiance(&world(), ray, None, &mut rand::thread_rng())
+    }
+
+    #[test]
+    fn aovs_of_floor() {
+        let down = Ray::new(Point::new(0.25, 0.5, 1.0), UnitVector::from(Vector::new(0.0, 0.0, -1.0)));
+        assert_eq!(radiance(Aov::Normal, down), Vector::new(0.5, 0.5, 1.0));
+        assert_eq!(radiance(Aov::Depth, down), Vector::new(0.75, 0.75, 0.75));
+        assert_eq!(radiance(Aov::Albedo, down), Vector::new(0.2, 0.4, 0.6));
+        assert_eq!(radiance(Aov::Position, down), Vector::new(0.25, 0.5, 0.0));
+        assert_eq!(radiance(Aov::SurfaceId, down), id_colour(0));
+    }
+
+    #[test]
+    fn aovs_of_sphere_and_background() {
+        let across = Ray::new(Point::zero(), UnitVector::from(Vector::new(1.0, 0.0, 0.0)));
+        assert_eq!(radiance(Aov::Normal, across), Vector::new(0.0, 0.5, 0.5));
+        assert_eq!(radiance(Aov::Albedo, across), Vector::new(0.9, 0.5, 0.1));
+        assert_eq!(radiance(Aov::SurfaceId, across), id_colour(1));
+        assert_ne!(id_colour(0), id_colour(1));
+        let up = Ray::new(Point::zero(), UnitVector::from(Vector::new(0.0, 0.0, 1.0)));
+        assert_eq!(radiance(Aov::Depth, up), Vector::new(1.0, 1.0, 1.0));
+        assert_eq!(radiance(Aov::SurfaceId, up), Vector::zero());
+    }
+
+    #[test]
+    fn albedo_is_the_colour_of_rough_materials() {
+        // Rays scattered by these materials are attenuated by varying amounts,
+        // but the albedo is their colour alone
+        let down = Ray::new(Point::new(0.0, 0.0, 1.0), UnitVector::from(Vector::new(0.0, 0.0, -1.0)));
+        let floor = || Quad::new(Point::new(-5.0, -5.0, 0.0), Vector::new(10.0, 0.0, 0.0), Vector::new(0.0, 10.0, 0.0));
+        let colour = Vector::new(0.8, 0.3, 0.2);
+        let surfaces: [Box<dyn Surface>; 2] = [
+            Box::new(UniformSurface::new(floor(), Principled::new(colour).with_clearcoat(1.0))),
+            Box::new(UniformSurface::new(floor(), RoughConductor::from_f0(colour, 0.7))),
+        ];
+        for surface in surfaces {
+            let mut world = SurfaceSet::new();
+            world.add(surface);
+            for _ in 0..10 {
+                assert_eq!(AovIntegrator::new(Aov::Albedo).radiance(&world, down, None, &mut rand::thread_rng()), colour);
+            }
+        }
+    }
+}
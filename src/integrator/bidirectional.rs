@@ -0,0 +1,544 @@
+use super::*;
+use crate::geometry::{Point, UnitVector};
+
+/// A bidirectional path tracer. Each camera ray begins a path, and a path is traced from
+/// a random light too, and every vertex of each is joined to every vertex of the other
+/// (as well as to the camera and to a sampled point on a light), with all the ways of
+/// finding a path combined by multiple importance sampling. Paths are ended by Russian roulette
+///
+/// Joining light paths to the camera lights pixels other than the one being rendered, so
+/// is only possible through `camera_radiance`. Without it, those paths are left to the others
+pub struct BidirectionalPathTracer {
+    // The number of bounces along each path before Russian roulette starts
+    min_bounces: u32,
+}
+
+impl BidirectionalPathTracer {
+    /// Create a bidirectional path tracer which follows every path from the camera
+    /// and from the lights for at least `min_bounces` bounces
+    pub fn new(min_bounces: u32) -> Self {
+        Self {
+            min_bounces,
+        }
+    }
+
+    /// Follows `ray` through the `world`, recording every surface it hits, until it
+    /// is absorbed or ended by Russian roulette. If it escapes the world, also
+    /// returns its direction and throughput
+    fn trace<'a>(&self, world: &'a SurfaceSet, ray: Ray, wavelength: Option<f64>, rng: &mut dyn RandomStream) -> (Vec<Vertex<'a>>, Option<(UnitVector, Vector)>) {
+        let mut vertices = Vec::new();
+        let mut ray = ray;
+        let mut throughput = Vector::new(1.0, 1.0, 1.0);
+        loop {
+            let intersection = match world.intersection(ray, Interval::new(0.001, f64::MAX, IntervalBounds::Open), rng) {
+                Some(intersection) => intersection,
+                None => return (vertices, Some((ray.direction, throughput))),
+            };
+            let surface = intersection.surfaces[0];
+            let hit = intersection.hit;
+            let scattered_ray = surface.scatter(&hit, ray, wavelength, rng);
+            let delta = scattered_ray.is_some_and(|sr| surface.pdf(&hit, ray, sr.ray.direction, wavelength) <= 0.0);
+            vertices.push(Vertex {
+                surface,
+                hit,
+                ray,
+                wavelength,
+                throughput,
+                delta,
+            });
+            let scattered_ray = match scattered_ray {
+                Some(sr) => sr,
+                None => return (vertices, None),
+            };
+            throughput = throughput * scattered_ray.attenuation;
+            ray = scattered_ray.ray;
+            if vertices.len() >= self.min_bounces as usize {
+                throughput = match russian_roulette(throughput, rng) {
+                    Some(throughput) => throughput,
+                    None => return (vertices, None),
+                };
+            }
+        }
+    }
+
+    /// Traces a path from a random light, returning the point it leaves the light from and the
+    /// vertices it goes on to scatter at, whose throughputs are the radiance arriving at them
+    fn trace_light<'a>(&self, world: &'a SurfaceSet, wavelength: Option<f64>, rng: &mut dyn RandomStream) -> Option<(PathPoint<'a>, Vec<Vertex<'a>>)> {
+        let (light, selection_probability) = world.sample_light(rng)?;
+        let emission = light.sample_emission(rng)?;
+        let pdf = selection_probability * emission.position_pdf * emission.direction_pdf;
+        if pdf <= 0.0 || emission.radiance == 0.0 {
+            return None
+        }
+        let cosine = emission.normal.map_or(1.0, |n| n.dot(emission.ray.direction.to_vector()).abs());
+        let weight = cosine / pdf * at_wavelength(emission.radiance, wavelength);
+        let origin = PathPoint {
+            location: Location::Finite(emission.ray.origin),
+            normal: emission.normal,
+            kind: Kind::Emitter(Some(light)),
+        };
+        let (mut vertices, _) = self.trace(world, emission.ray, wavelength, rng);
+        for vertex in vertices.iter_mut() {
+            vertex.throughput = vertex.throughput * weight;
+        }
+        Some((origin, vertices))
+    }
+
+    /// The light of `wavelength` along `ray`, and (given the `camera`) splats of light paths
+    /// joined to the camera
+    fn estimate(&self, world: &SurfaceSet, camera: Option<&Camera>, ray: Ray, splats: &mut Vec<Splat>,
+        wavelength: Option<f64>, rng: &mut dyn RandomStream) -> Vector
+    {
+        let (camera_path, escaped) = self.trace(world, ray, wavelength, rng);
+        let mut camera_points = vec![PathPoint::camera(ray.origin)];
+        camera_points.extend(camera_path.iter().map(Vertex::point));
+        let light_path = self.trace_light(world, wavelength, rng);
+        let light_points: Vec<PathPoint> = light_path.iter()
+            .flat_map(|(origin, vertices)| iter::once(*origin).chain(vertices.iter().map(Vertex::point)))
+            .collect();
+        let mut radiance = Vector::zero();
+        for (i, vertex) in camera_path.iter().enumerate() {
+            // The camera, and the vertices up to this one
+            let camera_points = &camera_points[..i + 2];
+            let emitted = vertex.surface.emitted(&vertex.hit, vertex.ray, wavelength);
+            if emitted != 0.0 {
+                let mut path = camera_points.to_vec();
+                path[i + 1].kind = Kind::Emitter(vertex.surface.light());
+                radiance = radiance + mis_weight(world, camera, &path, path.len()) * vertex.throughput * emitted;
+            }
+            if vertex.delta {
+                continue
+            }
+            radiance = radiance + connect_to_light(world, camera, camera_points, rng);
+            for j in 2..=light_points.len() {
+                radiance = radiance + connect(world, camera, camera_points, &light_points[..j], rng);
+            }
+        }
+        if let Some((direction, throughput)) = escaped {
+            let background = world.background();
+            let mut path = camera_points;
+            path.push(PathPoint {
+                location: Location::Infinite(direction),
+                normal: None,
+                kind: Kind::Emitter(background.light()),
+            });
+            let background_radiance = at_wavelength(background.radiance(direction), wavelength);
+            radiance = radiance + mis_weight(world, camera, &path, path.len()) * throughput * background_radiance;
+        }
+        if let Some(camera) = camera {
+            for j in 2..=light_points.len() {
+                splats.extend(connect_to_camera(world, camera, &light_points[..j], rng));
+            }
+        }
+        radiance
+    }
+}
+
+impl Default for BidirectionalPathTracer {
+    fn default() -> Self {
+        Self::new(3)
+    }
+}
+
+impl Integrator for BidirectionalPathTracer {
+    fn radiance(&self, world: &SurfaceSet, ray: Ray, wavelength: Option<f64>, rng: &mut dyn RandomStream) -> Vector {
+        self.estimate(world, None, ray, &mut Vec::new(), wavelength, rng)
+    }
+
+    fn camera_radiance(&self, world: &SurfaceSet, camera: &Camera, ray: Ray, splats: &mut Vec<Splat>,
+        wavelength: Option<f64>, rng: &mut dyn RandomStream) -> Vector
+    {
+        self.estimate(world, Some(camera), ray, splats, wavelength, rng)
+    }
+}
+
+/// A point at which a path from the camera or a light scatters
+struct Vertex<'a> {
+    surface: &'a dyn Surface,
+    hit: HitRecord,
+    // The ray which found the vertex
+    ray: Ray,
+    // The wavelength of the light traced along the path, if any
+    wavelength: Option<f64>,
+    // For paths from the camera, the fraction of the light leaving the vertex which reaches the
+    // camera. For paths from lights, the radiance arriving at the vertex along the ray (with
+    // both divided by the density of the path so far)
+    throughput: Vector,
+    // True iff the path was scattered into a single direction, which
+    // couldn't have been found by joining the vertex to another
+    delta: bool,
+}
+
+impl Vertex<'_> {
+    fn point(&self) -> PathPoint<'_> {
+        PathPoint {
+            location: Location::Finite(self.hit.point),
+            normal: (!self.surface.is_medium()).then_some(self.hit.geometric_normal),
+            kind: Kind::Scattering(self),
+        }
+    }
+}
+
+/// A vertex of a complete path from the camera to a light, with
+/// what determines the densities of sampling the path through it
+#[derive(Clone, Copy)]
+struct PathPoint<'a> {
+    location: Location,
+    // The normal to the surface at the point, if any, which foreshortens light arriving
+    normal: Option<UnitVector>,
+    kind: Kind<'a>,
+}
+
+#[derive(Clone, Copy)]
+enum Location {
+    Finite(Point),
+    /// Infinitely far away in a direction, as for the background
+    Infinite(UnitVector),
+}
+
+#[derive(Clone, Copy)]
+enum Kind<'a> {
+    Camera,
+    Scattering(&'a Vertex<'a>),
+    /// The end of the path, which emits light (and can be sampled, if it is a `Light`)
+    Emitter(Option<&'a dyn Light>),
+}
+
+impl PathPoint<'_> {
+    fn camera(eye_point: Point) -> Self {
+        Self {
+            location: Location::Finite(eye_point),
+            normal: None,
+            kind: Kind::Camera,
+        }
+    }
+
+    fn is_delta(&self) -> bool {
+        matches!(self.kind, Kind::Scattering(vertex) if vertex.delta)
+    }
+
+    fn is_delta_light(&self) -> bool {
+        matches!(self.kind, Kind::Emitter(Some(light)) if light.is_delta())
+    }
+}
+
+/// The direction from `from` to `to`
+fn direction(from: &PathPoint, to: &PathPoint) -> UnitVector {
+    match (from.location, to.location) {
+        (_, Location::Infinite(direction)) => direction,
+        (Location::Infinite(direction), _) => UnitVector::from(-1.0 * direction),
+        (Location::Finite(origin), Location::Finite(destination)) => UnitVector::from(destination - origin),
+    }
+}
+
+/// Converts the density `pdf` of the direction from `from` to `to`, per unit solid angle,
+/// to the density of `to` per unit area. Points at infinity keep densities per solid angle
+fn area_density(pdf: f64, from: &PathPoint, to: &PathPoint) -> f64 {
+    match (from.location, to.location) {
+        (Location::Finite(origin), Location::Finite(destination)) => {
+            let offset = destination - origin;
+            let cosine = to.normal.map_or(1.0, |n| n.dot(offset.normalise()).abs());
+            pdf * cosine / offset.l2_norm_squared()
+        },
+        _ => pdf,
+    }
+}
+
+/// The density with which a path arriving at `point` from `previous` goes on to `next`
+fn continuation_pdf(previous: &PathPoint, point: &PathPoint, next: &PathPoint) -> f64 {
+    let outgoing = direction(point, next);
+    let pdf = match point.kind {
+        // Scattering into a single direction has no finite density, but as every way of
+        // finding such a path must scatter through the vertex, the densities can be taken as one
+        Kind::Scattering(vertex) if vertex.delta => return 1.0,
+        Kind::Scattering(vertex) => {
+            // Seen from `previous`, the hit may be on the other side of the surface
+            let incoming = direction(previous, point);
+            let hit = HitRecord {
+                front_face: incoming.dot(vertex.hit.geometric_normal.to_vector()) < 0.0,
+                ..vertex.hit
+            };
+            vertex.surface.pdf(&hit, Ray::new(hit.point, incoming), outgoing, vertex.wavelength)
+        },
+        Kind::Emitter(Some(light)) => match point.location {
+            Location::Finite(position) => light.emission_pdf(position, point.normal, outgoing),
+            Location::Infinite(_) => 0.0,
+        },
+        Kind::Emitter(None) | Kind::Camera => 0.0,
+    };
+    area_density(pdf, point, next)
+}
+
+/// The density with which the light at the end of the path, `light_point`, is sampled from `point`
+fn light_sampling_pdf(world: &SurfaceSet, point: &PathPoint, light_point: &PathPoint) -> f64 {
+    let light = match light_point.kind {
+        Kind::Emitter(Some(light)) => light,
+        _ => return 0.0,
+    };
+    let selection_probability = world.light_selection_probability();
+    // A delta light is found with certainty once chosen, but as every way of finding
+    // a path from it must choose it, the density can be taken as one
+    if light.is_delta() {
+        return selection_probability
+    }
+    match point.location {
+        Location::Finite(position) => {
+            let pdf = light.pdf(position, direction(point, light_point));
+            selection_probability * area_density(pdf, point, light_point)
+        },
+        Location::Infinite(_) => 0.0,
+    }
+}
+
+/// The weight, by the power heuristic, of the `path` (from the camera to a light) found
+/// by joining a path of its first `t` points (from the camera) to a path of the rest.
+/// Joining light paths directly to the camera (`t` = 1) is only possible given the `camera`
+fn mis_weight(world: &SurfaceSet, camera: Option<&Camera>, path: &[PathPoint], t: usize) -> f64 {
+    let n = path.len() - 1;
+    // The densities of each point being found by a path from the camera and by one from the light
+    let camera_pdf = |i: usize| match (i, camera) {
+        (1, Some(camera)) => area_density(camera.direction_pdf(direction(&path[0], &path[1])), &path[0], &path[1]),
+        (1, None) => 0.0,
+        _ if i == n && path[n].is_delta_light() => 0.0,
+        _ => continuation_pdf(&path[i - 2], &path[i - 1], &path[i]),
+    };
+    let light_pdf = |i: usize| if i == n {
+        light_sampling_pdf(world, &path[n - 1], &path[n])
+    } else {
+        continuation_pdf(&path[(i + 2).min(n)], &path[i + 1], &path[i])
+    };
+    // Whether a path can be found by joining its first `t` points to the rest
+    let first_t = if camera.is_some() { 1 } else { 2 };
+    let possible = |t: usize| match t {
+        // There's no sampling points on lights to join to the camera
+        1 if n < 2 => false,
+        _ if t == n + 1 => true,
+        _ => !path[t - 1].is_delta() && !path[t].is_delta(),
+    };
+    let ratio = |numerator: f64, denominator: f64| if numerator == 0.0 { 0.0 } else { numerator / denominator };
+    // Walk outwards from `t`, keeping the ratio of each alternative's density to that of `t`
+    let mut sum = 1.0;
+    let mut density_ratio = 1.0;
+    for i in (first_t..t).rev() {
+        density_ratio *= ratio(light_pdf(i), camera_pdf(i));
+        if possible(i) {
+            sum += density_ratio.powi(2);
+        }
+    }
+    let mut density_ratio = 1.0;
+    for i in t..=n {
+        density_ratio *= ratio(camera_pdf(i), light_pdf(i));
+        if possible(i + 1) {
+            sum += density_ratio.powi(2);
+        }
+    }
+    1.0 / sum
+}
+
+/// Whether the straight line from `from` to `to` (at `distance`) is unobstructed
+fn unoccluded(world: &SurfaceSet, from: Point, direction: UnitVector, distance: f64, rng: &mut dyn RandomStream) -> bool {
+    let window = Interval::new(0.001, distance - 0.001, IntervalBounds::Open);
+    world.intersection(Ray::new(from, direction), window, rng).is_none()
+}
+
+/// Joins the last of the `camera_points` to a point sampled on a random light
+fn connect_to_light(world: &SurfaceSet, camera: Option<&Camera>, camera_points: &[PathPoint], rng: &mut dyn RandomStream) -> Vector {
+    let vertex = match camera_points.last().map(|point| point.kind) {
+        Some(Kind::Scattering(vertex)) => vertex,
+        _ => return Vector::zero(),
+    };
+    let (light, sample) = match world.sample_light(rng) {
+        Some((light, selection_probability)) => match light.sample(vertex.hit.point, rng) {
+            Some(sample) => (light, LightSample {
+                pdf: selection_probability * sample.pdf,
+                ..sample
+            }),
+            None => return Vector::zero(),
+        },
+        None => return Vector::zero(),
+    };
+    let f = vertex.surface.eval(&vertex.hit, vertex.ray, sample.direction, vertex.wavelength);
+    if f == 0.0 || sample.radiance == 0.0 || sample.pdf <= 0.0 {
+        return Vector::zero()
+    }
+    if !unoccluded(world, vertex.hit.point, sample.direction, sample.distance, rng) {
+        return Vector::zero()
+    }
+    let light_point = if sample.distance.is_infinite() {
+        PathPoint {
+            location: Location::Infinite(sample.direction),
+            normal: None,
+            kind: Kind::Emitter(Some(light)),
+        }
+    } else {
+        // The normal is found by hitting the light, if it's among the surfaces
+        let normal = if light.is_delta() {
+            None
+        } else {
+            let window = Interval::new(sample.distance - 0.001, sample.distance + 0.001, IntervalBounds::Closed);
+            world.intersection(Ray::new(vertex.hit.point, sample.direction), window, rng)
+                .map(|intersection| intersection.hit.geometric_normal)
+        };
+        PathPoint {
+            location: Location::Finite(vertex.hit.point + sample.distance * sample.direction.to_vector()),
+            normal,
+            kind: Kind::Emitter(Some(light)),
+        }
+    };
+    let path: Vec<PathPoint> = camera_points.iter().copied().chain(iter::once(light_point)).collect();
+    let weight = mis_weight(world, camera, &path, camera_points.len());
+    weight / sample.pdf * vertex.throughput * f * at_wavelength(sample.radiance, vertex.wavelength)
+}
+
+/// Joins the last of the `camera_points` to the last of the `light_points`
+fn connect(world: &SurfaceSet, camera: Option<&Camera>, camera_points: &[PathPoint], light_points: &[PathPoint], rng: &mut dyn RandomStream) -> Vector {
+    let (camera_vertex, light_vertex) = match (camera_points.last().map(|p| p.kind), light_points.last().map(|p| p.kind)) {
+        (Some(Kind::Scattering(camera_vertex)), Some(Kind::Scattering(light_vertex))) => (camera_vertex, light_vertex),
+        _ => return Vector::zero(),
+    };
+    if light_vertex.delta {
+        return Vector::zero()
+    }
+    let offset = light_vertex.hit.point - camera_vertex.hit.point;
+    let distance = offset.l2_norm();
+    let direction = UnitVector::from(offset);
+    let camera_f = camera_vertex.surface.eval(&camera_vertex.hit, camera_vertex.ray, direction, camera_vertex.wavelength);
+    let light_f = light_vertex.surface.eval(&light_vertex.hit, light_vertex.ray, UnitVector::from(-1.0 * offset), light_vertex.wavelength);
+    if camera_f == 0.0 || light_f == 0.0 || !unoccluded(world, camera_vertex.hit.point, direction, distance, rng) {
+        return Vector::zero()
+    }
+    let path: Vec<PathPoint> = camera_points.iter().chain(light_points.iter().rev()).copied().collect();
+    let weight = mis_weight(world, camera, &path, camera_points.len());
+    weight / distance.powi(2) * camera_vertex.throughput * camera_f * light_f * light_vertex.throughput
+}
+
+/// Joins the last of the `light_points` to the `camera`, giving the light reaching its pixel
+fn connect_to_camera(world: &SurfaceSet, camera: &Camera, light_points: &[PathPoint], rng: &mut dyn RandomStream) -> Option<Splat> {
+    let light_vertex = match light_points.last().map(|p| p.kind) {
+        Some(Kind::Scattering(light_vertex)) if !light_vertex.delta => light_vertex,
+        _ => return None,
+    };
+    let offset = camera.eye_point() - light_vertex.hit.point;
+    let distance = offset.l2_norm();
+    let towards_camera = UnitVector::from(offset);
+    let view_direction = UnitVector::from(-1.0 * offset);
+    let pixel = camera.pixel(view_direction)?;
+    let f = light_vertex.surface.eval(&light_vertex.hit, light_vertex.ray, towards_camera, light_vertex.wavelength);
+    if f == 0.0 || !unoccluded(world, light_vertex.hit.point, towards_camera, distance, rng) {
+        return None
+    }
+    let path: Vec<PathPoint> = iter::once(PathPoint::camera(camera.eye_point()))
+        .chain(light_points.iter().rev().copied())
+        .collect();
+    let weight = mis_weight(world, Some(camera), &path, 1);
+    // The camera's response to light from the vertex is the density of its rays in that direction
+    let importance = camera.direction_pdf(view_direction) / distance.powi(2);
+    Some(Splat {
+        pixel,
+        radiance: weight * importance * f * light_vertex.throughput,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        geometry::shape::{quad::Quad, sphere::Sphere},
+        surface::{UniformSurface, lambertian::Lambertian, dielectric::Dielectric, diffuse_light::DiffuseLight},
+        light::punctual::PointLight,
+        integrator::path::PathTracer,
+        random::SeededStream,
+    };
+
+    /// A closed grey box spanning [-1, 1] in x and y and [-3, 1] in z
+    fn closed_box() -> SurfaceSet {
+        let mut world = SurfaceSet::new();
+        let grey = || Lambertian::new(Vector::new(0.6, 0.6, 0.6));
+        let walls = [
+            (Point::new(-1.0, -1.0, -3.0), Vector::new(2.0, 0.0, 0.0), Vector::new(0.0, 0.0, 4.0)),
+            (Point::new(-1.0, 1.0, -3.0), Vector::new(2.0, 0.0, 0.0), Vector::new(0.0, 0.0, 4.0)),
+            (Point::new(-1.0, -1.0, -3.0), Vector::new(0.0, 2.0, 0.0), Vector::new(0.0, 0.0, 4.0)),
+            (Point::new(1.0, -1.0, -3.0), Vector::new(0.0, 2.0, 0.0), Vector::new(0.0, 0.0, 4.0)),
+            (Point::new(-1.0, -1.0, -3.0), Vector::new(2.0, 0.0, 0.0), Vector::new(0.0, 2.0, 0.0)),
+            (Point::new(-1.0, -1.0, 1.0), Vector::new(2.0, 0.0, 0.0), Vector::new(0.0, 2.0, 0.0)),
+        ];
+        for (corner, u, v) in walls {
+            world.add(Box::new(UniformSurface::new(Quad::new(corner, u, v), grey())));
+        }
+        world
+    }
+
+    fn ceiling_light() -> Box<dyn Surface> {
+        // Facing down into the box
+        Box::new(UniformSurface::new(
+            Quad::new(Point::new(-0.25, 0.99, -1.75), Vector::new(0.5, 0.0, 0.0), Vector::new(0.0, 0.0, 0.5)),
+            DiffuseLight::new(Vector::new(8.0, 8.0, 8.0)),
+        ))
+    }
+
+    fn assert_matches_path_tracer(world: &SurfaceSet, ray: Ray, samples: usize) {
+        let mean = |integrator: &dyn Integrator| {
+            let rng = &mut SeededStream::new(1);
+            (0..samples).map(|_| integrator.radiance(world, ray, None, rng)).sum::<Vector>() / samples as f64
+        };
+        let expected = mean(&PathTracer::default());
+        let actual = mean(&BidirectionalPathTracer::default());
+        assert!((actual.x - expected.x).abs() < 0.04 * expected.x, "{} vs {}", actual.x, expected.x);
+    }
+
+    #[test]
+    fn matches_path_tracer_under_area_light() {
+        let mut world = closed_box();
+        world.add(ceiling_light());
+        let ray = Ray::new(Point::zero(), UnitVector::from(Vector::new(0.2, -0.5, -1.0)));
+        assert_matches_path_tracer(&world, ray, 20000);
+    }
+
+    #[test]
+    fn matches_path_tracer_under_point_light() {
+        let mut world = closed_box();
+        world.add_light(Box::new(PointLight::new(Point::new(0.3, 0.8, -2.0), Vector::new(2.0, 2.0, 2.0))));
+        let ray = Ray::new(Point::zero(), UnitVector::from(Vector::new(-0.3, -0.5, -1.0)));
+        assert_matches_path_tracer(&world, ray, 20000);
+    }
+
+    #[test]
+    fn matches_path_tracer_through_glass() {
+        let mut world = closed_box();
+        world.add(ceiling_light());
+        world.add(Box::new(UniformSurface::new(Sphere::new(Point::new(0.0, -0.6, -1.8), 0.4), Dielectric::new(1.5))));
+        let ray = Ray::new(Point::zero(), UnitVector::from(Vector::new(0.0, -0.6, -1.8)));
+        assert_matches_path_tracer(&world, ray, 20000);
+    }
+
+    #[test]
+    fn weights_of_possible_strategies_sum_to_one() {
+        let mut world = closed_box();
+        world.add(ceiling_light());
+        world.add(Box::new(UniformSurface::new(Sphere::new(Point::new(0.4, -0.6, -2.0), 0.3), Dielectric::new(1.5))));
+        let camera = Camera::new(40, 30, 4.0, 3.0, 1.5, 0);
+        let integrator = BidirectionalPathTracer::new(8);
+        let mut paths_checked = 0;
+        while paths_checked < 100 {
+            let ray = Ray::new(Point::zero(), UnitVector::from(Vector::new(0.3, -0.4, -1.0)));
+            let (camera_path, _) = integrator.trace(&world, ray, None, &mut rand::thread_rng());
+            // Paths which end on the light, as complete paths
+            let Some(last) = camera_path.iter().position(|vertex| vertex.surface.light().is_some()) else {
+                continue
+            };
+            let mut path = vec![PathPoint::camera(ray.origin)];
+            path.extend(camera_path[..=last].iter().map(Vertex::point));
+            path[last + 1].kind = Kind::Emitter(camera_path[last].surface.light());
+            let n = path.len() - 1;
+            for camera in [None, Some(&camera)] {
+                let first_t = if camera.is_some() { 1 } else { 2 };
+                let total: f64 = (first_t..=n + 1)
+                    .filter(|&t| t == n + 1 || (!path[t - 1].is_delta() && !path[t].is_delta() && (t > 1 || n > 1)))
+                    .map(|t| mis_weight(&world, camera, &path, t))
+                    .sum();
+                assert!((total - 1.0).abs() < 1e-9, "{} for a path of {} points", total, path.len());
+            }
+            paths_checked += 1;
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use super::*;
+use crate::{
+    random::{RandomStream, SeededStream, hash},
+    spectrum::luminance,
+};
+
+use core::f64::consts::PI;
+use std::cell::RefCell;
+
+/// The spread of a small step's change to each primary sample
+const SMALL_STEP_SIGMA: f64 = 0.01;
+
+/// Tell apart the seeds of the bootstrap samples' streams and the chain's own stream
+const BOOTSTRAP_STREAM: u64 = 0;
+const CHAIN_STREAM: u64 = 1;
+
+/// Primary sample space Metropolis light transport (Kelemen et al. 2002) over another
+/// integrator. The random numbers behind the integrator's samples are treated as a point in
+/// a unit hypercube, which a Markov chain explores in proportion to the brightness of the
+/// light found: small steps nudge every number, to stay near paths that found light (say,
+/// through a keyhole), while large steps pick them afresh so no part of the image is missed.
+///
+/// The chain is set up on the first camera ray, by a bootstrap phase of independent samples
+/// which finds the image's total brightness, with randomness chosen by the camera's seed.
+/// Each camera ray then advances the chain by a single mutation, whose light is splatted
+/// wherever it lands, so the image is only made of splats. The chain belongs to the world
+/// and camera it began with, so each `Metropolis` should render a single image
+pub struct Metropolis<I: Integrator> {
+    integrator: I,
+    bootstrap_samples: usize,
+    large_step_probability: f64,
+    chain: RefCell<Option<Chain>>,
+}
+
+impl<I: Integrator> Metropolis<I> {
+    pub fn new(integrator: I) -> Self {
+        Self {
+            integrator,
+            bootstrap_samples: 100_000,
+            large_step_probability: 0.3,
+            chain: RefCell::new(None),
+        }
+    }
+
+    /// Estimate the image's brightness from `bootstrap_samples` samples, in place of 100,000
+    pub fn with_bootstrap_samples(mut self, bootstrap_samples: usize) -> Self {
+        self.bootstrap_samples = bootstrap_samples;
+        self
+    }
+
+    /// Pick every random number afresh with probability `large_step_probability` (by default
+    /// 0.3), rather than nudging them. More large steps explore the image more evenly
+    pub fn with_large_step_probability(mut self, large_step_probability: f64) -> Self {
+        self.large_step_probability = large_step_probability;
+        self
+    }
+
+    /// The light of `wavelength` the integrator finds from a camera ray driven by `samples`
+    fn contribution(&self, world: &SurfaceSet, camera: &Camera, wavelength: Option<f64>, samples: &mut PrimarySamples) -> Contribution {
+        // The first two numbers pick the ray's position in the image
+        let ray = camera.viewport_ray(samples.next_uniform(), samples.next_uniform());
+        let mut splats = Vec::new();
+        let radiance = self.integrator.camera_radiance(world, camera, ray, &mut splats, wavelength, samples);
+        if let Some(pixel) = camera.pixel(ray.direction) {
+            splats.push(Splat { pixel, radiance });
+        }
+        let importance = splats.iter().map(|splat| luminance(self.integrator.pixel_rgb(splat.radiance))).sum::<f64>();
+        Contribution {
+            splats,
+            importance: if importance.is_finite() { importance.max(0.0) } else { 0.0 },
+        }
+    }
+
+    /// Starts the chain at a sample picked from independent samples in proportion to
+    /// their importance, whose mean gives the chain's normalisation
+    fn bootstrap(&self, world: &SurfaceSet, camera: &Camera, wavelength: Option<f64>) -> Chain {
+        let seed = camera.seed();
+        // Each bootstrap sample has its own seed, so the one picked can be made again. Seeds
+        // are hashed, so that renders with nearby seeds share none of their streams
+        let start = |index: usize| {
+            let mut samples = PrimarySamples::new(hash(&[seed, BOOTSTRAP_STREAM, index as u64]));
+            samples.start_iteration(true);
+            (self.contribution(world, camera, wavelength, &mut samples), samples)
+        };
+        let importances: Vec<f64> = (0..self.bootstrap_samples).map(|index| start(index).0.importance).collect();
+        let total: f64 = importances.iter().sum();
+        let mut rng = SeededStream::new(hash(&[seed, CHAIN_STREAM]));
+        let target = rng.next_uniform() * total;
+        let index = importances.iter()
+            .scan(0.0, |sum, importance| {
+                *sum += importance;
+                Some(*sum)
+            })
+            .position(|sum| sum > target)
+            .unwrap_or(0);
+        let (current, mut samples) = start(index);
+        samples.accept();
+        Chain {
+            samples,
+            current,
+            normalisation: total / self.bootstrap_samples.max(1) as f64,
+            rng,
+        }
+    }
+
+    /// Proposes a mutation of the chain's state and accepts or rejects it, splatting
+    /// both states' light in proportion to the chance of each being the next state
+    fn mutate(&self, world: &SurfaceSet, camera: &Camera, wavelength: Option<f64>, chain: Chain, splats: &mut Vec<Splat>) -> Chain {
+        let Chain { mut samples, current, normalisation, mut rng } = chain;
+        samples.start_iteration(rng.next_uniform() < self.large_step_probability);
+        let proposed = self.contribution(world, camera, wavelength, &mut samples);
+        let acceptance = if current.importance > 0.0 {
+            (proposed.importance / current.importance).min(1.0)
+        } else {
+            1.0
+        };
+        // States are visited in proportion to their importance, so their light is divided by it
+        for (state, weight) in [(&proposed, acceptance), (&current, 1.0 - acceptance)] {
+            if weight > 0.0 && state.importance > 0.0 {
+                let scale = weight * normalisation / state.importance;
+                splats.extend(state.splats.iter().map(|splat| Splat {
+                    radiance: splat.radiance * scale,
+                    ..*splat
+                }));
+            }
+        }
+        let current = if rng.next_uniform() < acceptance {
+            samples.accept();
+            proposed
+        } else {
+            samples.reject();
+            current
+        };
+        Chain { samples, current, normalisation, rng }
+    }
+}
+
+impl<I: Integrator> Integrator for Metropolis<I> {
+    /// Without a camera there's no image to explore, so this is the integrator's own sample
+    fn radiance(&self, world: &SurfaceSet, ray: Ray, wavelength: Option<f64>, rng: &mut dyn RandomStream) -> Vector {
+        self.integrator.radiance(world, ray, wavelength, rng)
+    }
+
+    /// Advances the chain by one mutation, whose light is all splatted, so that there are as
+    /// many mutations as camera rays. `ray` and `rng` are unused, as the chain picks its own
+    fn camera_radiance(&self, world: &SurfaceSet, camera: &Camera, _ray: Ray, splats: &mut Vec<Splat>,
+        wavelength: Option<f64>, _rng: &mut dyn RandomStream) -> Vector
+    {
+        let chain = self.chain.take().unwrap_or_else(|| self.bootstrap(world, camera, wavelength));
+        let chain = self.mutate(world, camera, wavelength, chain, splats);
+        self.chain.replace(Some(chain));
+        Vector::zero()
+    }
+
+    fn pixel_rgb(&self, value: Vector) -> Vector {
+        self.integrator.pixel_rgb(value)
+    }
+
+    fn gamma_correct(&self) -> bool {
+        self.integrator.gamma_correct()
+    }
+}
+
+/// The light found by one state of the chain, and its importance (its luminance)
+struct Contribution {
+    splats: Vec<Splat>,
+    importance: f64,
+}
+
+struct Chain {
+    samples: PrimarySamples,
+    current: Contribution,
+    // The mean importance over the whole image
+    normalisation: f64,
+    // Decides between steps, and whether to accept them
+    rng: SeededStream,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PrimarySample {
+    value: f64,
+    // The iteration in which the value last changed
+    last_modified: u64,
+    // The value and its iteration before the current iteration's change, if rejected
+    backup: f64,
+    backup_modified: u64,
+}
+
+/// The chain's state: the random numbers handed out, in order, to the integrator. Numbers
+/// are only mutated as they're used, so paths can use as many as they like. A number left
+/// unused for several iterations makes up for the small steps it missed when next used
+struct PrimarySamples {
+    samples: Vec<PrimarySample>,
+    // The index of the next number to hand out
+    next: usize,
+    iteration: u64,
+    last_large_step: u64,
+    large_step: bool,
+    rng: SeededStream,
+}
+
+impl PrimarySamples {
+    fn new(seed: u64) -> Self {
+        Self {
+            samples: Vec::new(),
+            next: 0,
+            iteration: 0,
+            last_large_step: 0,
+            large_step: true,
+            rng: SeededStream::new(seed),
+        }
+    }
+
+    fn start_iteration(&mut self, large_step: bool) {
+        self.iteration += 1;
+        self.large_step = large_step;
+        self.next = 0;
+    }
+
+    fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step = self.iteration;
+        }
+    }
+
+    /// Restores the numbers changed in this iteration
+    fn reject(&mut self) {
+        for sample in self.samples.iter_mut().filter(|sample| sample.last_modified == self.iteration) {
+            sample.value = sample.backup;
+            sample.last_modified = sample.backup_modified;
+        }
+        self.iteration -= 1;
+    }
+
+    /// A normally distributed change to a number, over `steps` small steps
+    fn small_step(&mut self, steps: u64) -> f64 {
+        let (u1, u2) = (self.rng.next_uniform(), self.rng.next_uniform());
+        let normal = (-2.0 * (1.0 - u1).ln()).sqrt() * (2.0 * PI * u2).cos();
+        normal * SMALL_STEP_SIGMA * (steps as f64).sqrt()
+    }
+}
+
+impl RandomStream for PrimarySamples {
+    fn next_uniform(&mut self) -> f64 {
+        if self.next == self.samples.len() {
+            self.samples.push(PrimarySample { value: 0.0, last_modified: 0, backup: 0.0, backup_modified: 0 });
+        }
+        let index = self.next;
+        self.next += 1;
+        let mut sample = self.samples[index];
+        // A number unused since before the last large step would have been picked afresh by it
+        if sample.last_modified < self.last_large_step {
+            sample.value = self.rng.next_uniform();
+            sample.last_modified = self.last_large_step;
+        }
+        sample.backup = sample.value;
+        sample.backup_modified = sample.last_modified;
+        if self.large_step {
+            sample.value = self.rng.next_uniform();
+        } else {
+            let value = (sample.value + self.small_step(self.iteration - sample.last_modified)).rem_euclid(1.0);
+            // Rounding may wrap a tiny negative value to 1
+            sample.value = if value < 1.0 { value } else { 0.0 };
+        }
+        sample.last_modified = self.iteration;
+        self.samples[index] = sample;
+        sample.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        geometry::{Point, shape::quad::Quad},
+        integrator::path::PathTracer,
+        surface::{UniformSurface, lambertian::Lambertian, diffuse_light::DiffuseLight},
+    };
+
+    #[test]
+    fn rejected_mutations_restore_samples() {
+        let mut samples = PrimarySamples::new(7);
+        samples.start_iteration(true);
+        let first: Vec<f64> = (0..3).map(|_| samples.next_uniform()).collect();
+        samples.accept();
+        for large_step in [false, true] {
+            samples.start_iteration(large_step);
+            // Using more numbers than before adds new ones
+            let mutated: Vec<f64> = (0..4).map(|_| samples.next_uniform()).collect();
+            assert!(mutated.iter().zip(&first).all(|(mutated, first)| mutated != first));
+            if !large_step {
+                assert!(mutated.iter().zip(&first).all(|(mutated, first)| {
+                    let distance = (mutated - first).abs();
+                    distance.min(1.0 - distance) < 10.0 * SMALL_STEP_SIGMA
+                }));
+            }
+            samples.reject();
+            assert_eq!(samples.samples[..3].iter().map(|sample| sample.value).collect::<Vec<_>>(), first);
+        }
+    }
+
+    #[test]
+    fn mean_matches_path_tracer() {
+        // A coloured floor seen from above, lit from over a low wall beside it
+        let mut world = SurfaceSet::new();
+        world.add(Box::new(UniformSurface::new(
+            Quad::new(Point::new(-2.0, -2.0, -2.0), Vector::new(4.0, 0.0, 0.0), Vector::new(0.0, 4.0, 0.0)),
+            Lambertian::new(Vector::new(0.8, 0.5, 0.2)),
+        )));
+        world.add(Box::new(UniformSurface::new(
+            Quad::new(Point::new(2.0, -2.0, -2.0), Vector::new(0.0, 4.0, 0.0), Vector::new(0.0, 0.0, 0.5)),
+            Lambertian::new(Vector::zero()),
+        )));
+        world.add(Box::new(UniformSurface::new(
+            Quad::new(Point::new(3.0, -0.5, -1.5), Vector::new(0.0, 0.0, 1.0), Vector::new(0.0, 1.0, 0.0)),
+            DiffuseLight::new(Vector::new(8.0, 8.0, 8.0)),
+        )));
+        let camera = Camera::new(8, 6, 2.0, 1.5, 1.0, 0);
+        let samples = 20000;
+        let path_tracer = PathTracer::default();
+        let expected = (0..samples)
+            .map(|_| path_tracer.radiance(&world, camera.viewport_ray(rand::random(), rand::random()), None, &mut rand::thread_rng()))
+            .sum::<Vector>() / samples as f64;
+        let metropolis = Metropolis::new(PathTracer::default()).with_bootstrap_samples(20000);
+        let mut splats = Vec::new();
+        for _ in 0..samples {
+            assert_eq!(metropolis.camera_radiance(&world, &camera, camera.viewport_ray(0.5, 0.5), &mut splats, None, &mut rand::thread_rng()), Vector::zero());
+        }
+        let actual = splats.iter().map(|splat| splat.radiance).sum::<Vector>() / samples as f64;
+        assert!((actual - expected).l2_norm() < 0.05 * expected.l2_norm(), "{:?} vs {:?}", actual, expected);
+    }
+}
@@ -0,0 +1,126 @@
+pub mod path;
+pub mod aov;
+pub mod ambient_occlusion;
+pub mod bidirectional;
+pub mod photon_map;
+pub mod spectral;
+pub mod metropolis;
+
+use crate::{
+    camera::Camera,
+    geometry::{
+        Vector,
+        Ray,
+        Interval,
+        IntervalBounds,
+        shape::HitRecord,
+    },
+    light::{Light, LightSample},
+    surface::{Surface, SurfaceSet},
+    spectrum::at_wavelength,
+    random::RandomStream,
+};
+
+use std::iter;
+
+/// A means of estimating the light arriving back along camera rays
+pub trait Integrator {
+    /// A single sample of the radiance arriving at `ray`'s origin from along it, drawing its
+    /// random numbers from `rng`. If there's a `wavelength`, only light of that wavelength is
+    /// traced, and the sample is its spectral radiance there (in every component)
+    fn radiance(&self, world: &SurfaceSet, ray: Ray, wavelength: Option<f64>, rng: &mut dyn RandomStream) -> Vector;
+
+    /// A sample of the light arriving along one of the `camera`'s rays, as for `radiance`,
+    /// for integrators which may also find light reaching the camera elsewhere (such as by
+    /// tracing paths from the lights). Such light is added to `splats`
+    fn camera_radiance(&self, world: &SurfaceSet, _camera: &Camera, ray: Ray, _splats: &mut Vec<Splat>,
+        wavelength: Option<f64>, rng: &mut dyn RandomStream) -> Vector
+    {
+        self.radiance(world, ray, wavelength, rng)
+    }
+
+    /// The linear sRGB colour of a pixel whose samples (and splats) average to `value`, for
+    /// integrators whose samples are in another colour space, such as the XYZ of `Spectral`
+    fn pixel_rgb(&self, value: Vector) -> Vector {
+        value
+    }
+
+    /// Whether the samples are light, to be gamma corrected for display, as
+    /// opposed to data which is already encoded as colours
+    fn gamma_correct(&self) -> bool {
+        true
+    }
+}
+
+/// Light reaching the camera through an arbitrary `pixel`. Like the samples of a pixel, splats
+/// are summed and divided by the number of samples per pixel (as they may land anywhere,
+/// one splat is worth as much as a whole pixel's worth of samples)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Splat {
+    /// The column and row of the pixel
+    pub pixel: (u16, u16),
+    pub radiance: Vector,
+}
+
+/// The highest probability with which a path survives Russian roulette, so
+/// that even paths which lose no energy (as between mirrors) are ended
+const MAX_SURVIVAL_PROBABILITY: f64 = 0.95;
+
+/// Ends a path at random, continuing in proportion to the light it can still carry. Returns
+/// the survivor's `throughput`, compensated so that the estimate stays unbiased
+pub(crate) fn russian_roulette(throughput: Vector, rng: &mut dyn RandomStream) -> Option<Vector> {
+    let survival_probability = throughput.x.max(throughput.y).max(throughput.z)
+        .min(MAX_SURVIVAL_PROBABILITY);
+    (rng.next_uniform() < survival_probability).then(|| throughput / survival_probability)
+}
+
+/// Samples a light as seen from the `hit` of `ray` on `surface`, returning the light of
+/// `wavelength` scattered back along the ray (weighted against finding it by BSDF sampling)
+pub(crate) fn direct_light(world: &SurfaceSet, surface: &dyn Surface, hit: &HitRecord, ray: Ray,
+    wavelength: Option<f64>, rng: &mut dyn RandomStream) -> Vector
+{
+    let (light, sample) = match world.sample_light(rng) {
+        Some((light, selection_probability)) => match light.sample(hit.point, rng) {
+            Some(sample) => (light, LightSample {
+                pdf: selection_probability * sample.pdf,
+                ..sample
+            }),
+            None => return Vector::zero(),
+        },
+        None => return Vector::zero(),
+    };
+    let f = surface.eval(hit, ray, sample.direction, wavelength);
+    if f == 0.0 || sample.radiance == 0.0 || sample.pdf <= 0.0 {
+        return Vector::zero()
+    }
+    let shadow_ray = Ray::new(hit.point, sample.direction);
+    let unoccluded = Interval::new(0.001, sample.distance - 0.001, IntervalBounds::Open);
+    if world.intersection(shadow_ray, unoccluded, rng).is_some() {
+        return Vector::zero()
+    }
+    // Delta lights can't be found by BSDF sampling, so take the whole weight
+    let weight = if light.is_delta() {
+        1.0
+    } else {
+        power_heuristic(sample.pdf, surface.pdf(hit, ray, sample.direction, wavelength))
+    };
+    weight / sample.pdf * f * at_wavelength(sample.radiance, wavelength)
+}
+
+/// The weight of light from `light`, found by `ray` having been scattered with
+/// density `bsdf_pdf`, against finding it by sampling the light directly
+pub(crate) fn bsdf_sample_weight(world: &SurfaceSet, light: Option<&dyn Light>, ray: Ray, bsdf_pdf: Option<f64>) -> f64 {
+    match (bsdf_pdf, light) {
+        (Some(bsdf_pdf), Some(light)) => {
+            let light_pdf = world.light_selection_probability() * light.pdf(ray.origin, ray.direction);
+            power_heuristic(bsdf_pdf, light_pdf)
+        },
+        _ => 1.0,
+    }
+}
+
+/// Veach's power heuristic (with exponent 2) for the weight of a sample
+/// drawn with density `pdf`, against an alternative strategy's `other_pdf`
+pub(crate) fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    pdf.powi(2) / (pdf.powi(2) + other_pdf.powi(2))
+}
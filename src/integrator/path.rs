@@ -0,0 +1,268 @@
+use super::*;
+use super::photon_map::PhotonMap;
+
+/// A unidirectional path tracer, with next-event estimation and multiple importance sampling.
+/// Paths have no maximum length, but are ended at random by Russian roulette
+pub struct PathTracer {
+    // The number of bounces before Russian roulette starts
+    min_bounces: u32,
+    caustics: Option<PhotonMap>,
+}
+
+impl PathTracer {
+    /// Create a path tracer which follows every path for at least `min_bounces` bounces
+    pub fn new(min_bounces: u32) -> Self {
+        Self {
+            min_bounces,
+            caustics: None,
+        }
+    }
+
+    /// Take caustics from `photon_map` rather than by tracing them: light reaching diffuse
+    /// surfaces from its lights by way of specular bounces is estimated from the photons
+    /// there, and no longer found by following those bounces to the lights
+    pub fn with_caustics(mut self, photon_map: PhotonMap) -> Self {
+        self.caustics = Some(photon_map);
+        self
+    }
+}
+
+impl Default for PathTracer {
+    fn default() -> Self {
+        Self::new(3)
+    }
+}
+
+impl Integrator for PathTracer {
+    /// Light is found both by sampling lights directly from each surface hit, and by
+    /// scattered rays happening upon them, with the two combined by multiple importance sampling
+    fn radiance(&self, world: &SurfaceSet, ray: Ray, wavelength: Option<f64>, rng: &mut dyn RandomStream) -> Vector {
+        let mut radiance = Vector::zero();
+        let mut throughput = Vector::new(1.0, 1.0, 1.0);
+        let mut ray = ray;
+        // The density with which `ray` was scattered, if it was sampled from a
+        // finite BSDF (so could equally have been found by light sampling)
+        let mut bsdf_pdf = None;
+        let mut bounces = 0;
+        // Whether the last non-specular bounce was off a surface (rather than within a
+        // medium), so that light found after specular bounces since then is a caustic
+        let mut diffuse_surface_behind = false;
+        loop {
+            let caustic = diffuse_surface_behind && bsdf_pdf.is_none() && bounces > 0;
+            let mapped = |light: &dyn Light| self.caustics.as_ref().is_some_and(|map| map.covers(light));
+            let intersection = match world.intersection(ray, Interval::new(0.001, f64::MAX, IntervalBounds::Open), rng) {
+                Some(intersection) => intersection,
+                None => {
+                    let background = world.background();
+                    if caustic && background.light().is_some_and(mapped) {
+                        return radiance
+                    }
+                    let weight = bsdf_sample_weight(world, background.light(), ray, bsdf_pdf);
+                    return radiance + weight * throughput * at_wavelength(background.radiance(ray.direction), wavelength)
+                },
+            };
+            let surface = intersection.surfaces[0];
+            let hit = &intersection.hit;
+            if !(caustic && surface.light().is_some_and(mapped)) {
+                let weight = bsdf_sample_weight(world, surface.light(), ray, bsdf_pdf);
+                radiance = radiance + weight * throughput * surface.emitted(hit, ray, wavelength);
+            }
+            radiance = radiance + throughput * direct_light(world, surface, hit, ray, wavelength, rng);
+            let scattered_ray = match surface.scatter(hit, ray, wavelength, rng) {
+                Some(sr) => sr,
+                None => return radiance,
+            };
+            let pdf = surface.pdf(hit, ray, scattered_ray.ray.direction, wavelength);
+            bsdf_pdf = (pdf > 0.0).then_some(pdf);
+            if bsdf_pdf.is_some() {
+                diffuse_surface_behind = !surface.is_medium();
+                if let Some(map) = self.caustics.as_ref().filter(|_| diffuse_surface_behind) {
+                    radiance = radiance + throughput * at_wavelength(map.radiance(surface, hit, ray), wavelength);
+                }
+            }
+            throughput = throughput * scattered_ray.attenuation;
+            ray = scattered_ray.ray;
+            bounces += 1;
+            if bounces >= self.min_bounces {
+                throughput = match russian_roulette(throughput, rng) {
+                    Some(throughput) => throughput,
+                    None => return radiance,
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        geometry::{Point, UnitVector, BoundingBox, shape::{quad::Quad, sphere::Sphere}},
+        surface::{Material, Reflection, UniformSurface, lambertian::Lambertian, diffuse_light::DiffuseLight, dielectric::Dielectric},
+        light::punctual::PointLight,
+        background::ConstantBackground,
+    };
+
+    use core::f64::consts::PI;
+
+    #[test]
+    fn direct_lighting_matches_irradiance() {
+        // A grey floor lit only by a small spherical light overhead,
+        // inside a black sphere which blocks the background
+        let (albedo, radiance, radius, height) = (0.5, 10.0, 0.5, 4.0);
+        let mut world = SurfaceSet::new();
+        world.add(Box::new(UniformSurface::new(
+            Quad::new(Point::new(-50.0, -50.0, 0.0), Vector::new(100.0, 0.0, 0.0), Vector::new(0.0, 100.0, 0.0)),
+            Lambertian::new(Vector::new(albedo, albedo, albedo)),
+        )));
+        world.add(Box::new(UniformSurface::new(
+            Sphere::new(Point::new(0.0, 0.0, height), radius),
+            DiffuseLight::new(Vector::new(radiance, radiance, radiance)),
+        )));
+        world.add(Box::new(UniformSurface::new(
+            Sphere::new(Point::zero(), 100.0),
+            Lambertian::new(Vector::zero()),
+        )));
+        assert_eq!(world.lights().count(), 1);
+        let ray = Ray::new(Point::new(0.0, 0.0, 1.0), UnitVector::from(Vector::new(0.0, 0.0, -1.0)));
+        let integrator = PathTracer::default();
+        let samples = 2000;
+        let mean = (0..samples).map(|_| integrator.radiance(&world, ray, None, &mut rand::thread_rng())).sum::<Vector>() / samples as f64;
+        // The sphere subtends a cone of half-angle theta, with sin theta = radius / height,
+        // so the floor's irradiance is pi radiance sin^2 theta
+        let expected = albedo * radiance * (radius / height).powi(2);
+        assert!((mean.x - expected).abs() < 0.02 * expected, "{} vs {}", mean.x, expected);
+    }
+
+    #[test]
+    fn point_light_illuminates_floor() {
+        let albedo = 0.5;
+        let mut world = SurfaceSet::new();
+        world.add(Box::new(UniformSurface::new(
+            Quad::new(Point::new(-50.0, -50.0, 0.0), Vector::new(100.0, 0.0, 0.0), Vector::new(0.0, 100.0, 0.0)),
+            Lambertian::new(Vector::new(albedo, albedo, albedo)),
+        )));
+        world.add(Box::new(UniformSurface::new(
+            Sphere::new(Point::zero(), 100.0),
+            Lambertian::new(Vector::zero()),
+        )));
+        world.add_light(Box::new(PointLight::new(Point::new(0.0, 0.0, 2.0), Vector::new(8.0, 8.0, 8.0))));
+        let ray = Ray::new(Point::new(0.0, 0.0, 1.0), UnitVector::from(Vector::new(0.0, 0.0, -1.0)));
+        let integrator = PathTracer::default();
+        // A Lambertian surface reflects albedo / pi of the irradiance, here intensity / 2^2
+        let expected = albedo / PI * 2.0;
+        assert!((integrator.radiance(&world, ray, None, &mut rand::thread_rng()).x - expected).abs() < 1e-9);
+        // Shadowed by an occluder
+        world.add(Box::new(UniformSurface::new(
+            Sphere::new(Point::new(0.0, 0.0, 1.5), 0.1),
+            Lambertian::new(Vector::zero()),
+        )));
+        assert_eq!(integrator.radiance(&world, ray, None, &mut rand::thread_rng()), Vector::zero());
+    }
+
+    #[test]
+    fn constant_background_lights_floor() {
+        let albedo = 0.5;
+        let mut world = SurfaceSet::new();
+        world.add(Box::new(UniformSurface::new(
+            Quad::new(Point::new(-50.0, -50.0, 0.0), Vector::new(100.0, 0.0, 0.0), Vector::new(0.0, 100.0, 0.0)),
+            Lambertian::new(Vector::new(albedo, albedo, albedo)),
+        )));
+        world.set_background(Box::new(ConstantBackground::new(Vector::new(2.0, 2.0, 2.0))));
+        assert_eq!(world.lights().count(), 1);
+        let ray = Ray::new(Point::new(0.0, 0.0, 1.0), UnitVector::from(Vector::new(0.0, 0.0, -1.0)));
+        let integrator = PathTracer::default();
+        let samples = 2000;
+        let mean = (0..samples).map(|_| integrator.radiance(&world, ray, None, &mut rand::thread_rng())).sum::<Vector>() / samples as f64;
+        assert!((mean.x - albedo * 2.0).abs() < 0.02, "{}", mean.x);
+    }
+
+    /// A Lambertian reflector which also glows, without being sampled as a light
+    struct GlowingLambertian {
+        albedo: f64,
+        emission: f64,
+    }
+
+    impl Material for GlowingLambertian {
+        fn random_reflection(&self, ray_direction: UnitVector, rebound_normal: UnitVector, hit: &HitRecord, wavelength: Option<f64>, rng: &mut dyn RandomStream) -> Option<Reflection> {
+            Lambertian::new(Vector::new(self.albedo, self.albedo, self.albedo))
+                .random_reflection(ray_direction, rebound_normal, hit, wavelength, rng)
+        }
+
+        fn emitted(&self, _hit: &HitRecord) -> Vector {
+            Vector::new(self.emission, self.emission, self.emission)
+        }
+    }
+
+    #[test]
+    fn russian_roulette_is_unbiased() {
+        // Inside a closed sphere which glows and reflects, the radiance is the sum over
+        // all path lengths, emission / (1 - albedo), so depends on very long paths
+        let (albedo, emission) = (0.8, 1.0);
+        let mut world = SurfaceSet::new();
+        world.add(Box::new(UniformSurface::new(
+            Sphere::new(Point::zero(), 1.0),
+            GlowingLambertian { albedo, emission },
+        )));
+        let ray = Ray::new(Point::zero(), UnitVector::from(Vector::new(0.3, -0.4, 0.5)));
+        let integrator = PathTracer::new(1);
+        let samples = 20000;
+        let mean = (0..samples).map(|_| integrator.radiance(&world, ray, None, &mut rand::thread_rng())).sum::<Vector>() / samples as f64;
+        let expected = emission / (1.0 - albedo);
+        assert!((mean.x - expected).abs() < 0.03 * expected, "{} vs {}", mean.x, expected);
+    }
+
+    /// A grey floor beneath a glass sphere, weakly refracting so that its caustic is spread smoothly
+    fn floor_beneath_glass() -> SurfaceSet {
+        let albedo = 0.5;
+        let mut world = SurfaceSet::new();
+        world.add(Box::new(UniformSurface::new(
+            Quad::new(Point::new(-50.0, -50.0, 0.0), Vector::new(100.0, 0.0, 0.0), Vector::new(0.0, 100.0, 0.0)),
+            Lambertian::new(Vector::new(albedo, albedo, albedo)),
+        )));
+        world.add(Box::new(UniformSurface::new(Sphere::new(Point::new(0.0, 0.0, 2.0), 1.0), Dielectric::new(1.2))));
+        world
+    }
+
+    fn assert_photon_map_matches_path_tracer(world: &SurfaceSet) {
+        // Averaged over the floor beneath the sphere, since the photon map's estimate varies
+        // little between samples at any one point. Tracing the caustic is far noisier
+        let mean = |integrator: &PathTracer, samples: u32| {
+            (0..samples)
+                .map(|_| {
+                    let target = Point::new(rand::random::<f64>() - 0.5, rand::random::<f64>() - 0.5, 0.0);
+                    integrator.radiance(world, Ray::from_two_points(Point::new(2.0, 0.0, 1.0), target), None, &mut rand::thread_rng())
+                })
+                .sum::<Vector>() / samples as f64
+        };
+        let traced = mean(&PathTracer::default(), 50000);
+        let bounds = BoundingBox::new(Point::new(-1.0, -1.0, 0.0), Point::new(1.0, 1.0, 3.0));
+        let photon_map = PhotonMap::new(world, bounds, 200000, 0.05, 1);
+        assert!(!photon_map.is_empty());
+        let mapped = mean(&PathTracer::default().with_caustics(photon_map), 10000);
+        assert!(traced.x > 0.0);
+        assert!((mapped.x - traced.x).abs() < 0.06 * traced.x, "{} vs {}", mapped.x, traced.x);
+    }
+
+    #[test]
+    fn photon_mapped_caustic_matches_traced_caustic() {
+        // Lit only through the sphere, by a large light above it
+        let mut world = floor_beneath_glass();
+        world.add(Box::new(UniformSurface::new(
+            Sphere::new(Point::new(0.0, 0.0, 5.0), 1.0),
+            DiffuseLight::new(Vector::new(5.0, 5.0, 5.0)),
+        )));
+        world.add(Box::new(UniformSurface::new(
+            Sphere::new(Point::zero(), 100.0),
+            Lambertian::new(Vector::zero()),
+        )));
+        assert_photon_map_matches_path_tracer(&world);
+    }
+
+    #[test]
+    fn photon_mapped_caustic_from_background() {
+        let mut world = floor_beneath_glass();
+        world.set_background(Box::new(ConstantBackground::new(Vector::new(1.0, 1.0, 1.0))));
+        assert_photon_map_matches_path_tracer(&world);
+    }
+}
@@ -0,0 +1,323 @@
+use super::*;
+use crate::{
+    geometry::{Point, UnitVector, BoundingBox, uniform_disc},
+    light::EmissionSample,
+    random::{SeededStream, hash},
+};
+
+use core::f64::consts::PI;
+use std::ops::Range;
+
+/// A packet of light which arrived at a diffuse surface after
+/// leaving a light and bouncing only off specular surfaces
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Photon {
+    position: Point,
+    // The direction back towards where the photon came from
+    direction: UnitVector,
+    // Radiant flux
+    power: Vector,
+}
+
+/// Photons stored as a balanced kd-tree, in place: within any range of the tree, the photon
+/// in the middle splits the rest along its axis, with those before it no further along
+/// that axis than it, and those after it no nearer
+struct KdTree {
+    photons: Vec<Photon>,
+    axes: Vec<usize>,
+}
+
+impl KdTree {
+    fn new(mut photons: Vec<Photon>) -> Self {
+        let mut axes = vec![0; photons.len()];
+        Self::balance(&mut photons, &mut axes);
+        Self {
+            photons,
+            axes,
+        }
+    }
+
+    /// Arranges `photons` into a kd-tree, splitting each range along its widest axis
+    fn balance(photons: &mut [Photon], axes: &mut [usize]) {
+        if photons.is_empty() {
+            return
+        }
+        let (min, max) = photons.iter().fold(
+            (Point::new(f64::INFINITY, f64::INFINITY, f64::INFINITY), Point::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY)),
+            |(min, max), photon| {
+                let p = photon.position;
+                (Point::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z)), Point::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z)))
+            },
+        );
+        let extent = max - min;
+        let axis = (0..3)
+            .max_by(|&a, &b| coordinate(extent, a).total_cmp(&coordinate(extent, b)))
+            .unwrap_or(0);
+        let middle = photons.len() / 2;
+        photons.select_nth_unstable_by(middle, |a, b| {
+            coordinate(a.position, axis).total_cmp(&coordinate(b.position, axis))
+        });
+        axes[middle] = axis;
+        let (before, after) = photons.split_at_mut(middle);
+        let (axes_before, axes_after) = axes.split_at_mut(middle);
+        Self::balance(before, axes_before);
+        Self::balance(&mut after[1..], &mut axes_after[1..]);
+    }
+
+    /// Calls `f` on every photon within `radius` of `point`
+    fn within(&self, point: Point, radius: f64, f: &mut impl FnMut(&Photon)) {
+        self.search(0..self.photons.len(), point, radius, f)
+    }
+
+    fn search(&self, range: Range<usize>, point: Point, radius: f64, f: &mut impl FnMut(&Photon)) {
+        if range.is_empty() {
+            return
+        }
+        let middle = range.start + range.len() / 2;
+        let photon = &self.photons[middle];
+        if (photon.position - point).l2_norm_squared() <= radius * radius {
+            f(photon);
+        }
+        let axis = self.axes[middle];
+        let offset = coordinate(point, axis) - coordinate(photon.position, axis);
+        if offset <= radius {
+            self.search(range.start..middle, point, radius, f);
+        }
+        if offset >= -radius {
+            self.search(middle + 1..range.end, point, radius, f);
+        }
+    }
+}
+
+/// The `axis`th (x, y, then z) coordinate of `point`
+fn coordinate(point: Point, axis: usize) -> f64 {
+    match axis {
+        0 => point.x,
+        1 => point.y,
+        _ => point.z,
+    }
+}
+
+/// A map of caustics: light focused onto diffuse surfaces by mirrors or glass, which unidirectional
+/// path tracing finds only by chance. Photons are traced from the lights and stored where they
+/// first land on a diffuse surface after at least one specular bounce, and the light reflected
+/// there is estimated from the density of photons around a point
+pub struct PhotonMap {
+    tree: KdTree,
+    // The radius of the disc over which photons are gathered
+    radius: f64,
+    // The addresses of the lights which emitted photons, whose caustics the map holds
+    lights: Vec<usize>,
+}
+
+impl PhotonMap {
+    /// Trace `photons` photons from the lights of `world` (shared equally between them), gathering
+    /// them within `radius` of each point when estimating caustics. A smaller radius blurs
+    /// caustics less, but needs more photons to avoid noise. Lights infinitely far away, such
+    /// as the sky, shine their photons onto the sphere around `bounds`, which should hold
+    /// whatever casts or catches their caustics. The photons' paths are chosen by `seed`, so that
+    /// maps of the same world with the same seed are identical, as are the images rendered with them
+    /// by a camera with a seed of its own
+    pub fn new(world: &SurfaceSet, bounds: BoundingBox, photons: usize, radius: f64, seed: u64) -> Self {
+        assert!(radius > 0.0);
+        let centre = 0.5 * (bounds.min() + bounds.max());
+        let bounding_radius = 0.5 * (bounds.max() - bounds.min()).l2_norm();
+        let lights: Vec<&dyn Light> = world.lights().collect();
+        let per_light = photons / lights.len().max(1);
+        let mut stored = Vec::new();
+        let mut emitters = Vec::new();
+        for (light_index, light) in lights.into_iter().enumerate() {
+            let mut emits = false;
+            for photon in 0..per_light {
+                // Each photon has numbers of its own, fixed by the seed
+                let mut rng = SeededStream::new(hash(&[seed, light_index as u64, photon as u64]));
+                let emission = match light.sample_emission(&mut rng).or_else(|| shine(light, centre, bounding_radius, &mut rng)) {
+                    Some(emission) => emission,
+                    None => continue,
+                };
+                emits = true;
+                let cos_theta = emission.normal.map_or(1.0, |n| n.dot(emission.ray.direction.to_vector()).abs());
+                let pdf = emission.position_pdf * emission.direction_pdf * per_light as f64;
+                if pdf > 0.0 {
+                    trace(world, emission.ray, cos_theta * emission.radiance / pdf, &mut stored, &mut rng);
+                }
+            }
+            if emits {
+                emitters.push(address(light));
+            }
+        }
+        Self {
+            tree: KdTree::new(stored),
+            radius,
+            lights: emitters,
+        }
+    }
+
+    /// The number of photons stored
+    pub fn len(&self) -> usize {
+        self.tree.photons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.photons.is_empty()
+    }
+
+    /// True iff the map holds the caustics cast by `light`
+    pub(crate) fn covers(&self, light: &dyn Light) -> bool {
+        self.lights.contains(&address(light))
+    }
+
+    /// Given the `hit` of `ray` on `surface`, estimates the caustic light scattered back
+    /// along the ray from the photons nearby. Photons are traced in RGB, so this is RGB
+    /// (without dispersion) whatever wavelength is being traced
+    pub(crate) fn radiance(&self, surface: &dyn Surface, hit: &HitRecord, ray: Ray) -> Vector {
+        let mut flux = Vector::zero();
+        self.tree.within(hit.point, self.radius, &mut |photon| {
+            // `eval` includes the cosine of the photon's direction, whereas its
+            // power arrives already spread over the surface
+            let cos_theta = photon.direction.dot(hit.shading_normal.to_vector()).abs();
+            if cos_theta > 0.0 {
+                flux = flux + surface.eval(hit, ray, photon.direction, None) * photon.power / cos_theta;
+            }
+        });
+        flux / (PI * self.radius.powi(2))
+    }
+}
+
+/// Samples light arriving from `light`, if it's infinitely far away, as a ray
+/// crossing the sphere of `radius` about `centre` (through a disc facing the light)
+fn shine(light: &dyn Light, centre: Point, radius: f64, rng: &mut dyn RandomStream) -> Option<EmissionSample> {
+    let sample = light.sample(centre, rng)?;
+    if sample.distance < f64::INFINITY {
+        return None
+    }
+    let (u, v) = sample.direction.orthonormal_basis();
+    let (x, y) = uniform_disc(rng.next_uniform(), rng.next_uniform());
+    let origin = centre + radius * (sample.direction.to_vector() + x * u + y * v);
+    Some(EmissionSample {
+        ray: Ray::new(origin, UnitVector::from(-1.0 * sample.direction)),
+        normal: None,
+        radiance: sample.radiance,
+        position_pdf: 1.0 / (PI * radius.powi(2)),
+        direction_pdf: sample.pdf,
+    })
+}
+
+/// Identifies `light` by its address, which is fixed while the `SurfaceSet` holding it lives
+fn address(light: &dyn Light) -> usize {
+    light as *const dyn Light as *const () as usize
+}
+
+/// Follows a photon carrying `power` (in RGB) along `ray` through specular bounces,
+/// adding it to `photons` if it then lands on a diffuse surface
+fn trace(world: &SurfaceSet, ray: Ray, power: Vector, photons: &mut Vec<Photon>, rng: &mut dyn RandomStream) {
+    let (mut ray, mut power) = (ray, power);
+    let mut bounces = 0;
+    loop {
+        let intersection = match world.intersection(ray, Interval::new(0.001, f64::MAX, IntervalBounds::Open), rng) {
+            Some(intersection) => intersection,
+            None => return,
+        };
+        let surface = intersection.surfaces[0];
+        let hit = &intersection.hit;
+        let scattered_ray = match surface.scatter(hit, ray, None, rng) {
+            Some(sr) => sr,
+            None => return,
+        };
+        if surface.pdf(hit, ray, scattered_ray.ray.direction, None) > 0.0 {
+            // Light reaching media, or reaching surfaces directly, is left to the path tracer
+            if bounces > 0 && !surface.is_medium() {
+                photons.push(Photon {
+                    position: hit.point,
+                    direction: UnitVector::from(-1.0 * ray.direction),
+                    power,
+                });
+            }
+            return
+        }
+        power = match russian_roulette(scattered_ray.attenuation, rng) {
+            Some(attenuation) => power * attenuation,
+            None => return,
+        };
+        ray = scattered_ray.ray;
+        bounces += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        geometry::shape::quad::Quad,
+        surface::{UniformSurface, lambertian::Lambertian, metal::Metal},
+        light::punctual::SpotLight,
+    };
+
+    #[test]
+    fn kd_tree_finds_photons_within_radius() {
+        let photons: Vec<Photon> = (0..1000)
+            .map(|_| Photon {
+                position: Point::new(rand::random(), rand::random(), rand::random()),
+                direction: UnitVector::from(Vector::new(0.0, 0.0, 1.0)),
+                power: Vector::zero(),
+            })
+            .collect();
+        let tree = KdTree::new(photons.clone());
+        for _ in 0..20 {
+            let point = Point::new(rand::random(), rand::random(), rand::random());
+            let radius = 0.2 * rand::random::<f64>();
+            let mut found = Vec::new();
+            tree.within(point, radius, &mut |photon| found.push(photon.position));
+            let expected = photons.iter().filter(|p| (p.position - point).l2_norm() <= radius).count();
+            assert_eq!(found.len(), expected);
+            assert!(found.iter().all(|p| (*p - point).l2_norm() <= radius));
+        }
+    }
+
+    /// A spot light between a grey floor and a mirrored ceiling, shining up so that its
+    /// reflection acts as a light at the height of the ceiling plus its distance below it
+    fn spot_light_under_mirror(albedo: f64, intensity: f64, height: f64, ceiling: f64) -> SurfaceSet {
+        let mut world = SurfaceSet::new();
+        world.add(Box::new(UniformSurface::new(
+            Quad::new(Point::new(-50.0, -50.0, 0.0), Vector::new(100.0, 0.0, 0.0), Vector::new(0.0, 100.0, 0.0)),
+            Lambertian::new(Vector::new(albedo, albedo, albedo)),
+        )));
+        world.add(Box::new(UniformSurface::new(
+            Quad::new(Point::new(-50.0, -50.0, ceiling), Vector::new(100.0, 0.0, 0.0), Vector::new(0.0, 100.0, 0.0)),
+            Metal::new(Vector::new(1.0, 1.0, 1.0)),
+        )));
+        world.add_light(Box::new(SpotLight::new(
+            Point::new(0.0, 0.0, height),
+            Vector::new(0.0, 0.0, 1.0),
+            Vector::new(intensity, intensity, intensity),
+            0.3,
+            0.3,
+        )));
+        world
+    }
+
+    #[test]
+    fn mirror_caustic_matches_virtual_light() {
+        let (albedo, intensity, height, ceiling) = (0.5, 10.0, 1.0, 2.0);
+        let world = spot_light_under_mirror(albedo, intensity, height, ceiling);
+        let bounds = BoundingBox::new(Point::new(-2.0, -2.0, 0.0), Point::new(2.0, 2.0, ceiling));
+        let map = PhotonMap::new(&world, bounds, 200000, 0.1, 1);
+        assert!(!map.is_empty());
+        assert!(world.lights().all(|light| map.covers(light)));
+        let ray = Ray::new(Point::new(0.0, 0.0, 1.0), UnitVector::from(Vector::new(0.0, 0.0, -1.0)));
+        let intersection = world.intersection(ray, Interval::new(0.001, f64::MAX, IntervalBounds::Open), &mut rand::thread_rng()).unwrap();
+        let caustic = map.radiance(intersection.surfaces[0], &intersection.hit, ray);
+        let expected = albedo / PI * intensity / (2.0 * ceiling - height).powi(2);
+        assert!((caustic.x - expected).abs() < 0.05 * expected, "{} vs {}", caustic.x, expected);
+    }
+
+    #[test]
+    fn seeded_maps_are_identical() {
+        let world = spot_light_under_mirror(0.5, 10.0, 1.0, 2.0);
+        let bounds = BoundingBox::new(Point::new(-2.0, -2.0, 0.0), Point::new(2.0, 2.0, 2.0));
+        let photons = |seed| PhotonMap::new(&world, bounds, 2000, 0.1, seed).tree.photons;
+        assert!(!photons(1).is_empty());
+        assert_eq!(photons(1), photons(1));
+        assert_ne!(photons(1), photons(2));
+    }
+}
@@ -0,0 +1,119 @@
+use super::*;
+use crate::spectrum::{sample_wavelength, to_xyz, xyz_to_rgb};
+
+/// Renders spectrally with another integrator: each sample follows light of a single random
+/// wavelength, so that dispersive materials can bend each colour differently. Colours are
+/// uplifted to spectra wherever light meets a surface, and each sample is converted to XYZ
+/// through the colour matching functions, so colours are noisier than in RGB rendering.
+/// Pixels are accumulated in XYZ, and converted to sRGB once they're complete
+pub struct Spectral<I: Integrator> {
+    integrator: I,
+}
+
+impl<I: Integrator> Spectral<I> {
+    pub fn new(integrator: I) -> Self {
+        Self {
+            integrator,
+        }
+    }
+}
+
+/// A spectral sample from an integrator, which is the same in every component
+fn spectral_value(radiance: Vector) -> f64 {
+    (radiance.x + radiance.y + radiance.z) / 3.0
+}
+
+impl<I: Integrator> Integrator for Spectral<I> {
+    /// Traces light of a random wavelength, in place of any `wavelength` given
+    fn radiance(&self, world: &SurfaceSet, ray: Ray, _wavelength: Option<f64>, rng: &mut dyn RandomStream) -> Vector {
+        let wavelength = sample_wavelength(rng.next_uniform());
+        let radiance = self.integrator.radiance(world, ray, Some(wavelength), rng);
+        to_xyz(spectral_value(radiance), wavelength)
+    }
+
+    /// Splats share the wavelength of the camera sample
+    fn camera_radiance(&self, world: &SurfaceSet, camera: &Camera, ray: Ray, splats: &mut Vec<Splat>,
+        _wavelength: Option<f64>, rng: &mut dyn RandomStream) -> Vector
+    {
+        let wavelength = sample_wavelength(rng.next_uniform());
+        let first_splat = splats.len();
+        let radiance = self.integrator.camera_radiance(world, camera, ray, splats, Some(wavelength), rng);
+        for splat in &mut splats[first_splat..] {
+            splat.radiance = to_xyz(spectral_value(splat.radiance), wavelength);
+        }
+        to_xyz(spectral_value(radiance), wavelength)
+    }
+
+    fn pixel_rgb(&self, value: Vector) -> Vector {
+        xyz_to_rgb(value)
+    }
+
+    fn gamma_correct(&self) -> bool {
+        self.integrator.gamma_correct()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        geometry::{Point, UnitVector, shape::{quad::Quad, sphere::Sphere}},
+        integrator::path::PathTracer,
+        surface::{UniformSurface, lambertian::Lambertian, diffuse_light::DiffuseLight, dielectric::{Dielectric, Dispersion}},
+    };
+
+    /// A coloured floor lit by a white light overhead, inside a black sphere
+    fn lit_floor(albedo: Vector) -> SurfaceSet {
+        let mut world = SurfaceSet::new();
+        world.add(Box::new(UniformSurface::new(
+            Quad::new(Point::new(-50.0, -50.0, 0.0), Vector::new(100.0, 0.0, 0.0), Vector::new(0.0, 100.0, 0.0)),
+            Lambertian::new(albedo),
+        )));
+        world.add(Box::new(UniformSurface::new(
+            Sphere::new(Point::new(0.0, 0.0, 3.0), 1.0),
+            DiffuseLight::new(Vector::new(4.0, 4.0, 4.0)),
+        )));
+        world.add(Box::new(UniformSurface::new(
+            Sphere::new(Point::zero(), 100.0),
+            Lambertian::new(Vector::zero()),
+        )));
+        world
+    }
+
+    #[test]
+    fn spectral_colours_match_rgb() {
+        let world = lit_floor(Vector::new(0.7, 0.4, 0.1));
+        let ray = Ray::new(Point::new(0.0, 0.0, 1.0), UnitVector::from(Vector::new(0.0, 0.0, -1.0)));
+        let samples = 40000;
+        let mean = |integrator: &dyn Integrator| {
+            (0..samples).map(|_| integrator.radiance(&world, ray, None, &mut rand::thread_rng())).sum::<Vector>() / samples as f64
+        };
+        let rgb = mean(&PathTracer::default());
+        let spectral = Spectral::new(PathTracer::default());
+        let spectral = spectral.pixel_rgb(mean(&spectral));
+        assert!((spectral - rgb).l2_norm() < 0.05 * rgb.l2_norm(), "{:?} vs {:?}", spectral, rgb);
+    }
+
+    #[test]
+    fn dispersion_spreads_wavelengths() {
+        // Rays through a dispersive sphere leave it spread out by wavelength
+        let mut world = SurfaceSet::new();
+        world.add(Box::new(UniformSurface::new(Sphere::new(Point::zero(), 1.0), Dielectric::dispersive(Dispersion::SF11))));
+        let ray = Ray::new(Point::new(0.5, 0.0, 5.0), UnitVector::from(Vector::new(0.0, 0.0, -1.0)));
+        let exit = |wavelength: Option<f64>| {
+            let rng = &mut rand::thread_rng();
+            let entry = world.intersection(ray, Interval::new(0.001, f64::MAX, IntervalBounds::Open), rng).unwrap();
+            let inside = entry.surfaces[0].scatter(&entry.hit, ray, wavelength, rng).unwrap().ray;
+            let exit = world.intersection(inside, Interval::new(0.001, f64::MAX, IntervalBounds::Open), rng).unwrap();
+            exit.surfaces[0].scatter(&exit.hit, inside, wavelength, rng).unwrap().ray.direction
+        };
+        let exit_at = |wavelength| exit(Some(wavelength));
+        let (blue, red) = (exit_at(450.0), exit_at(650.0));
+        assert!(blue.dot(red.to_vector()) < 1.0 - 1e-6);
+        // Blue light is bent more strongly towards the axis
+        assert!(blue.x < red.x);
+        // Without a wavelength, rays see the index at the sodium D line
+        let sodium = exit_at(589.3);
+        assert!((exit(None).to_vector() - sodium.to_vector()).l2_norm() < 1e-12);
+    }
+}
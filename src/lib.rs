@@ -2,6 +2,16 @@ mod image;
 mod geometry;
 mod camera;
 mod surface;
+mod texture;
+mod volume;
+mod light;
+mod background;
+mod integrator;
+mod spectrum;
+mod random;
+mod sampler;
+mod filter;
+mod film;
 
 pub use self::{
     image::{
@@ -9,8 +19,11 @@ pub use self::{
         Image,
         formatter::{
             ImageFormatter,
+            ImageDecoder,
             ppm::PPMFormatter,
+            png::PNGDecoder,
         },
+        hdr::HdrImage,
     },
     geometry::{
         Point,
@@ -19,12 +32,24 @@ pub use self::{
         Ray,
         Interval,
         IntervalBounds,
+        BoundingBox,
+        TextureCoordinates,
         shape::{
             Shape,
+            HitRecord,
             sphere::Sphere,
+            quad::Quad,
         },
     },
     camera::Camera,
+    filter::{
+        Filter,
+        BoxFilter,
+        TentFilter,
+        GaussianFilter,
+        MitchellFilter,
+        LanczosFilter,
+    },
     surface::{
         Reflection,
         Material,
@@ -32,6 +57,97 @@ pub use self::{
         SurfaceSet,
         lambertian::Lambertian,
         metal::Metal,
-        dielectric::Dielectric,
+        dielectric::{Dielectric, Dispersion},
+        microfacet::{
+            Ggx,
+            ConductorFresnel,
+            RoughConductor,
+            RoughDielectric,
+            fresnel_dielectric,
+            fresnel_conductor,
+            fresnel_schlick,
+        },
+        principled::Principled,
+        diffuse_light::DiffuseLight,
+        thin_film::{ThinFilm, Substrate},
+    },
+    light::{
+        Light,
+        LightSample,
+        EmissionSample,
+        punctual::{
+            PointLight,
+            SpotLight,
+            DirectionalLight,
+        },
+    },
+    background::{
+        Background,
+        ConstantBackground,
+        GradientBackground,
+        environment::EnvironmentMap,
+        sky::PreethamSky,
+    },
+    integrator::{
+        Integrator,
+        Splat,
+        path::PathTracer,
+        aov::{Aov, AovIntegrator},
+        ambient_occlusion::AmbientOcclusion,
+        bidirectional::BidirectionalPathTracer,
+        photon_map::PhotonMap,
+        spectral::Spectral,
+        metropolis::Metropolis,
+    },
+    random::{
+        RandomStream,
+        SeededStream,
+    },
+    sampler::{
+        Sampler,
+        IndependentSampler,
+        stratified::StratifiedSampler,
+        halton::HaltonSampler,
+        sobol::SobolSampler,
+    },
+    spectrum::{
+        MIN_WAVELENGTH,
+        MAX_WAVELENGTH,
+        sample_wavelength,
+        colour_matching,
+        to_rgb,
+        to_xyz,
+        xyz_to_rgb,
+        uplift,
+    },
+    texture::{
+        Texture,
+        checker::{
+            SpatialChecker,
+            UvChecker,
+        },
+        image::{
+            ImageTexture,
+            TextureAddressing,
+            TextureFiltering,
+            ColourEncoding,
+        },
+        noise::{
+            Perlin,
+            NoisePattern,
+            NoiseTexture,
+        },
+        normal::{
+            NormalPerturbation,
+            NormalMap,
+            BumpMap,
+        },
+    },
+    volume::{
+        HeterogeneousVolume,
+        grid::{
+            Voxel,
+            VoxelGrid,
+        },
     },
 };
@@ -0,0 +1,67 @@
+pub mod punctual;
+
+use crate::{
+    geometry::{
+        Point,
+        Vector,
+        UnitVector,
+        Ray,
+    },
+    random::RandomStream,
+};
+
+/// A source of light which can be sampled directly, so that shaded points can
+/// aim shadow rays at it (next-event estimation) rather than hoping to hit it
+pub trait Light {
+    /// Samples a direction from `point` towards the light, returning None if
+    /// no part of the light can be seen from `point` (ignoring occlusion)
+    fn sample(&self, point: Point, rng: &mut dyn RandomStream) -> Option<LightSample>;
+    /// The density, per unit solid angle, with which `sample`
+    /// picks `direction` as seen from `point`
+    fn pdf(&self, point: Point, direction: UnitVector) -> f64;
+    /// True iff the light lies at a single point or shines from a single direction, so
+    /// can only be found by sampling it directly (and `pdf` is always zero)
+    fn is_delta(&self) -> bool {
+        false
+    }
+    /// Samples light leaving the light, to trace paths onwards from it. Returns
+    /// None if the light can't be sampled this way, as for lights infinitely far away
+    fn sample_emission(&self, _rng: &mut dyn RandomStream) -> Option<EmissionSample> {
+        None
+    }
+    /// The density, per unit solid angle, with which `sample_emission` sends light from
+    /// `point` on the light (where its surface has the `normal`, if any) in `direction`
+    fn emission_pdf(&self, _point: Point, _normal: Option<UnitVector>, _direction: UnitVector) -> f64 {
+        0.0
+    }
+}
+
+/// A direction towards a `Light`, and the light arriving from it. For delta lights,
+/// `radiance` is instead the irradiance arriving (normal to `direction`), and `pdf` one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightSample {
+    /// The direction from the shaded point towards the light
+    pub direction: UnitVector,
+    /// The distance to the sampled point on the light, which
+    /// a shadow ray must reach unobstructed
+    pub distance: f64,
+    /// The radiance arriving from the light, if unoccluded
+    pub radiance: Vector,
+    /// The density of `direction`, per unit solid angle
+    pub pdf: f64,
+}
+
+/// Light leaving a `Light`, from a sampled point in a sampled direction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EmissionSample {
+    /// Leaves the sampled point in the sampled direction
+    pub ray: Ray,
+    /// The normal to the light's surface at the point, if it has one
+    pub normal: Option<UnitVector>,
+    /// The radiance leaving along `ray`. For delta lights, this is instead the intensity
+    pub radiance: Vector,
+    /// The density of the point per unit area, or one for lights at a single point
+    pub position_pdf: f64,
+    /// The density of the direction per unit solid angle
+    pub direction_pdf: f64,
+}
@@ -0,0 +1,232 @@
+use super::*;
+use crate::{geometry::uniform_sphere, random::RandomStream};
+
+use core::f64::consts::PI;
+
+/// A light radiating equally in all directions from a single point,
+/// whose irradiance falls off with the inverse square of distance
+pub struct PointLight {
+    position: Point,
+    // Radiant intensity, i.e. power per unit solid angle
+    intensity: Vector,
+}
+
+impl PointLight {
+    pub fn new(position: Point, intensity: Vector) -> Self {
+        Self {
+            position,
+            intensity,
+        }
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, point: Point, _rng: &mut dyn RandomStream) -> Option<LightSample> {
+        sample_position(self.position, self.intensity, point)
+    }
+
+    fn pdf(&self, _point: Point, _direction: UnitVector) -> f64 {
+        0.0
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+
+    fn sample_emission(&self, rng: &mut dyn RandomStream) -> Option<EmissionSample> {
+        Some(EmissionSample {
+            ray: Ray::new(self.position, UnitVector::from(uniform_sphere(rng.next_uniform(), rng.next_uniform()))),
+            normal: None,
+            radiance: self.intensity,
+            position_pdf: 1.0,
+            direction_pdf: 1.0 / (4.0 * PI),
+        })
+    }
+
+    fn emission_pdf(&self, _point: Point, _normal: Option<UnitVector>, _direction: UnitVector) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+}
+
+/// A point light restricted to a cone, fading out smoothly between
+/// its inner and outer angles (measured from its axis)
+pub struct SpotLight {
+    position: Point,
+    direction: UnitVector,
+    intensity: Vector,
+    cos_inner: f64,
+    cos_outer: f64,
+}
+
+impl SpotLight {
+    /// Create a spot light at `position` shining along `direction`, at full `intensity` within
+    /// `inner_angle` of its axis and dark beyond `outer_angle` (both in radians)
+    pub fn new(position: Point, direction: Vector, intensity: Vector, inner_angle: f64, outer_angle: f64) -> Self {
+        assert!(0.0 <= inner_angle && inner_angle <= outer_angle);
+        Self {
+            position,
+            direction: UnitVector::from(direction),
+            intensity,
+            cos_inner: inner_angle.cos(),
+            cos_outer: outer_angle.cos(),
+        }
+    }
+
+    /// The fraction of the full intensity shone at `cos_theta` from the axis
+    fn falloff(&self, cos_theta: f64) -> f64 {
+        if cos_theta >= self.cos_inner {
+            return 1.0
+        }
+        if cos_theta <= self.cos_outer {
+            return 0.0
+        }
+        let t = (cos_theta - self.cos_outer) / (self.cos_inner - self.cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, point: Point, _rng: &mut dyn RandomStream) -> Option<LightSample> {
+        let cos_theta = UnitVector::from(point - self.position).dot(self.direction.to_vector());
+        let falloff = self.falloff(cos_theta);
+        if falloff <= 0.0 {
+            return None
+        }
+        sample_position(self.position, falloff * self.intensity, point)
+    }
+
+    fn pdf(&self, _point: Point, _direction: UnitVector) -> f64 {
+        0.0
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+
+    /// Samples directions uniformly within the outer cone
+    fn sample_emission(&self, rng: &mut dyn RandomStream) -> Option<EmissionSample> {
+        let cos_theta = 1.0 - rng.next_uniform() * (1.0 - self.cos_outer);
+        let sin_theta = (1.0 - cos_theta.powi(2)).max(0.0).sqrt();
+        let phi = 2.0 * PI * rng.next_uniform();
+        let (u, v) = self.direction.orthonormal_basis();
+        let direction = UnitVector::from(sin_theta * (phi.cos() * u + phi.sin() * v) + cos_theta * self.direction);
+        Some(EmissionSample {
+            ray: Ray::new(self.position, direction),
+            normal: None,
+            radiance: self.falloff(cos_theta) * self.intensity,
+            position_pdf: 1.0,
+            direction_pdf: self.emission_pdf(self.position, None, direction),
+        })
+    }
+
+    fn emission_pdf(&self, _point: Point, _normal: Option<UnitVector>, direction: UnitVector) -> f64 {
+        if direction.dot(self.direction.to_vector()) < self.cos_outer {
+            return 0.0
+        }
+        1.0 / (2.0 * PI * (1.0 - self.cos_outer))
+    }
+}
+
+/// A light infinitely far away, such as the sun, shining
+/// uniformly in one direction throughout the scene
+pub struct DirectionalLight {
+    // The direction in which the light travels
+    direction: UnitVector,
+    // Irradiance on a surface facing the light
+    irradiance: Vector,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vector, irradiance: Vector) -> Self {
+        Self {
+            direction: UnitVector::from(direction),
+            irradiance,
+        }
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _point: Point, _rng: &mut dyn RandomStream) -> Option<LightSample> {
+        Some(LightSample {
+            direction: UnitVector::from(-1.0 * self.direction),
+            distance: f64::INFINITY,
+            radiance: self.irradiance,
+            pdf: 1.0,
+        })
+    }
+
+    fn pdf(&self, _point: Point, _direction: UnitVector) -> f64 {
+        0.0
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+}
+
+/// The light arriving at `point` from an isotropic source at `position`
+fn sample_position(position: Point, intensity: Vector, point: Point) -> Option<LightSample> {
+    let offset = position - point;
+    let distance = offset.l2_norm();
+    if distance <= 0.0 {
+        return None
+    }
+    Some(LightSample {
+        direction: UnitVector::from(offset),
+        distance,
+        radiance: intensity / distance.powi(2),
+        pdf: 1.0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn point_light_falls_off_with_inverse_square() {
+        let light = PointLight::new(Point::new(0.0, 0.0, 2.0), Vector::new(8.0, 8.0, 8.0));
+        let sample = light.sample(Point::zero(), &mut rand::thread_rng()).unwrap();
+        assert_eq!(sample.direction.to_vector(), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(sample.distance, 2.0);
+        assert_eq!(sample.radiance, Vector::new(2.0, 2.0, 2.0));
+        assert_eq!(light.pdf(Point::zero(), sample.direction), 0.0);
+        assert!(light.is_delta());
+    }
+
+    #[test]
+    fn spot_light_cone() {
+        let down = Vector::new(0.0, 0.0, -1.0);
+        let light = SpotLight::new(Point::new(0.0, 0.0, 1.0), down, Vector::new(1.0, 1.0, 1.0), 0.2, 0.4);
+        // The falloff at x on the plane z = 0, undoing the inverse-square law
+        let falloff = |x: f64| light.sample(Point::new(x, 0.0, 0.0), &mut rand::thread_rng()).map(|s| s.radiance.x * (1.0 + x * x));
+        assert!((falloff(0.1).unwrap() - 1.0).abs() < 1e-12);
+        let halfway = (0.5 * (0.2f64.cos() + 0.4f64.cos())).acos();
+        assert!((falloff(halfway.tan()).unwrap() - 0.5).abs() < 1e-9);
+        assert!(falloff(0.5).is_none());
+    }
+
+    #[test]
+    fn directional_light_is_uniform() {
+        let light = DirectionalLight::new(Vector::new(0.0, -1.0, 0.0), Vector::new(3.0, 2.0, 1.0));
+        for point in [Point::zero(), Point::new(100.0, -5.0, 3.0)] {
+            let sample = light.sample(point, &mut rand::thread_rng()).unwrap();
+            assert_eq!(sample.direction.to_vector(), Vector::new(0.0, 1.0, 0.0));
+            assert_eq!(sample.radiance, Vector::new(3.0, 2.0, 1.0));
+            assert_eq!(sample.distance, f64::INFINITY);
+        }
+    }
+
+    #[test]
+    fn spot_light_emits_within_cone() {
+        let down = Vector::new(0.0, 0.0, -1.0);
+        let light = SpotLight::new(Point::new(0.0, 0.0, 1.0), down, Vector::new(1.0, 1.0, 1.0), 0.2, 0.4);
+        for _ in 0..100 {
+            let sample = light.sample_emission(&mut rand::thread_rng()).unwrap();
+            assert!(sample.ray.direction.dot(down) >= 0.4f64.cos() - 1e-12);
+            assert_eq!(sample.direction_pdf, light.emission_pdf(sample.ray.origin, None, sample.ray.direction));
+            assert!(sample.direction_pdf > 0.0);
+        }
+        assert_eq!(light.emission_pdf(Point::zero(), None, UnitVector::from(Vector::new(0.0, 1.0, -1.0))), 0.0);
+    }
+}
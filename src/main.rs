@@ -7,7 +7,13 @@ use ray_tracing::{
     Metal,
     Dielectric,
     SurfaceSet,
+    PreethamSky,
     Camera,
+    PathTracer,
+    PhotonMap,
+    BoundingBox,
+    SobolSampler,
+    MitchellFilter,
 };
 
 use std::path::Path;
@@ -21,8 +27,9 @@ fn main() {
     let focal_length = 1.0;
     let viewport_height = 2.0;
     let viewport_width = viewport_height * image_width as f64 / image_height as f64;
-    let camera = Camera::new(image_width, image_height, viewport_width, viewport_height, focal_length, 7, 50);
     let mut world = SurfaceSet::new();
+    // A clear afternoon sky, with the sun behind and to the right of the camera
+    world.set_background(Box::new(PreethamSky::new(0.6, 2.5, 3.0)));
     // Middle
     world.add(Box::new(UniformSurface::new(
         Sphere::new(
@@ -55,5 +62,16 @@ fn main() {
         ),
         Metal::new(Vector::new(0.8, 0.6, 0.2)),
     )));
+    // Sunlight focused by the glass sphere onto the ground, which the path tracer would only find
+    // by chance, is instead gathered from photons shone onto the spheres and the ground about them
+    let bounds = BoundingBox::new(Point::new(-2.5, -0.5, -3.0), Point::new(2.5, 0.5, 1.0));
+    // The same seed fixes both the photons and the camera's samples, so the image is reproducible
+    let seed = 1;
+    let photon_map = PhotonMap::new(&world, bounds, 2_000_000, 0.02, seed);
+    let camera = Camera::new(image_width, image_height, viewport_width, viewport_height, focal_length, 7)
+        .with_seed(seed)
+        .with_integrator(PathTracer::default().with_caustics(photon_map))
+        .with_sampler(SobolSampler::new())
+        .with_filter(MitchellFilter::default());
     camera.render(&world, Path::new("tmp.ppm")).unwrap();
 }
@@ -0,0 +1,79 @@
+/// A source of the uniform random numbers in [0, 1) which drive rendering. A stream is
+/// passed to everything which draws numbers, from the integrator down to the materials,
+/// lights and shapes it samples: the camera passes a `Sampler`'s numbers for each sample,
+/// so each draw takes the next of its dimensions in turn, while integrators such as
+/// `Metropolis` pass their own to steer the paths the rest of the renderer traces
+pub trait RandomStream {
+    fn next_uniform(&mut self) -> f64;
+}
+
+/// Numbers fixed by a seed, for randomness which must be reproducible
+#[derive(Debug, Clone)]
+pub struct SeededStream {
+    seed: u64,
+    // The numbers handed out so far
+    count: u64,
+}
+
+impl SeededStream {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, count: 0 }
+    }
+}
+
+impl RandomStream for SeededStream {
+    fn next_uniform(&mut self) -> f64 {
+        self.count += 1;
+        to_uniform(hash(&[self.seed, self.count]))
+    }
+}
+
+/// The thread's own generator, for randomness which needn't be reproducible
+impl RandomStream for rand::rngs::ThreadRng {
+    fn next_uniform(&mut self) -> f64 {
+        rand::Rng::gen(self)
+    }
+}
+
+/// The increment of the SplitMix64 generator's state, 2^64 over the golden ratio
+const GOLDEN_GAMMA: u64 = 0x9e3779b97f4a7c15;
+
+/// Advances `state`, returning the next output of the SplitMix64 generator
+pub(crate) fn split_mix(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(GOLDEN_GAMMA);
+    mix(*state)
+}
+
+/// Scrambles the bits of `x` so that nearby inputs give unrelated
+/// outputs, by the finaliser of the SplitMix64 generator
+pub(crate) fn mix(x: u64) -> u64 {
+    let x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    let x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+/// A hash of `values`, in order
+pub(crate) fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(GOLDEN_GAMMA, |hash, value| mix(hash ^ mix(*value)))
+}
+
+/// A number in [0, 1) from the top bits of `hash`
+pub(crate) fn to_uniform(hash: u64) -> f64 {
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeds_fix_the_numbers() {
+        let numbers = |seed| {
+            let mut stream = SeededStream::new(seed);
+            (0..100).map(|_| stream.next_uniform()).collect::<Vec<_>>()
+        };
+        assert!(numbers(1).iter().all(|number| (0.0..1.0).contains(number)));
+        assert_eq!(numbers(1), numbers(1));
+        assert_ne!(numbers(1), numbers(2));
+    }
+}
@@ -0,0 +1,92 @@
+use super::*;
+use super::stratified::permute;
+
+/// The number of dimensions with a Halton sequence of their own,
+/// beyond which numbers are independent
+const DIMENSIONS: usize = 1000;
+
+/// The largest f64 below 1
+const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+
+/// Takes each pixel's samples from the Halton sequence, whose dimensions are the radical
+/// inverses of the sample index in successive prime bases. The digits are scrambled by
+/// random permutations, which differ by pixel and dimension, so neighbouring pixels and
+/// high dimensions (whose large bases would otherwise correlate) look random
+pub struct HaltonSampler {
+    primes: Vec<u32>,
+}
+
+impl HaltonSampler {
+    pub fn new() -> Self {
+        let mut primes: Vec<u32> = Vec::with_capacity(DIMENSIONS);
+        let mut candidate = 2;
+        while primes.len() < DIMENSIONS {
+            if primes.iter().take_while(|prime| *prime * *prime <= candidate).all(|prime| candidate % prime != 0) {
+                primes.push(candidate);
+            }
+            candidate += 1;
+        }
+        Self {
+            primes,
+        }
+    }
+}
+
+impl Default for HaltonSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn sample(&self, seed: u64, pixel: (u16, u16), index: u32, _samples_per_pixel: u32, dimension: u32) -> f64 {
+        let scramble = hash(&[seed, pixel.0.into(), pixel.1.into(), dimension.into()]);
+        match self.primes.get(dimension as usize) {
+            Some(&base) => scrambled_radical_inverse(index, base, scramble),
+            None => to_uniform(hash(&[scramble, index.into()])),
+        }
+    }
+}
+
+/// The digits of `index` in `base`, each permuted by its own permutation chosen by
+/// `scramble`, mirrored about the radix point. As the (infinitely many) leading zeros
+/// are permuted too, digits are added until they're too small to change the result
+fn scrambled_radical_inverse(index: u32, base: u32, scramble: u64) -> f64 {
+    let inverse_base = 1.0 / base as f64;
+    let (mut index, mut weight, mut result) = (index, inverse_base, 0.0);
+    let mut digit_position = 0;
+    while weight > f64::EPSILON / 4.0 {
+        let seed = hash(&[scramble, digit_position]) as u32;
+        let digit = permute(index % base, base, seed);
+        result += digit as f64 * weight;
+        index /= base;
+        weight *= inverse_base;
+        digit_position += 1;
+    }
+    result.min(ONE_MINUS_EPSILON)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bases_are_primes() {
+        let sampler = HaltonSampler::new();
+        assert_eq!(sampler.primes[..8], [2, 3, 5, 7, 11, 13, 17, 19]);
+        assert_eq!(sampler.primes.len(), DIMENSIONS);
+    }
+
+    #[test]
+    fn scrambled_sequence_is_stratified() {
+        // The first base^k indices fall one in each interval of width base^-k
+        for base in [2u32, 3, 5] {
+            let count = base.pow(3);
+            let mut intervals: Vec<u32> = (0..count)
+                .map(|index| (scrambled_radical_inverse(index, base, 42) * count as f64) as u32)
+                .collect();
+            intervals.sort();
+            assert_eq!(intervals, (0..count).collect::<Vec<_>>());
+        }
+    }
+}
@@ -0,0 +1,140 @@
+pub mod stratified;
+pub mod halton;
+pub mod sobol;
+
+use crate::random::{RandomStream, hash, to_uniform};
+
+use std::rc::Rc;
+
+/// A source of the numbers behind each of a pixel's samples, one per dimension: the camera
+/// takes the first two to place its ray within the pixel, and the rest are used in turn by
+/// the integrator, materials and lights. Samplers which spread each dimension's numbers
+/// evenly over a pixel's samples (rather than at random) make images converge faster.
+///
+/// Numbers are a function of their arguments alone, so that the same `seed` always renders
+/// the same image, however the samples are ordered. The camera passes each sample's numbers
+/// on as a `RandomStream`, from which the integrator, materials and lights draw them in turn
+pub trait Sampler {
+    /// The number in [0, 1) for `dimension` of sample `index` of `pixel`,
+    /// which has `samples_per_pixel` samples, in the render chosen by `seed`
+    fn sample(&self, seed: u64, pixel: (u16, u16), index: u32, samples_per_pixel: u32, dimension: u32) -> f64;
+}
+
+/// Picks every number independently at random, so that the image converges as white noise
+#[derive(Debug, Default, Clone, Copy)]
+pub struct IndependentSampler;
+
+impl IndependentSampler {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn sample(&self, seed: u64, pixel: (u16, u16), index: u32, _samples_per_pixel: u32, dimension: u32) -> f64 {
+        to_uniform(hash(&[seed, pixel.0.into(), pixel.1.into(), index.into(), dimension.into()]))
+    }
+}
+
+/// One sample of a pixel, handing out its numbers as a `RandomStream`
+pub(crate) struct PixelSample {
+    sampler: Rc<dyn Sampler>,
+    seed: u64,
+    pixel: (u16, u16),
+    index: u32,
+    samples_per_pixel: u32,
+    // The next dimension to hand out
+    dimension: u32,
+}
+
+impl PixelSample {
+    /// Sample `index` of `pixel` in the render chosen by `seed`, starting at `dimension`
+    pub(crate) fn new(sampler: Rc<dyn Sampler>, seed: u64, pixel: (u16, u16), index: u32, samples_per_pixel: u32, dimension: u32) -> Self {
+        Self {
+            sampler,
+            seed,
+            pixel,
+            index,
+            samples_per_pixel,
+            dimension,
+        }
+    }
+}
+
+impl RandomStream for PixelSample {
+    fn next_uniform(&mut self) -> f64 {
+        let value = self.sampler.sample(self.seed, self.pixel, self.index, self.samples_per_pixel, self.dimension);
+        self.dimension += 1;
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::{stratified::StratifiedSampler, halton::HaltonSampler, sobol::SobolSampler};
+
+    /// The mean squared error, over many pixels, of estimating the integral over the unit
+    /// square of a smooth function (whose integral is 1/4) from `sampler`'s first two dimensions
+    fn mean_squared_error(sampler: &dyn Sampler, samples_per_pixel: u32) -> f64 {
+        let pixels = 200;
+        (0..pixels).map(|x| {
+            let estimate = (0..samples_per_pixel).map(|index| {
+                let u = sampler.sample(17, (x, 0), index, samples_per_pixel, 2);
+                let v = sampler.sample(17, (x, 0), index, samples_per_pixel, 3);
+                u * v
+            }).sum::<f64>() / samples_per_pixel as f64;
+            (estimate - 0.25).powi(2)
+        }).sum::<f64>() / pixels as f64
+    }
+
+    #[test]
+    fn samples_lie_in_unit_interval() {
+        let samplers: [Box<dyn Sampler>; 4] = [
+            Box::new(IndependentSampler::new()),
+            Box::new(StratifiedSampler::new()),
+            Box::new(HaltonSampler::new()),
+            Box::new(SobolSampler::new()),
+        ];
+        for sampler in samplers {
+            for dimension in (0..40).chain([500, 5000]) {
+                for index in 0..16 {
+                    let value = sampler.sample(17, (3, 7), index, 16, dimension);
+                    assert!((0.0..1.0).contains(&value), "{} in dimension {}", value, dimension);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn even_samplers_beat_independent_sampling() {
+        let independent = mean_squared_error(&IndependentSampler::new(), 64);
+        for sampler in [&StratifiedSampler::new() as &dyn Sampler, &HaltonSampler::new(), &SobolSampler::new()] {
+            assert!(mean_squared_error(sampler, 64) < independent / 4.0);
+        }
+    }
+
+    #[test]
+    fn pixel_samples_stream_successive_dimensions() {
+        let sampler: Rc<dyn Sampler> = Rc::new(IndependentSampler::new());
+        let mut stream = PixelSample::new(sampler.clone(), 17, (1, 2), 3, 4, 5);
+        for dimension in 5..10 {
+            assert_eq!(stream.next_uniform(), sampler.sample(17, (1, 2), 3, 4, dimension));
+        }
+    }
+
+    #[test]
+    fn seeds_choose_the_numbers() {
+        let samplers: [Box<dyn Sampler>; 4] = [
+            Box::new(IndependentSampler::new()),
+            Box::new(StratifiedSampler::new()),
+            Box::new(HaltonSampler::new()),
+            Box::new(SobolSampler::new()),
+        ];
+        for sampler in samplers {
+            let numbers = |seed| (0..8).map(|dimension| sampler.sample(seed, (6, 9), 2, 8, dimension)).collect::<Vec<_>>();
+            assert_eq!(numbers(1), numbers(1));
+            assert_ne!(numbers(1), numbers(2));
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use super::*;
+
+/// The primitive polynomials (degree and interior coefficients) and initial direction
+/// numbers of Sobol's second to fourth dimensions, from Joe and Kuo (2008)
+const POLYNOMIALS: [(usize, u32, [u32; 3]); 3] = [
+    (1, 0, [1, 0, 0]),
+    (2, 1, [1, 3, 0]),
+    (3, 1, [1, 3, 1]),
+];
+
+/// Takes each pixel's samples from the Sobol sequence, Owen-scrambled by Burley's hash-based
+/// method ("Practical Hash-based Owen Scrambling", 2020). Dimensions come in independently
+/// scrambled and shuffled groups of four, so only the first four of Sobol's are needed.
+/// Owen scrambling keeps the sequence's stratification, and is best with powers of two samples
+pub struct SobolSampler {
+    // The direction numbers of each dimension of a group, as the columns of its generator matrix
+    directions: [[u32; 32]; 4],
+}
+
+impl SobolSampler {
+    pub fn new() -> Self {
+        let mut directions = [[0; 32]; 4];
+        // The first dimension is the van der Corput sequence
+        for (bit, direction) in directions[0].iter_mut().enumerate() {
+            *direction = 1 << (31 - bit);
+        }
+        for ((degree, coefficients, initial), directions) in POLYNOMIALS.into_iter().zip(&mut directions[1..]) {
+            let mut m = [0u32; 32];
+            for k in 0..32 {
+                m[k] = if k < degree {
+                    initial[k]
+                } else {
+                    let mut value = m[k - degree] ^ (m[k - degree] << degree);
+                    for j in 1..degree {
+                        if (coefficients >> (degree - 1 - j)) & 1 == 1 {
+                            value ^= m[k - j] << j;
+                        }
+                    }
+                    value
+                };
+                directions[k] = m[k] << (31 - k);
+            }
+        }
+        Self {
+            directions,
+        }
+    }
+
+    /// Component `dimension` (of the four) of the `index`th unscrambled point
+    fn sobol(&self, index: u32, dimension: usize) -> u32 {
+        (0..32).filter(|bit| (index >> bit) & 1 == 1).fold(0, |value, bit| value ^ self.directions[dimension][bit])
+    }
+}
+
+impl Default for SobolSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn sample(&self, seed: u64, pixel: (u16, u16), index: u32, _samples_per_pixel: u32, dimension: u32) -> f64 {
+        let group = hash(&[seed, pixel.0.into(), pixel.1.into(), (dimension / 4).into()]);
+        // Shuffling the order of points decorrelates the groups
+        let index = nested_uniform_scramble(index, group as u32);
+        let component = (dimension % 4) as usize;
+        let value = nested_uniform_scramble(self.sobol(index, component), hash(&[group, component as u64]) as u32);
+        value as f64 / (1u64 << 32) as f64
+    }
+}
+
+/// Laine and Karras' hash, in which each bit is only affected by those below it
+fn laine_karras_permutation(x: u32, seed: u32) -> u32 {
+    let mut x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
+
+/// Owen scrambling of the binary fraction `x`: each bit is flipped
+/// or not depending on the bits above it, by a hash of them
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unscrambled_points_match_sobol_sequence() {
+        let sampler = SobolSampler::new();
+        let point = |index| [0, 1, 2, 3].map(|dimension| sampler.sobol(index, dimension) as f64 / (1u64 << 32) as f64);
+        assert_eq!(point(0), [0.0; 4]);
+        assert_eq!(point(1), [0.5; 4]);
+        assert_eq!(point(2), [0.25, 0.75, 0.75, 0.75]);
+        assert_eq!(point(3), [0.75, 0.25, 0.25, 0.25]);
+        assert_eq!(point(4), [0.125, 0.625, 0.375, 0.125]);
+    }
+
+    #[test]
+    fn scrambled_points_are_stratified() {
+        // Each of the first 2^k points falls in its own 2D elementary interval of any shape
+        let sampler = SobolSampler::new();
+        for (columns, rows) in [(16, 1), (4, 4), (2, 8)] {
+            let mut cells: Vec<u32> = (0..16).map(|index| {
+                let (u, v) = (sampler.sample(17, (5, 1), index, 16, 4), sampler.sample(17, (5, 1), index, 16, 5));
+                (u * columns as f64) as u32 * rows + (v * rows as f64) as u32
+            }).collect();
+            cells.sort();
+            assert_eq!(cells, (0..16).collect::<Vec<_>>());
+        }
+    }
+}
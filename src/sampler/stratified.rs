@@ -0,0 +1,86 @@
+use super::*;
+
+/// Splits each dimension into as many equal strata as a pixel has samples, jittering one
+/// sample within each. The strata are shuffled independently for every dimension and pixel
+/// (as in Latin hypercube sampling), so that dimensions aren't correlated
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StratifiedSampler;
+
+impl StratifiedSampler {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn sample(&self, seed: u64, pixel: (u16, u16), index: u32, samples_per_pixel: u32, dimension: u32) -> f64 {
+        let shuffle = hash(&[seed, pixel.0.into(), pixel.1.into(), dimension.into()]);
+        let stratum = permute(index % samples_per_pixel, samples_per_pixel, shuffle as u32);
+        let jitter = to_uniform(hash(&[shuffle, index.into()]));
+        (stratum as f64 + jitter) / samples_per_pixel as f64
+    }
+}
+
+/// The element at `index` of a random permutation of 0..`length`, chosen by `seed`, by
+/// Kensler's hash (from "Correlated Multi-Jittered Sampling", 2013), so that no
+/// permutation need be stored. Hashes outside the range are walked on until inside it
+pub(crate) fn permute(index: u32, length: u32, seed: u32) -> u32 {
+    let mut mask = length - 1;
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+    let mut i = index;
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= seed >> 16;
+        i ^= (i & mask) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= seed >> 23;
+        i ^= (i & mask) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & mask) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & mask) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & mask) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= mask;
+        i ^= i >> 5;
+        if i < length {
+            return (i + seed) % length
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permutations_visit_every_index() {
+        for length in [1, 5, 16, 100] {
+            for seed in [0, 1, 0xdeadbeef] {
+                let mut seen = vec![false; length as usize];
+                for index in 0..length {
+                    seen[permute(index, length, seed) as usize] = true;
+                }
+                assert!(seen.iter().all(|seen| *seen));
+            }
+        }
+    }
+
+    #[test]
+    fn every_stratum_has_one_sample() {
+        let sampler = StratifiedSampler::new();
+        for dimension in 0..5 {
+            let mut strata: Vec<u32> = (0..10).map(|index| (sampler.sample(17, (4, 2), index, 10, dimension) * 10.0) as u32).collect();
+            strata.sort();
+            assert_eq!(strata, (0..10).collect::<Vec<_>>());
+        }
+    }
+}
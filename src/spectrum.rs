@@ -0,0 +1,187 @@
+use crate::geometry::Vector;
+
+/// The shortest wavelength of visible light rendered, in nanometres
+pub const MIN_WAVELENGTH: f64 = 380.0;
+/// The longest wavelength of visible light rendered, in nanometres
+pub const MAX_WAVELENGTH: f64 = 720.0;
+
+/// Smits' spectra (in ten equal bins over the visible range) which, mixed
+/// in proportion to the differences between an RGB colour's components,
+/// give a smooth reflectance spectrum with that colour
+const WHITE: [f64; 10] = [1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000];
+const CYAN: [f64; 10] = [0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000];
+const MAGENTA: [f64; 10] = [1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959];
+const YELLOW: [f64; 10] = [0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840];
+const RED: [f64; 10] = [0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149];
+const GREEN: [f64; 10] = [0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025];
+const BLUE: [f64; 10] = [1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496];
+
+/// The integrals of the colour matching functions over the visible range, i.e. the
+/// XYZ of the flat spectrum of 1 (the equal energy white, illuminant E)
+const MATCHING_INTEGRALS: [f64; 3] = [106.7607, 106.9119, 106.8253];
+
+/// The XYZ of sRGB's white point, illuminant D65, with a luminance of 1
+const D65_WHITE: [f64; 3] = [0.95047, 1.0, 1.08883];
+
+/// The matrix from XYZ to linear sRGB, whose white is D65
+const XYZ_TO_SRGB: [[f64; 3]; 3] = [
+    [3.2406, -1.5372, -0.4986],
+    [-0.9689, 1.8758, 0.0415],
+    [0.0557, -0.2040, 1.0570],
+];
+
+/// The Bradford matrix from XYZ to the cone responses in which whites are adapted, and back
+const BRADFORD: [[f64; 3]; 3] = [
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+];
+const BRADFORD_INVERSE: [[f64; 3]; 3] = [
+    [0.9869929, -0.1470543, 0.1599627],
+    [0.4323053, 0.5183603, 0.0492912],
+    [-0.0085287, 0.0400428, 0.9684867],
+];
+
+/// Smits' spectra reproduce colours whose white is the flat spectrum's, as seen by scaling
+/// each sRGB component alone. This matrix (which keeps white as it is) takes an sRGB colour
+/// to the colour to uplift instead, so that `xyz_to_rgb`'s white balance gives it back
+const SMITS_FROM_SRGB: [[f64; 3]; 3] = [
+    [0.888412, 0.104037, 0.007542],
+    [-0.007038, 1.006550, 0.000412],
+    [-0.002974, -0.006573, 1.009714],
+];
+
+fn transform(matrix: &[[f64; 3]; 3], v: [f64; 3]) -> [f64; 3] {
+    matrix.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+}
+
+/// Picks a wavelength uniformly over the visible range, given a uniform random number in [0, 1)
+pub fn sample_wavelength(u: f64) -> f64 {
+    MIN_WAVELENGTH + u * (MAX_WAVELENGTH - MIN_WAVELENGTH)
+}
+
+/// The CIE 1931 colour matching functions at `wavelength` (in nanometres), as
+/// the XYZ components of a `Vector`, by the multi-lobe fit of Wyman et al. (2013)
+pub fn colour_matching(wavelength: f64) -> Vector {
+    let lobe = |mean: f64, below: f64, above: f64| {
+        let width = if wavelength < mean { below } else { above };
+        (-0.5 * ((wavelength - mean) / width).powi(2)).exp()
+    };
+    Vector::new(
+        1.056 * lobe(599.8, 37.9, 31.0) + 0.362 * lobe(442.0, 16.0, 26.7) - 0.065 * lobe(501.1, 20.4, 26.2),
+        0.821 * lobe(568.8, 46.9, 40.5) + 0.286 * lobe(530.9, 16.3, 31.1),
+        1.217 * lobe(437.0, 11.8, 36.0) + 0.681 * lobe(459.0, 26.0, 13.8),
+    )
+}
+
+/// Converts a single-wavelength sample `value` of a spectrum, at a `wavelength` picked by
+/// `sample_wavelength`, to an estimate of the spectrum's XYZ colour, scaled so that the flat
+/// spectrum of 1 (to which `uplift` takes white) has a luminance (Y) of 1. Estimates are
+/// averaged in XYZ, and converted once with `xyz_to_rgb`
+pub fn to_xyz(value: f64, wavelength: f64) -> Vector {
+    let pdf = 1.0 / (MAX_WAVELENGTH - MIN_WAVELENGTH);
+    value * colour_matching(wavelength) / (pdf * MATCHING_INTEGRALS[1])
+}
+
+/// Converts the XYZ colour `xyz`, as from `to_xyz`, to linear sRGB. The colour is white
+/// balanced by the Bradford transform from the flat spectrum's white to sRGB's, so that
+/// white surfaces under white light stay white
+pub fn xyz_to_rgb(xyz: Vector) -> Vector {
+    let source_white = transform(&BRADFORD, MATCHING_INTEGRALS.map(|c| c / MATCHING_INTEGRALS[1]));
+    let target_white = transform(&BRADFORD, D65_WHITE);
+    let [l, m, s] = transform(&BRADFORD, [xyz.x, xyz.y, xyz.z]);
+    let cones = [l * target_white[0] / source_white[0], m * target_white[1] / source_white[1], s * target_white[2] / source_white[2]];
+    let [r, g, b] = transform(&XYZ_TO_SRGB, transform(&BRADFORD_INVERSE, cones));
+    Vector::new(r, g, b)
+}
+
+/// The linear sRGB colour estimated by a single-wavelength sample, as
+/// for `to_xyz`. Being linear, estimates may equally be averaged in RGB
+pub fn to_rgb(value: f64, wavelength: f64) -> Vector {
+    xyz_to_rgb(to_xyz(value, wavelength))
+}
+
+/// The value at `wavelength` (in nanometres) of a smooth spectrum
+/// with the linear RGB colour `rgb`, by Smits' method
+pub fn uplift(rgb: Vector, wavelength: f64) -> f64 {
+    let bin = (((wavelength - MIN_WAVELENGTH) / (MAX_WAVELENGTH - MIN_WAVELENGTH) * 10.0) as usize).min(9);
+    let [r, g, b] = transform(&SMITS_FROM_SRGB, [rgb.x, rgb.y, rgb.z]);
+    // The smallest component is white, the next
+    // a secondary colour, and the rest a primary
+    let value = if r <= g && r <= b {
+        r * WHITE[bin] + if g <= b {
+            (g - r) * CYAN[bin] + (b - g) * BLUE[bin]
+        } else {
+            (b - r) * CYAN[bin] + (g - b) * GREEN[bin]
+        }
+    } else if g <= r && g <= b {
+        g * WHITE[bin] + if r <= b {
+            (r - g) * MAGENTA[bin] + (b - r) * BLUE[bin]
+        } else {
+            (b - g) * MAGENTA[bin] + (r - b) * RED[bin]
+        }
+    } else {
+        b * WHITE[bin] + if r <= g {
+            (r - b) * YELLOW[bin] + (g - r) * GREEN[bin]
+        } else {
+            (g - b) * YELLOW[bin] + (r - g) * RED[bin]
+        }
+    };
+    value.max(0.0)
+}
+
+/// The brightness of the linear sRGB `colour`
+pub(crate) fn luminance(colour: Vector) -> f64 {
+    0.2126 * colour.x + 0.7152 * colour.y + 0.0722 * colour.z
+}
+
+/// `rgb` as seen by light of the `wavelength` being traced: unchanged when rendering in RGB (with
+/// no wavelength), or else its uplifted spectrum's value there (in every component, so it still
+/// scales colours)
+pub(crate) fn at_wavelength(rgb: Vector, wavelength: Option<f64>) -> Vector {
+    match wavelength {
+        Some(wavelength) => {
+            let value = uplift(rgb, wavelength);
+            Vector::new(value, value, value)
+        },
+        None => rgb,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The mean of `to_rgb` over evenly spaced wavelengths, which converges to the colour
+    fn colour_of(spectrum: impl Fn(f64) -> f64) -> Vector {
+        let steps = 3400;
+        (0..steps)
+            .map(|i| sample_wavelength((i as f64 + 0.5) / steps as f64))
+            .map(|wavelength| to_rgb(spectrum(wavelength), wavelength))
+            .sum::<Vector>() / steps as f64
+    }
+
+    #[test]
+    fn colours_are_seen_at_the_wavelength_traced() {
+        let colour = Vector::new(0.7, 0.4, 0.1);
+        assert_eq!(at_wavelength(colour, None), colour);
+        let red = at_wavelength(colour, Some(650.0));
+        assert_eq!(red, Vector::new(red.x, red.x, red.x));
+        assert!(red.x > at_wavelength(colour, Some(450.0)).x);
+        assert!((at_wavelength(Vector::new(0.2, 0.2, 0.2), Some(500.0)) - Vector::new(0.2, 0.2, 0.2)).l2_norm() < 1e-3);
+    }
+
+    #[test]
+    fn flat_spectrum_is_white() {
+        let white = colour_of(|_| 1.0);
+        assert!((white - Vector::new(1.0, 1.0, 1.0)).l2_norm() < 1e-3, "{:?}", white);
+    }
+
+    #[test]
+    fn uplifting_preserves_colour() {
+        for rgb in [Vector::new(1.0, 1.0, 1.0), Vector::new(0.8, 0.2, 0.1), Vector::new(0.1, 0.5, 0.3), Vector::new(0.2, 0.3, 0.9)] {
+            let colour = colour_of(|wavelength| uplift(rgb, wavelength));
+            assert!((colour - rgb).l2_norm() < 0.02, "{:?} vs {:?}", colour, rgb);
+        }
+    }
+}
@@ -1,23 +1,80 @@
 use super::*;
 
+/// How a material's refractive index varies with the wavelength of light,
+/// splitting white light into its colours
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dispersion {
+    /// Cauchy's equation, n = a + b / λ², with λ in micrometres
+    Cauchy { a: f64, b: f64 },
+    /// The Sellmeier equation, n² = 1 + Σ bᵢ λ² / (λ² - cᵢ), with λ in micrometres
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    /// Borosilicate crown glass (Schott N-BK7), as in most lenses
+    pub const BK7: Self = Self::Sellmeier {
+        b: [1.03961212, 0.231792344, 1.01046945],
+        c: [0.00600069867, 0.0200179144, 103.560653],
+    };
+
+    /// Dense flint glass (Schott SF11), which disperses light strongly, as in prisms
+    pub const SF11: Self = Self::Sellmeier {
+        b: [1.73759695, 0.313747346, 1.89878101],
+        c: [0.013188707, 0.0623068142, 155.23629],
+    };
+
+    /// The refractive index for light of `wavelength`, in nanometres
+    pub fn refraction_index(&self, wavelength: f64) -> f64 {
+        let lambda = wavelength / 1000.0;
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / lambda.powi(2),
+            Dispersion::Sellmeier { b, c } => {
+                let n2 = 1.0 + (0..3).map(|i| b[i] * lambda.powi(2) / (lambda.powi(2) - c[i])).sum::<f64>();
+                n2.sqrt()
+            },
+        }
+    }
+}
+
+/// The wavelength of the sodium D line, in nanometres, at which
+/// refractive indices are conventionally quoted
+const SODIUM_D_LINE: f64 = 589.3;
+
 /// A Dielectric material always refracts the incident ray according to
 /// its refraction index
 pub struct Dielectric {
     refraction_index: f64,
+    dispersion: Option<Dispersion>,
 }
 
 impl Dielectric {
     pub fn new(refraction_index: f64) -> Self {
-        Self { refraction_index }
+        Self {
+            refraction_index,
+            dispersion: None,
+        }
+    }
+
+    /// Create a dielectric whose refractive index varies with wavelength. Rays
+    /// without a wavelength see its index at the sodium D line (589.3 nm)
+    pub fn dispersive(dispersion: Dispersion) -> Self {
+        Self {
+            refraction_index: dispersion.refraction_index(SODIUM_D_LINE),
+            dispersion: Some(dispersion),
+        }
     }
 }
 
 impl Material for Dielectric {
-    fn random_reflection(&self, ray_direction: UnitVector, rebound_normal: UnitVector, entering_surface: impl Fn() -> bool) -> Option<Reflection> {
-        let relative_index = if entering_surface() {
-            self.refraction_index
+    fn random_reflection(&self, ray_direction: UnitVector, rebound_normal: UnitVector, hit: &HitRecord, wavelength: Option<f64>, _rng: &mut dyn RandomStream) -> Option<Reflection> {
+        let refraction_index = match (self.dispersion, wavelength) {
+            (Some(dispersion), Some(wavelength)) => dispersion.refraction_index(wavelength),
+            _ => self.refraction_index,
+        };
+        let relative_index = if hit.front_face {
+            refraction_index
         } else {
-            1.0 / self.refraction_index
+            1.0 / refraction_index
         };
         let n = rebound_normal.to_vector();
         let refracted_perpendicular = 1.0 / relative_index * (ray_direction - ray_direction.dot(n) * n);
@@ -29,4 +86,42 @@ impl Material for Dielectric {
             direction: UnitVector::from(refracted_parallel + refracted_perpendicular),
         })
     }
+
+    /// Clear glass passes on all the light it doesn't reflect
+    fn albedo(&self, _hit: &HitRecord, _wavelength: Option<f64>) -> Vector {
+        Vector::new(1.0, 1.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::TextureCoordinates;
+
+    #[test]
+    fn glass_disperses_blue_more_than_red() {
+        for glass in [Dispersion::BK7, Dispersion::SF11, Dispersion::Cauchy { a: 1.5046, b: 0.0042 }] {
+            assert!(glass.refraction_index(450.0) > glass.refraction_index(650.0));
+        }
+        assert!((Dispersion::BK7.refraction_index(587.6) - 1.5168).abs() < 1e-4);
+        assert!((Dispersion::SF11.refraction_index(587.6) - 1.7847).abs() < 1e-4);
+    }
+
+    #[test]
+    fn rays_of_each_wavelength_refract_differently() {
+        let glass = Dielectric::dispersive(Dispersion::SF11);
+        let normal = UnitVector::from(Vector::new(0.0, 0.0, 1.0));
+        let incoming = UnitVector::from(Vector::new(1.0, 0.0, -1.0));
+        let ray = Ray::new(Point::new(-1.0, 0.0, 1.0), incoming);
+        let hit = HitRecord::new(ray, 1.0, normal, TextureCoordinates::new(0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        // The sine of the angle of refraction, for light of `wavelength`
+        let refracted = |wavelength| {
+            glass.random_reflection(incoming, hit.rebound_normal(), &hit, wavelength, &mut rand::thread_rng()).unwrap().direction.x
+        };
+        let sin_incidence = 0.5f64.sqrt();
+        let (blue, red) = (refracted(Some(450.0)), refracted(Some(650.0)));
+        assert!((blue - sin_incidence / Dispersion::SF11.refraction_index(450.0)).abs() < 1e-12);
+        assert!(blue < red);
+        assert!((refracted(None) - sin_incidence / Dispersion::SF11.refraction_index(589.3)).abs() < 1e-12);
+    }
 }
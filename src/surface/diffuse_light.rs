@@ -0,0 +1,32 @@
+use super::*;
+use crate::texture::Texture;
+
+/// An emissive material, which radiates light uniformly from the front
+/// face of its `Shape` and absorbs all light falling on it
+pub struct DiffuseLight<T: Texture> {
+    radiance: T,
+}
+
+impl<T: Texture> DiffuseLight<T> {
+    pub fn new(radiance: T) -> Self {
+        Self { radiance }
+    }
+}
+
+impl<T: Texture> Material for DiffuseLight<T> {
+    fn random_reflection(&self, _ray_direction: UnitVector, _rebound_normal: UnitVector, _hit: &HitRecord, _wavelength: Option<f64>, _rng: &mut dyn RandomStream) -> Option<Reflection> {
+        None
+    }
+
+    fn emitted(&self, hit: &HitRecord) -> Vector {
+        if hit.front_face {
+            self.radiance.value(hit.point, hit.uv)
+        } else {
+            Vector::zero()
+        }
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
+}
@@ -1,25 +1,49 @@
 use super::*;
+use crate::{
+    geometry::Frame,
+    texture::Texture,
+};
+
+use core::f64::consts::PI;
 
 /// A Lambertian material scatters a ray in a random direction
 /// from the point of incidence. The reflected ray's direction
 /// has a distribution proportional to the cosine of the angle
 /// between the incident ray and the normal (against the ray)
 /// at the point of intersection.
-pub struct Lambertian {
-    albedo: Vector,
+pub struct Lambertian<T: Texture> {
+    albedo: T,
 }
 
-impl Lambertian {
-    pub fn new(albedo: Vector) -> Self {
+impl<T: Texture> Lambertian<T> {
+    pub fn new(albedo: T) -> Self {
         Self { albedo }
     }
 }
 
-impl Material for Lambertian {
-    fn random_reflection(&self, _ray_direction: UnitVector, rebound_normal: UnitVector, _entering_surface: impl Fn() -> bool) -> Option<Reflection> {
+impl<T: Texture> Material for Lambertian<T> {
+    fn random_reflection(&self, _ray_direction: UnitVector, rebound_normal: UnitVector, hit: &HitRecord, _wavelength: Option<f64>, rng: &mut dyn RandomStream) -> Option<Reflection> {
         Some(Reflection {
-            attenuation: self.albedo,
-            direction: UnitVector::from(rebound_normal + UnitVector::random()),
+            attenuation: self.albedo.value(hit.point, hit.uv),
+            direction: UnitVector::from(Frame::from_normal(rebound_normal).to_world(
+                cosine_hemisphere(rng.next_uniform(), rng.next_uniform())
+            )),
         })
     }
+
+    fn eval(&self, wi: UnitVector, wo: UnitVector, rebound_normal: UnitVector, hit: &HitRecord, _wavelength: Option<f64>) -> Vector {
+        let cos_i = wi.dot(rebound_normal.to_vector());
+        if cos_i <= 0.0 || wo.dot(rebound_normal.to_vector()) <= 0.0 {
+            return Vector::zero()
+        }
+        cos_i / PI * self.albedo.value(hit.point, hit.uv)
+    }
+
+    fn pdf(&self, wi: UnitVector, _wo: UnitVector, rebound_normal: UnitVector, _hit: &HitRecord, _wavelength: Option<f64>) -> f64 {
+        wi.dot(rebound_normal.to_vector()).max(0.0) / PI
+    }
+
+    fn albedo(&self, hit: &HitRecord, _wavelength: Option<f64>) -> Vector {
+        self.albedo.value(hit.point, hit.uv)
+    }
 }
@@ -1,23 +1,28 @@
 use super::*;
+use crate::texture::Texture;
 
 /// A Metal material perfectly reflects an incident ray such
 /// that the angle between the point of incidence and the normal
 /// (against the ray) is preserved.
-pub struct Metal {
-    albedo: Vector,
+pub struct Metal<T: Texture> {
+    albedo: T,
 }
 
-impl Metal {
-    pub fn new(albedo: Vector) -> Self {
+impl<T: Texture> Metal<T> {
+    pub fn new(albedo: T) -> Self {
         Self { albedo }
     }
 }
 
-impl Material for Metal {
-    fn random_reflection(&self, ray_direction: UnitVector, rebound_normal: UnitVector, _entering_surface: impl Fn() -> bool) -> Option<Reflection> {
+impl<T: Texture> Material for Metal<T> {
+    fn random_reflection(&self, ray_direction: UnitVector, rebound_normal: UnitVector, hit: &HitRecord, _wavelength: Option<f64>, _rng: &mut dyn RandomStream) -> Option<Reflection> {
         Some(Reflection {
-            attenuation: self.albedo,
+            attenuation: self.albedo.value(hit.point, hit.uv),
             direction: UnitVector::from(ray_direction - 2.0 * rebound_normal * ray_direction.dot(rebound_normal.to_vector())),
         })
     }
+
+    fn albedo(&self, hit: &HitRecord, _wavelength: Option<f64>) -> Vector {
+        self.albedo.value(hit.point, hit.uv)
+    }
 }
@@ -0,0 +1,548 @@
+use super::*;
+use crate::{
+    geometry::Frame,
+    texture::Texture,
+};
+
+use core::f64::consts::PI;
+
+/// Below this, the GGX distribution is numerically unstable, so
+/// materials treat distributions this smooth as perfect mirrors
+const MIN_ALPHA: f64 = 1e-3;
+
+/// The GGX (Trowbridge-Reitz) distribution of microfacet normals, with Smith
+/// masking-shadowing. All directions are in a local frame in which the
+/// macrosurface normal is the z axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ggx {
+    alpha: f64,
+}
+
+impl Ggx {
+    /// Create the distribution for a perceptual `roughness` in [0, 1],
+    /// with 0 a mirror and 1 very rough
+    pub fn from_roughness(roughness: f64) -> Self {
+        Self {
+            alpha: roughness.clamp(0.0, 1.0).powi(2).max(MIN_ALPHA),
+        }
+    }
+
+    /// True iff the distribution is at its minimum roughness, where materials
+    /// reflect and refract in the macrosurface normal alone, as a mirror would
+    pub fn is_smooth(&self) -> bool {
+        self.alpha <= MIN_ALPHA
+    }
+
+    /// The density of microfacet normals `m` (per unit projected area)
+    pub fn d(&self, m: Vector) -> f64 {
+        if m.z <= 0.0 {
+            return 0.0
+        }
+        let alpha2 = self.alpha.powi(2);
+        alpha2 / (PI * (m.z.powi(2) * (alpha2 - 1.0) + 1.0).powi(2))
+    }
+
+    /// Smith's auxiliary function for the direction `w`
+    fn lambda(&self, w: Vector) -> f64 {
+        let cos2 = w.z.powi(2);
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        0.5 * (-1.0 + (1.0 + self.alpha.powi(2) * tan2).sqrt())
+    }
+
+    /// The fraction of microfacets visible from direction `w`
+    pub fn g1(&self, w: Vector) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// The (height-correlated) fraction of microfacets visible
+    /// from both directions `wo` and `wi`
+    pub fn g2(&self, wo: Vector, wi: Vector) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Samples a microfacet normal visible from `wo` (which must lie in the upper
+    /// hemisphere), given two uniform random numbers in [0, 1)
+    /// See Heitz (2018) "Sampling the GGX Distribution of Visible Normals"
+    pub fn sample_visible_normal(&self, wo: Vector, u1: f64, u2: f64) -> Vector {
+        // Stretch the view direction so the distribution becomes a hemisphere
+        let v = Vector::new(self.alpha * wo.x, self.alpha * wo.y, wo.z).normalise();
+        let length2 = v.x.powi(2) + v.y.powi(2);
+        let t1 = if length2 > 0.0 {
+            Vector::new(-v.y, v.x, 0.0) / length2.sqrt()
+        } else {
+            Vector::new(1.0, 0.0, 0.0)
+        };
+        let t2 = v.cross(t1);
+        // Sample the projected area of the hemisphere as seen from v
+        let r = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + v.z);
+        let p2 = (1.0 - s) * (1.0 - p1.powi(2)).sqrt() + s * r * phi.sin();
+        let n = p1 * t1 + p2 * t2 + (1.0 - p1.powi(2) - p2.powi(2)).max(0.0).sqrt() * v;
+        // Unstretch
+        Vector::new(self.alpha * n.x, self.alpha * n.y, n.z.max(0.0)).normalise()
+    }
+
+    /// The density of `sample_visible_normal` returning `m`, given `wo`
+    pub fn visible_normal_pdf(&self, wo: Vector, m: Vector) -> f64 {
+        self.g1(wo) * wo.dot(m).max(0.0) * self.d(m) / wo.z
+    }
+
+    /// The BRDF of perfectly reflective (i.e. Fresnel-free) microfacets
+    pub fn reflection_brdf(&self, wo: Vector, wi: Vector) -> f64 {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return 0.0
+        }
+        let m = (wo + wi).normalise();
+        self.d(m) * self.g2(wo, wi) / (4.0 * wo.z * wi.z)
+    }
+
+    /// The density of reflecting `wo` in a visible normal to give `wi`
+    pub fn reflection_pdf(&self, wo: Vector, wi: Vector) -> f64 {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return 0.0
+        }
+        let m = (wo + wi).normalise();
+        self.visible_normal_pdf(wo, m) / (4.0 * wo.dot(m))
+    }
+
+    /// The half vector between `wo` and the refracted `wi`, if they are consistent
+    fn refraction_half_vector(wo: Vector, wi: Vector, eta: f64) -> Option<Vector> {
+        if wo.z <= 0.0 || wi.z >= 0.0 {
+            return None
+        }
+        let m = (wo + eta * wi).normalise();
+        let m = if m.z < 0.0 { -1.0 * m } else { m };
+        (wo.dot(m) > 0.0 && wi.dot(m) < 0.0).then_some(m)
+    }
+
+    /// The BTDF of perfectly transmissive (i.e. Fresnel-free) microfacets,
+    /// with relative refractive index `eta` as for `refract`
+    pub fn refraction_btdf(&self, wo: Vector, wi: Vector, eta: f64) -> f64 {
+        let m = match Self::refraction_half_vector(wo, wi, eta) {
+            Some(m) => m,
+            None => return 0.0,
+        };
+        let denominator = (wo.dot(m) + eta * wi.dot(m)).powi(2);
+        self.d(m) * self.g2(wo, wi) * eta.powi(2) * wo.dot(m) * wi.dot(m).abs()
+            / (wo.z * wi.z.abs() * denominator)
+    }
+
+    /// The density of refracting `wo` through a visible normal to give `wi`
+    pub fn refraction_pdf(&self, wo: Vector, wi: Vector, eta: f64) -> f64 {
+        let m = match Self::refraction_half_vector(wo, wi, eta) {
+            Some(m) => m,
+            None => return 0.0,
+        };
+        let denominator = (wo.dot(m) + eta * wi.dot(m)).powi(2);
+        self.visible_normal_pdf(wo, m) * eta.powi(2) * wi.dot(m).abs() / denominator
+    }
+
+    /// The BSDF of rough glass (i.e. microfacets which reflect or refract
+    /// according to the Fresnel equations) and the density with which
+    /// `sample_dielectric` picks `wi`, as a pair
+    pub fn dielectric_bsdf(&self, wo: Vector, wi: Vector, eta: f64) -> (f64, f64) {
+        if wi.z > 0.0 {
+            let f = fresnel_dielectric(wo.dot((wo + wi).normalise()), eta);
+            (f * self.reflection_brdf(wo, wi), f * self.reflection_pdf(wo, wi))
+        } else {
+            let f = Self::refraction_half_vector(wo, wi, eta)
+                .map_or(1.0, |m| fresnel_dielectric(wo.dot(m), eta));
+            ((1.0 - f) * self.refraction_btdf(wo, wi, eta), (1.0 - f) * self.refraction_pdf(wo, wi, eta))
+        }
+    }
+
+    /// Samples a visible normal, and then reflects or refracts `wo` in it in proportion to the
+    /// Fresnel reflectance. Returns None if the resulting direction is inconsistent
+    pub fn sample_dielectric(&self, wo: Vector, eta: f64, rng: &mut dyn RandomStream) -> Option<Vector> {
+        let m = self.sample_visible_normal(wo, rng.next_uniform(), rng.next_uniform());
+        scatter_dielectric(wo, m, eta, rng)
+    }
+
+    /// Samples a visible normal, and reflects `wo` in it. Returns
+    /// None if the reflected direction is below the surface
+    pub fn sample_reflection(&self, wo: Vector, rng: &mut dyn RandomStream) -> Option<Vector> {
+        let m = self.sample_visible_normal(wo, rng.next_uniform(), rng.next_uniform());
+        let wi = reflect(wo, m);
+        (wi.z > 0.0).then_some(wi)
+    }
+}
+
+/// Reflects or refracts `wo` in the normal `m`, at random in proportion to the Fresnel
+/// reflectance, as for `Ggx::sample_dielectric`
+fn scatter_dielectric(wo: Vector, m: Vector, eta: f64, rng: &mut dyn RandomStream) -> Option<Vector> {
+    let refracted = match refract(wo, m, eta) {
+        Some(wi) if rng.next_uniform() >= fresnel_dielectric(wo.dot(m), eta) => Some(wi),
+        _ => None,
+    };
+    match refracted {
+        Some(wi) => (wi.z < 0.0).then_some(wi),
+        None => {
+            let wi = reflect(wo, m);
+            (wi.z > 0.0).then_some(wi)
+        },
+    }
+}
+
+/// Returns `wo` mirrored in the normal `m`
+pub fn reflect(wo: Vector, m: Vector) -> Vector {
+    2.0 * wo.dot(m) * m - wo
+}
+
+/// Returns `wo` refracted through the normal `m` (on the same side as `wo`),
+/// or None under total internal reflection
+/// # Parameters
+/// 1. `eta` - the ratio of the refractive index beyond the surface to that
+///    on the side of `wo`
+pub fn refract(wo: Vector, m: Vector, eta: f64) -> Option<Vector> {
+    let cos_i = wo.dot(m);
+    let sin2_t = (1.0 - cos_i.powi(2)).max(0.0) / eta.powi(2);
+    if sin2_t >= 1.0 {
+        return None
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-1.0 * wo / eta + (cos_i / eta - cos_t) * m)
+}
+
+/// The fraction of unpolarised light reflected at a dielectric interface
+/// # Parameters
+/// 1. `cos_i` - the cosine of the angle of incidence
+/// 1. `eta` - the ratio of the refractive index beyond the interface to
+///    that on the incident side
+/// # Example
+/// ```
+/// use ray_tracing::fresnel_dielectric;
+/// assert!((fresnel_dielectric(1.0, 1.5) - 0.04).abs() < 1e-12);
+/// assert_eq!(fresnel_dielectric(0.1, 1.0 / 1.5), 1.0);
+/// ```
+pub fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_i.powi(2)) / eta.powi(2);
+    if sin2_t >= 1.0 {
+        return 1.0
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let r_s = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    let r_p = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    0.5 * (r_s.powi(2) + r_p.powi(2))
+}
+
+/// The fraction of unpolarised light reflected by a conductor with
+/// complex refractive index `eta` + i`k`, for each colour channel
+pub fn fresnel_conductor(cos_i: f64, eta: Vector, k: Vector) -> Vector {
+    let cos2 = cos_i.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+    let channel = |eta: f64, k: f64| {
+        let t0 = eta.powi(2) - k.powi(2) - sin2;
+        let a2_plus_b2 = (t0.powi(2) + 4.0 * eta.powi(2) * k.powi(2)).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+        let t2 = 2.0 * cos_i * a;
+        let r_s = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2_plus_b2 + sin2.powi(2);
+        let t4 = t2 * sin2;
+        let r_p = r_s * (t3 - t4) / (t3 + t4);
+        0.5 * (r_s + r_p)
+    };
+    Vector::new(channel(eta.x, k.x), channel(eta.y, k.y), channel(eta.z, k.z))
+}
+
+/// Schlick's approximation to the Fresnel reflectance, given
+/// the reflectance `f0` at normal incidence
+pub fn fresnel_schlick(cos_i: f64, f0: Vector) -> Vector {
+    f0 + (1.0 - f0) * (1.0 - cos_i.clamp(0.0, 1.0)).powi(5)
+}
+
+/// How a `RoughConductor` reflects light at each microfacet
+pub enum ConductorFresnel<T: Texture> {
+    /// Exact reflectance from the measured complex refractive index per channel
+    ComplexIor {
+        eta: Vector,
+        k: Vector,
+    },
+    /// Schlick's approximation from an artist-friendly reflectance at normal incidence
+    Schlick(T),
+}
+
+/// A rough metal, modelled as a surface of mirror-like GGX microfacets
+pub struct RoughConductor<T: Texture> {
+    distribution: Ggx,
+    fresnel: ConductorFresnel<T>,
+}
+
+impl RoughConductor<Vector> {
+    /// Create a conductor from its measured refractive index `eta` and
+    /// extinction coefficient `k`, e.g. gold is roughly
+    /// eta = (0.18, 0.42, 1.37) and k = (3.42, 2.35, 1.77)
+    pub fn from_complex_ior(eta: Vector, k: Vector, roughness: f64) -> Self {
+        Self {
+            distribution: Ggx::from_roughness(roughness),
+            fresnel: ConductorFresnel::ComplexIor { eta, k },
+        }
+    }
+}
+
+impl<T: Texture> RoughConductor<T> {
+    /// Create a conductor from its (colour) reflectance at normal incidence
+    pub fn from_f0(f0: T, roughness: f64) -> Self {
+        Self {
+            distribution: Ggx::from_roughness(roughness),
+            fresnel: ConductorFresnel::Schlick(f0),
+        }
+    }
+
+    fn fresnel(&self, cos_i: f64, hit: &HitRecord) -> Vector {
+        match &self.fresnel {
+            ConductorFresnel::ComplexIor { eta, k } => fresnel_conductor(cos_i, *eta, *k),
+            ConductorFresnel::Schlick(f0) => fresnel_schlick(cos_i, f0.value(hit.point, hit.uv)),
+        }
+    }
+}
+
+impl<T: Texture> Material for RoughConductor<T> {
+    fn random_reflection(&self, ray_direction: UnitVector, rebound_normal: UnitVector, hit: &HitRecord, _wavelength: Option<f64>, rng: &mut dyn RandomStream) -> Option<Reflection> {
+        let frame = Frame::from_normal(rebound_normal);
+        let wo = frame.to_local(-1.0 * ray_direction);
+        if wo.z <= 0.0 {
+            return None
+        }
+        if self.distribution.is_smooth() {
+            return Some(Reflection {
+                attenuation: self.fresnel(wo.z, hit),
+                direction: UnitVector::from(frame.to_world(Vector::new(-wo.x, -wo.y, wo.z))),
+            })
+        }
+        let wi = self.distribution.sample_reflection(wo, rng)?;
+        let m = (wo + wi).normalise();
+        let shadowing = self.distribution.g2(wo, wi) / self.distribution.g1(wo);
+        Some(Reflection {
+            attenuation: shadowing * self.fresnel(wo.dot(m), hit),
+            direction: UnitVector::from(frame.to_world(wi)),
+        })
+    }
+
+    /// A smooth conductor is a mirror, with no finite BSDF
+    fn eval(&self, wi: UnitVector, wo: UnitVector, rebound_normal: UnitVector, hit: &HitRecord, _wavelength: Option<f64>) -> Vector {
+        let frame = Frame::from_normal(rebound_normal);
+        let (wo, wi) = (frame.to_local(wo.to_vector()), frame.to_local(wi.to_vector()));
+        let brdf = self.distribution.reflection_brdf(wo, wi);
+        if brdf == 0.0 || self.distribution.is_smooth() {
+            return Vector::zero()
+        }
+        brdf * wi.z * self.fresnel(wo.dot((wo + wi).normalise()), hit)
+    }
+
+    fn pdf(&self, wi: UnitVector, wo: UnitVector, rebound_normal: UnitVector, _hit: &HitRecord, _wavelength: Option<f64>) -> f64 {
+        if self.distribution.is_smooth() {
+            return 0.0
+        }
+        let frame = Frame::from_normal(rebound_normal);
+        self.distribution.reflection_pdf(frame.to_local(wo.to_vector()), frame.to_local(wi.to_vector()))
+    }
+
+    /// The reflectance at normal incidence
+    fn albedo(&self, hit: &HitRecord, _wavelength: Option<f64>) -> Vector {
+        self.fresnel(1.0, hit)
+    }
+}
+
+/// A rough glass-like material, modelled as a surface of GGX microfacets
+/// each of which reflects or refracts according to the Fresnel equations
+pub struct RoughDielectric {
+    refraction_index: f64,
+    distribution: Ggx,
+}
+
+impl RoughDielectric {
+    pub fn new(refraction_index: f64, roughness: f64) -> Self {
+        Self {
+            refraction_index,
+            distribution: Ggx::from_roughness(roughness),
+        }
+    }
+
+    /// The relative refractive index across the surface at `hit`
+    fn eta(&self, hit: &HitRecord) -> f64 {
+        if hit.front_face {
+            self.refraction_index
+        } else {
+            1.0 / self.refraction_index
+        }
+    }
+}
+
+impl Material for RoughDielectric {
+    fn random_reflection(&self, ray_direction: UnitVector, rebound_normal: UnitVector, hit: &HitRecord, _wavelength: Option<f64>, rng: &mut dyn RandomStream) -> Option<Reflection> {
+        let eta = self.eta(hit);
+        let frame = Frame::from_normal(rebound_normal);
+        let wo = frame.to_local(-1.0 * ray_direction);
+        if wo.z <= 0.0 {
+            return None
+        }
+        // Choosing between reflection and refraction in proportion
+        // to the Fresnel reflectance cancels it from the weight
+        if self.distribution.is_smooth() {
+            let wi = scatter_dielectric(wo, Vector::new(0.0, 0.0, 1.0), eta, rng)?;
+            return Some(Reflection {
+                attenuation: Vector::new(1.0, 1.0, 1.0),
+                direction: UnitVector::from(frame.to_world(wi)),
+            })
+        }
+        let wi = self.distribution.sample_dielectric(wo, eta, rng)?;
+        let shadowing = self.distribution.g2(wo, wi) / self.distribution.g1(wo);
+        Some(Reflection {
+            attenuation: Vector::new(shadowing, shadowing, shadowing),
+            direction: UnitVector::from(frame.to_world(wi)),
+        })
+    }
+
+    /// Smooth glass only scatters into isolated directions, with no finite BSDF
+    fn eval(&self, wi: UnitVector, wo: UnitVector, rebound_normal: UnitVector, hit: &HitRecord, _wavelength: Option<f64>) -> Vector {
+        if self.distribution.is_smooth() {
+            return Vector::zero()
+        }
+        let frame = Frame::from_normal(rebound_normal);
+        let (wo, wi) = (frame.to_local(wo.to_vector()), frame.to_local(wi.to_vector()));
+        let (f, _) = self.distribution.dielectric_bsdf(wo, wi, self.eta(hit));
+        let f = f * wi.z.abs();
+        Vector::new(f, f, f)
+    }
+
+    fn pdf(&self, wi: UnitVector, wo: UnitVector, rebound_normal: UnitVector, hit: &HitRecord, _wavelength: Option<f64>) -> f64 {
+        if self.distribution.is_smooth() {
+            return 0.0
+        }
+        let frame = Frame::from_normal(rebound_normal);
+        let (wo, wi) = (frame.to_local(wo.to_vector()), frame.to_local(wi.to_vector()));
+        self.distribution.dielectric_bsdf(wo, wi, self.eta(hit)).1
+    }
+
+    /// Clear glass passes on all the light it doesn't reflect
+    fn albedo(&self, _hit: &HitRecord, _wavelength: Option<f64>) -> Vector {
+        Vector::new(1.0, 1.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{Point, TextureCoordinates};
+
+    fn normal() -> UnitVector {
+        UnitVector::from(Vector::new(0.0, 0.0, 1.0))
+    }
+
+    /// A hit at the origin on the plane z = 0, by a ray in `direction`
+    fn hit(direction: Vector) -> (UnitVector, HitRecord) {
+        let direction = UnitVector::from(direction);
+        let ray = Ray::new(Point::zero() - direction.to_vector(), direction);
+        (direction, HitRecord::new(ray, 1.0, normal(), TextureCoordinates::new(0.0, 0.0), Vector::new(1.0, 0.0, 0.0)))
+    }
+
+    #[test]
+    fn ggx_is_normalised() {
+        // The projected area of the microfacets should equal that of the macrosurface
+        let ggx = Ggx::from_roughness(0.6);
+        let steps = 2000;
+        let integral: f64 = (0..steps).map(|i| {
+            let theta = (i as f64 + 0.5) / steps as f64 * PI / 2.0;
+            let m = Vector::new(theta.sin(), 0.0, theta.cos());
+            ggx.d(m) * theta.cos() * theta.sin() * 2.0 * PI * (PI / 2.0 / steps as f64)
+        }).sum();
+        assert!((integral - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn visible_normals_face_viewer() {
+        let ggx = Ggx::from_roughness(0.8);
+        let wo = Vector::new(0.6, 0.0, 0.8);
+        for i in 0..100 {
+            for j in 0..10 {
+                let m = ggx.sample_visible_normal(wo, i as f64 / 100.0, j as f64 / 10.0);
+                assert!((m.l2_norm() - 1.0).abs() < 1e-12);
+                assert!(m.z >= 0.0 && wo.dot(m) >= 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn conductor_normal_incidence() {
+        let eta = Vector::new(0.2, 1.0, 1.5);
+        let k = Vector::new(3.0, 0.0, 2.0);
+        let channel = |eta: f64, k: f64| ((eta - 1.0).powi(2) + k.powi(2)) / ((eta + 1.0).powi(2) + k.powi(2));
+        let expected = Vector::new(channel(eta.x, k.x), channel(eta.y, k.y), channel(eta.z, k.z));
+        assert!((fresnel_conductor(1.0, eta, k) - expected).l2_norm() < 1e-12);
+    }
+
+    #[test]
+    fn smooth_conductor_is_a_mirror() {
+        let conductor = RoughConductor::from_f0(Vector::new(1.0, 1.0, 1.0), 0.0);
+        let (direction, hit) = hit(Vector::new(1.0, 0.0, -1.0));
+        let reflection = conductor.random_reflection(direction, hit.rebound_normal(), &hit, None, &mut rand::thread_rng()).unwrap();
+        let expected = Vector::new(1.0, 0.0, 1.0).normalise();
+        assert!((reflection.direction.to_vector() - expected).l2_norm() < 1e-12);
+        assert_eq!(reflection.attenuation, Vector::new(1.0, 1.0, 1.0));
+        // Mirrors have no finite BSDF
+        let wo = UnitVector::from(-1.0 * direction.to_vector());
+        assert_eq!(conductor.eval(reflection.direction, wo, hit.rebound_normal(), &hit, None), Vector::zero());
+        assert_eq!(conductor.pdf(reflection.direction, wo, hit.rebound_normal(), &hit, None), 0.0);
+    }
+
+    #[test]
+    fn smooth_dielectric_refracts_by_snells_law() {
+        let dielectric = RoughDielectric::new(1.5, 0.0);
+        let (direction, hit) = hit(Vector::new(1.0, 0.0, -1.0));
+        let sin_t = 0.5f64.sqrt() / 1.5;
+        let refracted = Vector::new(sin_t, 0.0, -(1.0 - sin_t.powi(2)).sqrt());
+        let reflected = Vector::new(1.0, 0.0, 1.0).normalise();
+        for _ in 0..100 {
+            let scattered = dielectric.random_reflection(direction, hit.rebound_normal(), &hit, None, &mut rand::thread_rng()).unwrap();
+            let wi = scattered.direction.to_vector();
+            assert!((wi - refracted).l2_norm() < 1e-12 || (wi - reflected).l2_norm() < 1e-12, "{:?}", wi);
+            assert_eq!(scattered.attenuation, Vector::new(1.0, 1.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn rough_conductor_conserves_energy() {
+        let conductor = RoughConductor::from_f0(Vector::new(1.0, 1.0, 1.0), 0.5);
+        let (direction, hit) = hit(Vector::new(1.0, 0.0, -2.0));
+        let samples = 20000;
+        let mean = (0..samples)
+            .filter_map(|_| conductor.random_reflection(direction, hit.rebound_normal(), &hit, None, &mut rand::thread_rng()))
+            .map(|r| r.attenuation.x)
+            .sum::<f64>() / samples as f64;
+        // Single scattering loses some energy at this roughness, but never gains any
+        assert!(mean <= 1.0 && mean > 0.9);
+    }
+
+    #[test]
+    fn rough_dielectric_mostly_refracts() {
+        let dielectric = RoughDielectric::new(1.5, 0.3);
+        let (direction, hit) = hit(Vector::new(0.0, 0.0, -1.0));
+        let samples = 10000;
+        let refracted = (0..samples)
+            .filter_map(|_| dielectric.random_reflection(direction, hit.rebound_normal(), &hit, None, &mut rand::thread_rng()))
+            .filter(|r| r.direction.z < 0.0)
+            .count();
+        let fraction = refracted as f64 / samples as f64;
+        assert!(fraction > 0.9 && fraction < 0.99);
+    }
+
+    #[test]
+    fn dielectric_density_matches_sampling() {
+        // Dividing the BSDF by its density should recover the sampling weight
+        let ggx = Ggx::from_roughness(0.4);
+        let wo = Vector::new(0.5, 0.0, 0.75f64.sqrt());
+        for eta in [1.5, 1.0 / 1.5] {
+            for _ in 0..1000 {
+                if let Some(wi) = ggx.sample_dielectric(wo, eta, &mut rand::thread_rng()) {
+                    let (f, pdf) = ggx.dielectric_bsdf(wo, wi, eta);
+                    let expected = ggx.g2(wo, wi) / ggx.g1(wo);
+                    assert!((f * wi.z.abs() / pdf - expected).abs() < 1e-6);
+                }
+            }
+        }
+    }
+}
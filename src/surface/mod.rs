@@ -1,26 +1,69 @@
 pub mod lambertian;
 pub mod metal;
 pub mod dielectric;
+pub mod microfacet;
+pub mod principled;
+pub mod diffuse_light;
+pub mod thin_film;
 
-use crate::geometry::{
-    Point,
-    Vector,
-    UnitVector,
-    Ray,
-    shape::Shape,
-    Interval,
-    IntervalBounds,
+use crate::{
+    geometry::{
+        Point,
+        Vector,
+        UnitVector,
+        Ray,
+        Frame,
+        shape::{Shape, HitRecord},
+        Interval,
+        IntervalBounds,
+    },
+    texture::normal::NormalPerturbation,
+    light::{Light, LightSample, EmissionSample},
+    background::{Background, GradientBackground},
+    spectrum::at_wavelength,
+    random::RandomStream,
 };
 
 
 /// A boundary in 3D space which scatters Rays in some (possibly random) fashion
 pub trait Surface {
-    /// Given a `point` on `self`, and an incident `ray`, return a
-    /// random reflected `Ray`, or None if it is absorbed
-    fn scatter(&self, point: Point, ray: Ray) -> Option<ScatteredRay>;
-    /// Determines the first time (if any) at which `ray`
-    /// intersects `self` in the `time_interval`
-    fn intersection(&self, ray: Ray, time_interval: Interval) -> Option<f64>;
+    /// Given the `hit` of an incident `ray` of light of `wavelength` on `self` (as for
+    /// `Material::random_reflection`), return a random reflected `Ray` drawn with the numbers
+    /// from `rng`, or None if it is absorbed
+    fn scatter(&self, hit: &HitRecord, ray: Ray, wavelength: Option<f64>, rng: &mut dyn RandomStream) -> Option<ScatteredRay>;
+    /// Determines the first time (if any) at which `ray` intersects `self` in the
+    /// `time_interval`, drawing from `rng` if the intersection is itself random
+    fn intersection(&self, ray: Ray, time_interval: Interval, rng: &mut dyn RandomStream) -> Option<HitRecord>;
+    /// Given the `hit` of an incident `ray` on `self`, return the
+    /// light of `wavelength` emitted from the point of intersection back along the ray
+    fn emitted(&self, _hit: &HitRecord, _ray: Ray, _wavelength: Option<f64>) -> Vector {
+        Vector::zero()
+    }
+    /// Given the `hit` of an incident `ray` on `self`, return the fraction of
+    /// light arriving from the direction `wi` which is scattered back along the ray,
+    /// as for `Material::eval`
+    fn eval(&self, _hit: &HitRecord, _ray: Ray, _wi: UnitVector, _wavelength: Option<f64>) -> Vector {
+        Vector::zero()
+    }
+    /// Given the `hit` of an incident `ray` on `self`, return the density with
+    /// which `scatter` picks the direction `wi`, as for `Material::pdf`
+    fn pdf(&self, _hit: &HitRecord, _ray: Ray, _wi: UnitVector, _wavelength: Option<f64>) -> f64 {
+        0.0
+    }
+    /// Given the `hit` of a ray on `self`, return its colour at the point of
+    /// intersection, as for `Material::albedo`
+    fn albedo(&self, _hit: &HitRecord, _wavelength: Option<f64>) -> Vector {
+        Vector::zero()
+    }
+    /// Returns `self` as a `Light` which can be sampled directly, if it emits light
+    fn light(&self) -> Option<&dyn Light> {
+        None
+    }
+    /// True iff hits are points within a participating medium, as opposed to on a
+    /// surface, so that light arriving at them isn't foreshortened
+    fn is_medium(&self) -> bool {
+        false
+    }
 }
 
 /// An attenuated, reflected `Ray`
@@ -38,11 +81,62 @@ pub trait Material {
     /// direction of the reflected ray, or None if it is absorbed
     /// # Parameters
     /// 1. `ray_direction` - the direction of the incident ray
-    /// 1. `rebound_normal` - the normal from the Shape at the point of intersection, with
-    /// convention the normal points against the incident ray
-    /// 1. `entering_surface` - a closure returning true iff the ray is entering the surface, as opposed to leaving it
-    /// NB: determining whether the ray is entering the surface may be expensive for some Shapes, hence the closure
-    fn random_reflection(&self, ray_direction: UnitVector, rebound_normal: UnitVector, entering_surface: impl Fn() -> bool) -> Option<Reflection>;
+    /// 1. `rebound_normal` - the (shading) normal from the Shape at the point of intersection,
+    ///    with convention the normal points against the incident ray
+    /// 1. `hit` - the local geometry at the point of intersection, at which any `Texture`s
+    ///    are evaluated. `hit.front_face` is true iff the ray is entering the surface, as
+    ///    opposed to leaving it
+    /// 1. `wavelength` - the wavelength of the light being traced (in nanometres), or None
+    ///    when rendering in RGB. Attenuations are RGB colours either way, and only materials
+    ///    whose scattering depends on wavelength, such as dispersive glass, need it
+    /// 1. `rng` - the source of the random numbers with which to pick the direction
+    fn random_reflection(&self, ray_direction: UnitVector, rebound_normal: UnitVector, hit: &HitRecord, wavelength: Option<f64>, rng: &mut dyn RandomStream) -> Option<Reflection>;
+
+    /// Returns the fraction of light arriving from `wi` which is scattered towards
+    /// `wo`, per unit solid angle: the BSDF times the cosine of `wi` with the normal.
+    /// The attenuation of a `Reflection` in direction `wi` is this divided by `pdf`
+    ///
+    /// Materials which only scatter into isolated directions, such as mirrors and
+    /// smooth glass, have no finite BSDF, and keep the default of zero
+    /// # Parameters
+    /// 1. `wi` - the direction towards the incoming light
+    /// 1. `wo` - the direction towards the viewer, i.e. opposite the incident ray
+    /// 1. `rebound_normal`, `hit`, `wavelength` - as for `random_reflection`
+    fn eval(&self, _wi: UnitVector, _wo: UnitVector, _rebound_normal: UnitVector, _hit: &HitRecord, _wavelength: Option<f64>) -> Vector {
+        Vector::zero()
+    }
+
+    /// Returns the density, per unit solid angle, with which `random_reflection`
+    /// picks `wi` for an incident ray along -`wo`, with parameters as for `eval`.
+    /// Like `eval`, this is zero for materials which only scatter into isolated directions
+    fn pdf(&self, _wi: UnitVector, _wo: UnitVector, _rebound_normal: UnitVector, _hit: &HitRecord, _wavelength: Option<f64>) -> f64 {
+        0.0
+    }
+
+    /// Returns the colour of the material at `hit`, whatever the directions of the light:
+    /// the fraction of light it scatters when lit and seen head on, such as its texture or
+    /// base colour, for light of `wavelength` (as for `random_reflection`). Materials which
+    /// scatter no light, such as lights, keep the default of black
+    fn albedo(&self, _hit: &HitRecord, _wavelength: Option<f64>) -> Vector {
+        Vector::zero()
+    }
+
+    /// Returns the radiance the material emits from the point of `hit`, back along the ray
+    fn emitted(&self, _hit: &HitRecord) -> Vector {
+        Vector::zero()
+    }
+
+    /// True iff the material may emit light, so its `Surface` should be sampled as a `Light`
+    fn is_emissive(&self) -> bool {
+        false
+    }
+}
+
+/// Maps two uniform random numbers in [0, 1) to a direction in the upper
+/// hemisphere, distributed in proportion to its cosine with the z axis
+pub(crate) fn cosine_hemisphere(u1: f64, u2: f64) -> Vector {
+    let (r, phi) = (u1.sqrt(), 2.0 * core::f64::consts::PI * u2);
+    Vector::new(r * phi.cos(), r * phi.sin(), (1.0 - u1).sqrt())
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -55,6 +149,8 @@ pub struct Reflection {
 pub struct UniformSurface<S: Shape, M: Material> {
     shape: S,
     material: M,
+    // Applied to the shape's shading normal at every hit
+    normal_perturbation: Option<Box<dyn NormalPerturbation>>,
 }
 
 impl<S: Shape, M: Material> UniformSurface<S, M> {
@@ -62,58 +158,205 @@ impl<S: Shape, M: Material> UniformSurface<S, M> {
         Self {
             shape,
             material,
+            normal_perturbation: None,
         }
     }
+
+    /// Perturb the shape's shading normal, e.g. with a `NormalMap` or `BumpMap`
+    pub fn with_normal_perturbation(mut self, perturbation: impl NormalPerturbation + 'static) -> Self {
+        self.normal_perturbation = Some(Box::new(perturbation));
+        self
+    }
 }
 
 impl<S: Shape, M: Material> Surface for UniformSurface<S, M> {
-    fn scatter(&self, point: Point, ray: Ray) -> Option<ScatteredRay> {
-        let entering_surface = || self.shape.intersection(
-            Ray::new(point, ray.direction),
-            Interval::new(0.0001, f64::MAX, IntervalBounds::Open)
-        ).is_some();
+    fn scatter(&self, hit: &HitRecord, ray: Ray, wavelength: Option<f64>, rng: &mut dyn RandomStream) -> Option<ScatteredRay> {
         let reflection = self.material.random_reflection(
             ray.direction,
-            self.shape.normal_against_ray(point, ray),
-            entering_surface
+            hit.rebound_normal(),
+            hit,
+            wavelength,
+            rng,
         )?;
         Some(ScatteredRay {
-            attenuation: reflection.attenuation,
+            attenuation: at_wavelength(reflection.attenuation, wavelength),
             ray: Ray {
-                origin: point,
+                origin: hit.point,
                 direction: reflection.direction,
             },
         })
     }
+
+    fn eval(&self, hit: &HitRecord, ray: Ray, wi: UnitVector, wavelength: Option<f64>) -> Vector {
+        let f = self.material.eval(wi, UnitVector::from(-1.0 * ray.direction), hit.rebound_normal(), hit, wavelength);
+        at_wavelength(f, wavelength)
+    }
+
+    fn pdf(&self, hit: &HitRecord, ray: Ray, wi: UnitVector, wavelength: Option<f64>) -> f64 {
+        self.material.pdf(wi, UnitVector::from(-1.0 * ray.direction), hit.rebound_normal(), hit, wavelength)
+    }
+
+    fn albedo(&self, hit: &HitRecord, wavelength: Option<f64>) -> Vector {
+        at_wavelength(self.material.albedo(hit, wavelength), wavelength)
+    }
+
+    fn emitted(&self, hit: &HitRecord, _ray: Ray, wavelength: Option<f64>) -> Vector {
+        at_wavelength(self.material.emitted(hit), wavelength)
+    }
+
+    fn light(&self) -> Option<&dyn Light> {
+        if self.material.is_emissive() {
+            Some(self)
+        } else {
+            None
+        }
+    }
     
-    fn intersection(&self, ray: Ray, time_interval: Interval) -> Option<f64> {
-        self.shape.intersection(ray, time_interval)
+    fn intersection(&self, ray: Ray, time_interval: Interval, _rng: &mut dyn RandomStream) -> Option<HitRecord> {
+        let mut hit = self.shape.intersection(ray, time_interval)?;
+        if let Some(perturbation) = &self.normal_perturbation {
+            let normal = perturbation.perturb(&hit);
+            hit.set_shading_frame(normal, hit.tangent.to_vector());
+        }
+        Some(hit)
+    }
+}
+
+/// Emissive surfaces are sampled according to their shape
+impl<S: Shape, M: Material> Light for UniformSurface<S, M> {
+    fn sample(&self, point: Point, rng: &mut dyn RandomStream) -> Option<LightSample> {
+        let (hit, pdf) = self.shape.sample(point, rng)?;
+        let offset = hit.point - point;
+        Some(LightSample {
+            direction: UnitVector::from(offset),
+            distance: offset.l2_norm(),
+            radiance: self.material.emitted(&hit),
+            pdf,
+        })
+    }
+
+    fn pdf(&self, point: Point, direction: UnitVector) -> f64 {
+        self.shape.pdf(point, direction)
+    }
+
+    /// Samples points uniformly by area, and directions by cosine from the front face
+    fn sample_emission(&self, rng: &mut dyn RandomStream) -> Option<EmissionSample> {
+        let (hit, position_pdf) = self.shape.sample_surface(rng)?;
+        let normal = hit.geometric_normal;
+        let direction = UnitVector::from(Frame::from_normal(normal).to_world(
+            cosine_hemisphere(rng.next_uniform(), rng.next_uniform())
+        ));
+        Some(EmissionSample {
+            ray: Ray::new(hit.point, direction),
+            normal: Some(normal),
+            radiance: self.material.emitted(&hit),
+            position_pdf,
+            direction_pdf: self.emission_pdf(hit.point, Some(normal), direction),
+        })
+    }
+
+    fn emission_pdf(&self, _point: Point, normal: Option<UnitVector>, direction: UnitVector) -> f64 {
+        normal.map_or(0.0, |n| direction.dot(n.to_vector()).max(0.0) / core::f64::consts::PI)
     }
 }
 
 
 pub struct SurfaceSet {
     surfaces: Vec<Box<dyn Surface>>,
+    // The indices of the surfaces which emit light
+    emissive_surfaces: Vec<usize>,
+    // Lights without geometry, which rays can't hit
+    lights: Vec<Box<dyn Light>>,
+    // Seen by rays which escape the scene
+    background: Box<dyn Background>,
 }
 
-impl SurfaceSet {
-    pub fn new() -> Self {
+impl Default for SurfaceSet {
+    fn default() -> Self {
         Self {
-            surfaces: vec![],
+            surfaces: Vec::new(),
+            emissive_surfaces: Vec::new(),
+            lights: Vec::new(),
+            background: Box::new(GradientBackground::default()),
         }
     }
+}
+
+impl SurfaceSet {
+    /// Create an empty scene, under the default `GradientBackground`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_background(&mut self, background: Box<dyn Background>) {
+        self.background = background;
+    }
+
+    pub fn background(&self) -> &dyn Background {
+        self.background.as_ref()
+    }
 
     pub fn add(&mut self, surface: Box<dyn Surface>) {
+        if surface.light().is_some() {
+            self.emissive_surfaces.push(self.surfaces.len());
+        }
         self.surfaces.push(surface);
     }
 
+    /// Add a light without geometry, e.g. a `PointLight`
+    pub fn add_light(&mut self, light: Box<dyn Light>) {
+        self.lights.push(light);
+    }
+
     pub fn clear(&mut self) {
         self.surfaces.clear();
+        self.emissive_surfaces.clear();
+        self.lights.clear();
+    }
+
+    /// All the lights: the surfaces which emit light, those added
+    /// with `add_light`, and the background (if it is sampled)
+    pub fn lights(&self) -> impl Iterator<Item = &dyn Light> {
+        (0..self.light_count()).filter_map(|i| self.light(i))
+    }
+
+    fn light_count(&self) -> usize {
+        self.emissive_surfaces.len() + self.lights.len() + self.background.light().map_or(0, |_| 1)
+    }
+
+    fn light(&self, i: usize) -> Option<&dyn Light> {
+        let surfaces = self.emissive_surfaces.len();
+        match self.emissive_surfaces.get(i) {
+            Some(surface) => self.surfaces[*surface].light(),
+            None => match self.lights.get(i - surfaces) {
+                Some(light) => Some(light.as_ref()),
+                None => self.background.light(),
+            },
+        }
+    }
+
+    /// Picks one of the lights uniformly at random, returning
+    /// it with the probability of its having been picked
+    pub fn sample_light(&self, rng: &mut dyn RandomStream) -> Option<(&dyn Light, f64)> {
+        let count = self.light_count();
+        if count == 0 {
+            return None
+        }
+        let i = ((rng.next_uniform() * count as f64) as usize).min(count - 1);
+        Some((self.light(i)?, self.light_selection_probability()))
+    }
+
+    /// The probability of `sample_light` picking any given light
+    pub fn light_selection_probability(&self) -> f64 {
+        match self.light_count() {
+            0 => 0.0,
+            count => 1.0 / count as f64,
+        }
     }
 
     /// Determines the first time (if any) at which the
     /// `Ray` intersects any `Surface` in the `time_interval`
-    pub fn intersection(&self, ray: Ray, time_interval: Interval) -> Option<SurfaceSetIntersection> {
+    pub fn intersection(&self, ray: Ray, time_interval: Interval, rng: &mut dyn RandomStream) -> Option<SurfaceSetIntersection<'_>> {
         let subsequent_bounds = match time_interval.bounds() {
             IntervalBounds::Open => IntervalBounds::LeftOpenRightClosed,
             IntervalBounds::Closed => IntervalBounds::Closed,
@@ -121,20 +364,23 @@ impl SurfaceSet {
             IntervalBounds::LeftClosedRightOpen => IntervalBounds::Closed,
         };
         let mut out: Option<SurfaceSetIntersection<'_>> = None;
-        self.surfaces.iter().fold(time_interval, |window, s| {
-            let t = match s.intersection(ray, window) {
-                Some(t) => t,
+        self.surfaces.iter().enumerate().fold(time_interval, |window, (index, s)| {
+            let hit = match s.intersection(ray, window, rng) {
+                Some(hit) => hit,
                 None => return window,
             };
-            if t == window.max() && out.is_some() {
-                out.as_mut().unwrap().surfaces.push(s);
-            } else {
-                out.replace(SurfaceSetIntersection {
-                    t,
-                    surfaces: vec![s],
-                });
+            match out.as_mut() {
+                Some(intersection) if hit.t == window.max() => intersection.surfaces.push(s.as_ref()),
+                _ => {
+                    out.replace(SurfaceSetIntersection {
+                        t: hit.t,
+                        index,
+                        surfaces: vec![s.as_ref()],
+                        hit,
+                    });
+                },
             }
-            Interval::new(window.min(), t, subsequent_bounds)
+            Interval::new(window.min(), hit.t, subsequent_bounds)
         });
         out
     }
@@ -142,5 +388,9 @@ impl SurfaceSet {
 
 pub struct SurfaceSetIntersection<'a> {
     pub t: f64,
-    pub surfaces: Vec<&'a Box<dyn Surface>>,
+    /// The position of the first of the `surfaces` in the `SurfaceSet`, in order of addition
+    pub index: usize,
+    pub surfaces: Vec<&'a dyn Surface>,
+    /// The local geometry of the first of the `surfaces`
+    pub hit: HitRecord,
 }
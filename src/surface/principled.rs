@@ -0,0 +1,306 @@
+use super::*;
+use super::microfacet::{Ggx, fresnel_schlick};
+use crate::{
+    geometry::Frame,
+    texture::Texture,
+};
+
+use core::f64::consts::PI;
+
+/// The reflectance at normal incidence of the clearcoat, a varnish of refractive index 1.5
+const CLEARCOAT_F0: f64 = 0.04;
+
+/// An artist-friendly "uber" material in the style of Disney's principled BSDF,
+/// covering plastics, metals, glass, varnished and cloth-like surfaces with one
+/// set of parameters in [0, 1], each of which may be textured. Scalar parameters
+/// take the average of their texture's channels
+///
+/// The material is a stack of layers: a clearcoat over a (possibly) metallic base,
+/// whose dielectric part is glass (according to `transmission`) or a specular
+/// layer over a diffuse and sheen layer. Each layer passes on whatever it doesn't
+/// reflect, and each is energy-conserving by itself, so the whole never reflects
+/// more light than it receives
+pub struct Principled {
+    base_colour: Box<dyn Texture>,
+    metallic: Box<dyn Texture>,
+    roughness: Box<dyn Texture>,
+    specular: Box<dyn Texture>,
+    clearcoat: Box<dyn Texture>,
+    clearcoat_roughness: Box<dyn Texture>,
+    sheen: Box<dyn Texture>,
+    transmission: Box<dyn Texture>,
+}
+
+impl Principled {
+    /// Create a moderately rough dielectric, with a specular reflectance
+    /// of 4% at normal incidence (i.e. a refractive index of 1.5)
+    pub fn new(base_colour: impl Texture + 'static) -> Self {
+        Self {
+            base_colour: Box::new(base_colour),
+            metallic: Box::new(0.0),
+            roughness: Box::new(0.5),
+            specular: Box::new(0.5),
+            clearcoat: Box::new(0.0),
+            clearcoat_roughness: Box::new(0.1),
+            sheen: Box::new(0.0),
+            transmission: Box::new(0.0),
+        }
+    }
+
+    /// Blend from a dielectric (0) to a metal (1) whose reflectance is the base colour
+    pub fn with_metallic(mut self, metallic: impl Texture + 'static) -> Self {
+        self.metallic = Box::new(metallic);
+        self
+    }
+
+    /// The roughness of the base layer, from a mirror (0) to very rough (1)
+    pub fn with_roughness(mut self, roughness: impl Texture + 'static) -> Self {
+        self.roughness = Box::new(roughness);
+        self
+    }
+
+    /// The dielectric's reflectance at normal incidence, in units of 8%
+    pub fn with_specular(mut self, specular: impl Texture + 'static) -> Self {
+        self.specular = Box::new(specular);
+        self
+    }
+
+    /// The strength of a colourless varnish over the whole material
+    pub fn with_clearcoat(mut self, clearcoat: impl Texture + 'static) -> Self {
+        self.clearcoat = Box::new(clearcoat);
+        self
+    }
+
+    pub fn with_clearcoat_roughness(mut self, roughness: impl Texture + 'static) -> Self {
+        self.clearcoat_roughness = Box::new(roughness);
+        self
+    }
+
+    /// The strength of the bright rim seen on cloth at grazing angles
+    pub fn with_sheen(mut self, sheen: impl Texture + 'static) -> Self {
+        self.sheen = Box::new(sheen);
+        self
+    }
+
+    /// Blend from an opaque (0) to a glass-like (1) dielectric, tinted by the base colour
+    pub fn with_transmission(mut self, transmission: impl Texture + 'static) -> Self {
+        self.transmission = Box::new(transmission);
+        self
+    }
+
+    /// Evaluates the parameters at `hit`, weighting the lobes for light leaving along `wo`
+    fn lobes(&self, hit: &HitRecord, wo: Vector) -> Lobes {
+        let scalar = |texture: &dyn Texture| texture.scalar(hit.point, hit.uv).clamp(0.0, 1.0);
+        let schlick = |f0: f64| fresnel_schlick(wo.z, Vector::new(f0, f0, f0)).x;
+        let specular_f0 = 0.08 * scalar(self.specular.as_ref());
+        let refraction_index = ((1.0 + specular_f0.sqrt()) / (1.0 - specular_f0.sqrt())).max(1.01);
+
+        let clearcoat = scalar(self.clearcoat.as_ref()) * schlick(CLEARCOAT_F0);
+        let base = 1.0 - clearcoat;
+        let metallic = scalar(self.metallic.as_ref());
+        let metal = base * metallic;
+        let dielectric = base * (1.0 - metallic);
+        let transmission = scalar(self.transmission.as_ref());
+        let glass = dielectric * transmission;
+        let opaque = dielectric * (1.0 - transmission);
+        let specular = opaque * schlick(specular_f0);
+        let rough = opaque - specular;
+        let sheen = rough * scalar(self.sheen.as_ref()) * (1.0 - wo.z).powi(5);
+        Lobes {
+            base_colour: self.base_colour.value(hit.point, hit.uv),
+            eta: if hit.front_face { refraction_index } else { 1.0 / refraction_index },
+            base: Ggx::from_roughness(scalar(self.roughness.as_ref())),
+            coat: Ggx::from_roughness(scalar(self.clearcoat_roughness.as_ref())),
+            clearcoat,
+            metal,
+            glass,
+            specular,
+            sheen,
+            diffuse: rough - sheen,
+        }
+    }
+}
+
+/// The `Principled` BSDF at a point, as a mixture of lobes whose weights sum to one.
+/// All directions are in the local frame of the rebound normal
+struct Lobes {
+    base_colour: Vector,
+    eta: f64,
+    base: Ggx,
+    coat: Ggx,
+    clearcoat: f64,
+    metal: f64,
+    glass: f64,
+    specular: f64,
+    sheen: f64,
+    diffuse: f64,
+}
+
+impl Lobes {
+    fn eval(&self, wo: Vector, wi: Vector) -> Vector {
+        let (glass, _) = self.base.dielectric_bsdf(wo, wi, self.eta);
+        if wi.z <= 0.0 {
+            return self.glass * glass * self.base_colour
+        }
+        let white = Vector::new(1.0, 1.0, 1.0);
+        let m = (wo + wi).normalise();
+        let specular = self.base.reflection_brdf(wo, wi);
+        (self.clearcoat * self.coat.reflection_brdf(wo, wi) + self.glass * glass + self.specular * specular) * white
+            + self.metal * specular * fresnel_schlick(wo.dot(m), self.base_colour)
+            + (self.sheen * white + self.diffuse * self.base_colour) / PI
+    }
+
+    /// The density with which `sample` picks `wi`
+    fn pdf(&self, wo: Vector, wi: Vector) -> f64 {
+        let (_, glass) = self.base.dielectric_bsdf(wo, wi, self.eta);
+        self.clearcoat * self.coat.reflection_pdf(wo, wi)
+            + (self.metal + self.specular) * self.base.reflection_pdf(wo, wi)
+            + self.glass * glass
+            + (self.sheen + self.diffuse) * wi.z.max(0.0) / PI
+    }
+
+    /// Picks a lobe according to its weight, and samples it
+    fn sample(&self, wo: Vector, rng: &mut dyn RandomStream) -> Option<Vector> {
+        let mut u = rng.next_uniform();
+        for (weight, lobe) in [(self.clearcoat, 0), (self.metal, 1), (self.glass, 2), (self.specular, 1)] {
+            if u < weight {
+                return match lobe {
+                    0 => self.coat.sample_reflection(wo, rng),
+                    1 => self.base.sample_reflection(wo, rng),
+                    _ => self.base.sample_dielectric(wo, self.eta, rng),
+                }
+            }
+            u -= weight;
+        }
+        Some(cosine_hemisphere(rng.next_uniform(), rng.next_uniform()))
+    }
+}
+
+impl Material for Principled {
+    fn random_reflection(&self, ray_direction: UnitVector, rebound_normal: UnitVector, hit: &HitRecord, _wavelength: Option<f64>, rng: &mut dyn RandomStream) -> Option<Reflection> {
+        let frame = Frame::from_normal(rebound_normal);
+        let wo = frame.to_local(-1.0 * ray_direction);
+        if wo.z <= 0.0 {
+            return None
+        }
+        let lobes = self.lobes(hit, wo);
+        let wi = lobes.sample(wo, rng)?;
+        // Weighting by the whole mixture, rather than the sampled lobe,
+        // keeps the estimate low-variance where lobes overlap
+        let pdf = lobes.pdf(wo, wi);
+        if pdf <= 0.0 {
+            return None
+        }
+        Some(Reflection {
+            attenuation: wi.z.abs() / pdf * lobes.eval(wo, wi),
+            direction: UnitVector::from(frame.to_world(wi)),
+        })
+    }
+
+    fn eval(&self, wi: UnitVector, wo: UnitVector, rebound_normal: UnitVector, hit: &HitRecord, _wavelength: Option<f64>) -> Vector {
+        let frame = Frame::from_normal(rebound_normal);
+        let (wo, wi) = (frame.to_local(wo.to_vector()), frame.to_local(wi.to_vector()));
+        if wo.z <= 0.0 {
+            return Vector::zero()
+        }
+        wi.z.abs() * self.lobes(hit, wo).eval(wo, wi)
+    }
+
+    fn pdf(&self, wi: UnitVector, wo: UnitVector, rebound_normal: UnitVector, hit: &HitRecord, _wavelength: Option<f64>) -> f64 {
+        let frame = Frame::from_normal(rebound_normal);
+        let (wo, wi) = (frame.to_local(wo.to_vector()), frame.to_local(wi.to_vector()));
+        if wo.z <= 0.0 {
+            return 0.0
+        }
+        self.lobes(hit, wo).pdf(wo, wi)
+    }
+
+    fn albedo(&self, hit: &HitRecord, _wavelength: Option<f64>) -> Vector {
+        self.base_colour.value(hit.point, hit.uv)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        geometry::{Point, TextureCoordinates},
+        texture::checker::UvChecker,
+    };
+
+    /// A hit at the origin on the plane z = 0, seen from `wo`
+    fn hit(wo: Vector, uv: TextureCoordinates) -> (UnitVector, HitRecord) {
+        let direction = UnitVector::from(-1.0 * wo);
+        let ray = Ray::new(Point::zero() + wo, direction);
+        let normal = UnitVector::from(Vector::new(0.0, 0.0, 1.0));
+        (direction, HitRecord::new(ray, 1.0, normal, uv, Vector::new(1.0, 0.0, 0.0)))
+    }
+
+    fn view(degrees: f64) -> Vector {
+        let theta = degrees.to_radians();
+        Vector::new(theta.sin(), 0.0, theta.cos())
+    }
+
+    /// The average fraction of light reflected towards `wo`
+    /// (under uniform illumination), per channel
+    fn albedo(material: &Principled, wo: Vector) -> Vector {
+        let (direction, hit) = hit(wo, TextureCoordinates::new(0.0, 0.0));
+        let samples = 20000;
+        let total = (0..samples)
+            .filter_map(|_| material.random_reflection(direction, hit.rebound_normal(), &hit, None, &mut rand::thread_rng()))
+            .fold(Vector::zero(), |total, reflection| total + reflection.attenuation);
+        total / samples as f64
+    }
+
+    #[test]
+    fn white_furnace() {
+        let white = Vector::new(1.0, 1.0, 1.0);
+        let materials = [
+            Principled::new(white),
+            Principled::new(white).with_metallic(1.0).with_roughness(0.3),
+            Principled::new(white).with_transmission(1.0).with_roughness(0.2),
+            Principled::new(white).with_clearcoat(1.0).with_sheen(1.0).with_specular(1.0),
+            Principled::new(white).with_metallic(0.5).with_transmission(0.5).with_clearcoat(0.5).with_roughness(0.6),
+        ];
+        for (index, material) in materials.iter().enumerate() {
+            for degrees in [0.0, 30.0, 60.0, 80.0] {
+                let albedo = albedo(material, view(degrees));
+                // Only shadowing between microfacets loses energy
+                assert!(albedo < 1.02 && albedo > 0.85, "albedo {:?} at {} degrees for material {}", albedo, degrees, index);
+            }
+        }
+    }
+
+    #[test]
+    fn sampling_density_is_normalised() {
+        let lobes = Principled::new(Vector::new(0.5, 0.5, 0.5))
+            .with_transmission(0.5)
+            .with_clearcoat(1.0)
+            .with_sheen(1.0)
+            .lobes(&hit(view(45.0), TextureCoordinates::new(0.0, 0.0)).1, view(45.0));
+        // Integrate the density over the sphere on a grid in (cos theta, phi)
+        let steps = 800;
+        let step = |i: usize| (i as f64 + 0.5) / steps as f64;
+        let integral: f64 = (0..steps).flat_map(|i| (0..steps).map(move |j| (i, j))).map(|(i, j)| {
+            let (cos_theta, phi) = (2.0 * step(i) - 1.0, 2.0 * PI * step(j));
+            let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
+            let wi = Vector::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+            lobes.pdf(view(45.0), wi)
+        }).sum::<f64>() * 4.0 * PI / (steps * steps) as f64;
+        assert!((integral - 1.0).abs() < 0.02, "integral {}", integral);
+    }
+
+    #[test]
+    fn textured_base_colour() {
+        let red = Vector::new(1.0, 0.0, 0.0);
+        let blue = Vector::new(0.0, 0.0, 1.0);
+        let material = Principled::new(UvChecker::new(2, 1, red, blue)).with_specular(0.0);
+        for (u, colour) in [(0.25, red), (0.75, blue)] {
+            // Head on, a dielectric with no specular reflectance is purely diffuse
+            let (direction, hit) = hit(view(0.0), TextureCoordinates::new(u, 0.5));
+            let reflection = material.random_reflection(direction, hit.rebound_normal(), &hit, None, &mut rand::thread_rng()).unwrap();
+            assert!((reflection.attenuation - colour).l2_norm() < 1e-9);
+            assert!(reflection.direction.to_vector().z > 0.0);
+        }
+    }
+}
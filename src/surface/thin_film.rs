@@ -0,0 +1,347 @@
+use super::*;
+use crate::spectrum::{sample_wavelength, to_rgb, uplift};
+
+use core::f64::consts::PI;
+use std::ops::{Add, Sub, Mul, Div};
+
+/// The number of wavelengths at which a film's reflectance is found to approximate its colour in RGB
+const RGB_WAVELENGTHS: usize = 16;
+
+/// What lies beneath a `ThinFilm`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Substrate {
+    /// A transparent material with the given refractive index, which light not
+    /// reflected by the film passes on into (the index 1 makes a free-standing
+    /// film, such as a soap bubble)
+    Dielectric(f64),
+    /// A conductor with the complex refractive index `eta` + i`k` for each colour
+    /// channel (as for `RoughConductor::from_complex_ior`), which reflects all light
+    /// it doesn't absorb
+    Conductor { eta: Vector, k: Vector },
+}
+
+/// A film of transparent material, only a few wavelengths thick, coating a base `Material`,
+/// as on lenses and soap bubbles. Light reflected from the top and bottom of the film
+/// interferes, so that some wavelengths are reflected more strongly than others
+///
+/// Over a `Substrate::Dielectric`, light is reflected from the film (as by a mirror) in
+/// proportion to the film's reflectance, and otherwise passes into the base material, which
+/// should be smooth too (e.g. a `Dielectric`), as mirror-like reflections can't be weighed
+/// against a rough base's. Over a `Substrate::Conductor`, the base (e.g. a `Metal`) reflects
+/// all light, with its colour scaled by the film's reflectance relative to the bare conductor's
+pub struct ThinFilm<M: Material> {
+    base: M,
+    substrate: Substrate,
+    // In nanometres
+    thickness: f64,
+    film_index: f64,
+}
+
+impl<M: Material> ThinFilm<M> {
+    /// Coat `base`, whose surface is the `substrate`, with a film `thickness`
+    /// nanometres thick, of refractive index `film_index`
+    pub fn new(base: M, substrate: Substrate, thickness: f64, film_index: f64) -> Self {
+        assert!(thickness >= 0.0 && film_index > 0.0);
+        Self {
+            base,
+            substrate,
+            thickness,
+            film_index,
+        }
+    }
+
+    /// The fraction of light of `wavelength` reflected by the coated substrate, at an angle of
+    /// incidence with cosine `cos_i`, from outside iff `front_face`, or that reflected by the
+    /// bare substrate if `bare`
+    fn spectral_reflectance(&self, cos_i: f64, front_face: bool, wavelength: f64, bare: bool) -> f64 {
+        let substrate = match self.substrate {
+            Substrate::Dielectric(index) => Complex::real(index),
+            Substrate::Conductor { eta, k } => Complex::new(uplift(eta, wavelength), uplift(k, wavelength)),
+        };
+        let (outside, inside) = if front_face {
+            (Complex::real(1.0), substrate)
+        } else {
+            (substrate, Complex::real(1.0))
+        };
+        let thickness = if bare { 0.0 } else { self.thickness };
+        airy_reflectance(cos_i, outside, Complex::real(self.film_index), inside, thickness, wavelength)
+    }
+
+    /// The film's reflectance as for `spectral_reflectance`, for light of `wavelength`
+    /// (in every component), or approximated in RGB when rendering without one
+    fn reflectance(&self, cos_i: f64, hit: &HitRecord, wavelength: Option<f64>, bare: bool) -> Vector {
+        match wavelength {
+            Some(wavelength) => {
+                let reflectance = self.spectral_reflectance(cos_i, hit.front_face, wavelength, bare);
+                Vector::new(reflectance, reflectance, reflectance)
+            },
+            None => {
+                let colour = (0..RGB_WAVELENGTHS)
+                    .map(|i| sample_wavelength((i as f64 + 0.5) / RGB_WAVELENGTHS as f64))
+                    .map(|wavelength| to_rgb(self.spectral_reflectance(cos_i, hit.front_face, wavelength, bare), wavelength))
+                    .sum::<Vector>() / RGB_WAVELENGTHS as f64;
+                colour.map(|c| c.clamp(0.0, 1.0))
+            },
+        }
+    }
+
+    /// The fraction of the base's reflection which the coated conductor keeps
+    fn conductor_tint(&self, cos_i: f64, hit: &HitRecord, wavelength: Option<f64>) -> Vector {
+        let coated = self.reflectance(cos_i, hit, wavelength, false);
+        let bare = self.reflectance(cos_i, hit, wavelength, true);
+        Vector::new(
+            ratio(coated.x, bare.x),
+            ratio(coated.y, bare.y),
+            ratio(coated.z, bare.z),
+        )
+    }
+}
+
+fn ratio(numerator: f64, denominator: f64) -> f64 {
+    if denominator > 0.0 { numerator / denominator } else { 0.0 }
+}
+
+fn mean(v: Vector) -> f64 {
+    (v.x + v.y + v.z) / 3.0
+}
+
+impl<M: Material> Material for ThinFilm<M> {
+    fn random_reflection(&self, ray_direction: UnitVector, rebound_normal: UnitVector, hit: &HitRecord, wavelength: Option<f64>, rng: &mut dyn RandomStream) -> Option<Reflection> {
+        let cos_i = -ray_direction.dot(rebound_normal.to_vector());
+        if let Substrate::Conductor { .. } = self.substrate {
+            let reflection = self.base.random_reflection(ray_direction, rebound_normal, hit, wavelength, rng)?;
+            return Some(Reflection {
+                attenuation: reflection.attenuation * self.conductor_tint(cos_i, hit, wavelength),
+                ..reflection
+            })
+        }
+        let reflectance = self.reflectance(cos_i, hit, wavelength, false);
+        let probability = mean(reflectance);
+        if rng.next_uniform() < probability {
+            let n = rebound_normal.to_vector();
+            return Some(Reflection {
+                attenuation: reflectance / probability,
+                direction: UnitVector::from(ray_direction - 2.0 * n * ray_direction.dot(n)),
+            })
+        }
+        let reflection = self.base.random_reflection(ray_direction, rebound_normal, hit, wavelength, rng)?;
+        Some(Reflection {
+            attenuation: reflection.attenuation * (1.0 - reflectance) / (1.0 - probability),
+            ..reflection
+        })
+    }
+
+    /// The film's own reflection is mirror-like, so only light passing through it to the base
+    /// has a finite BSDF
+    fn eval(&self, wi: UnitVector, wo: UnitVector, rebound_normal: UnitVector, hit: &HitRecord, wavelength: Option<f64>) -> Vector {
+        let f = self.base.eval(wi, wo, rebound_normal, hit, wavelength);
+        if f == 0.0 {
+            return f
+        }
+        let cos_o = wo.dot(rebound_normal.to_vector());
+        match self.substrate {
+            Substrate::Dielectric(_) => f * (1.0 - self.reflectance(cos_o, hit, wavelength, false)),
+            Substrate::Conductor { .. } => f * self.conductor_tint(cos_o, hit, wavelength),
+        }
+    }
+
+    fn pdf(&self, wi: UnitVector, wo: UnitVector, rebound_normal: UnitVector, hit: &HitRecord, wavelength: Option<f64>) -> f64 {
+        let pdf = self.base.pdf(wi, wo, rebound_normal, hit, wavelength);
+        match self.substrate {
+            Substrate::Dielectric(_) if pdf > 0.0 => {
+                let cos_o = wo.dot(rebound_normal.to_vector());
+                pdf * (1.0 - mean(self.reflectance(cos_o, hit, wavelength, false)))
+            },
+            _ => pdf,
+        }
+    }
+
+    /// The coated base's colour, seen head on
+    fn albedo(&self, hit: &HitRecord, wavelength: Option<f64>) -> Vector {
+        let base = self.base.albedo(hit, wavelength);
+        match self.substrate {
+            Substrate::Dielectric(_) => {
+                let reflectance = self.reflectance(1.0, hit, wavelength, false);
+                reflectance + (1.0 - reflectance) * base
+            },
+            Substrate::Conductor { .. } => base * self.conductor_tint(1.0, hit, wavelength),
+        }
+    }
+
+    fn emitted(&self, hit: &HitRecord) -> Vector {
+        self.base.emitted(hit)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.base.is_emissive()
+    }
+}
+
+/// The fraction of unpolarised light of `wavelength` (in nanometres) reflected by a film of
+/// index `film` and `thickness` (in nanometres) between media of index `outside` (on the
+/// incident side, which must be real) and `inside`, at an angle of incidence with cosine
+/// `cos_i`. Summing the light reflected back and forth within the film gives Airy's formula
+fn airy_reflectance(cos_i: f64, outside: Complex, film: Complex, inside: Complex, thickness: f64, wavelength: f64) -> f64 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    // Snell's law, n sin θ constant, gives the (possibly complex) cosines in each layer
+    let sin_i = (1.0 - cos_i.powi(2)).sqrt();
+    let cosine_in = |index: Complex| {
+        let sin = outside * Complex::real(sin_i) / index;
+        (Complex::real(1.0) - sin * sin).sqrt()
+    };
+    let (cos_outside, cos_film, cos_inside) = (Complex::real(cos_i), cosine_in(film), cosine_in(inside));
+    // The phase difference between successive reflections from the bottom of the film
+    let phase = Complex::real(4.0 * PI * thickness / wavelength) * film * cos_film;
+    let delay = (Complex::new(0.0, 1.0) * phase).exp();
+    let reflectance = |r_top: Complex, r_bottom: Complex| {
+        let r = (r_top + r_bottom * delay) / (Complex::real(1.0) + r_top * r_bottom * delay);
+        r.norm_squared()
+    };
+    // The Fresnel amplitude coefficients for each interface and polarisation
+    let s = |n1: Complex, c1: Complex, n2: Complex, c2: Complex| (n1 * c1 - n2 * c2) / (n1 * c1 + n2 * c2);
+    let p = |n1: Complex, c1: Complex, n2: Complex, c2: Complex| (n2 * c1 - n1 * c2) / (n2 * c1 + n1 * c2);
+    let r_s = reflectance(s(outside, cos_outside, film, cos_film), s(film, cos_film, inside, cos_inside));
+    let r_p = reflectance(p(outside, cos_outside, film, cos_film), p(film, cos_film, inside, cos_inside));
+    (0.5 * (r_s + r_p)).clamp(0.0, 1.0)
+}
+
+/// A complex number, for the amplitudes and phases of light waves
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    fn real(re: f64) -> Self {
+        Self::new(re, 0.0)
+    }
+
+    fn norm_squared(self) -> f64 {
+        self.re.powi(2) + self.im.powi(2)
+    }
+
+    fn exp(self) -> Self {
+        let magnitude = self.re.exp();
+        Self::new(magnitude * self.im.cos(), magnitude * self.im.sin())
+    }
+
+    /// The square root with non-negative real part, or, for a negative real number, the
+    /// positive imaginary root (so waves beyond total internal reflection decay)
+    fn sqrt(self) -> Self {
+        let modulus = self.norm_squared().sqrt();
+        let re = (0.5 * (modulus + self.re)).max(0.0).sqrt();
+        let im = (0.5 * (modulus - self.re)).max(0.0).sqrt();
+        Self::new(re, if self.im < 0.0 { -im } else { im })
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self::new(self.re * other.re - self.im * other.im, self.re * other.im + self.im * other.re)
+    }
+}
+
+impl Div for Complex {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        let denominator = other.norm_squared();
+        Self::new(
+            (self.re * other.re + self.im * other.im) / denominator,
+            (self.im * other.re - self.re * other.im) / denominator,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        geometry::TextureCoordinates,
+        surface::{dielectric::Dielectric, metal::Metal, microfacet::{fresnel_dielectric, fresnel_conductor}},
+    };
+
+    #[test]
+    fn bare_substrates_reflect_by_fresnel() {
+        for cos_i in [1.0, 0.7, 0.2] {
+            let glass = airy_reflectance(cos_i, Complex::real(1.0), Complex::real(1.4), Complex::real(1.5), 0.0, 550.0);
+            assert!((glass - fresnel_dielectric(cos_i, 1.5)).abs() < 1e-9);
+            let (eta, k) = (Vector::new(0.18, 0.18, 0.18), Vector::new(3.42, 3.42, 3.42));
+            let gold = airy_reflectance(cos_i, Complex::real(1.0), Complex::real(1.4), Complex::new(eta.x, k.x), 0.0, 550.0);
+            assert!((gold - fresnel_conductor(cos_i, eta, k).x).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn quarter_wave_coating_cancels_reflection() {
+        // A film of index sqrt(1.5), a quarter of a wavelength thick within
+        // it, is the classic anti-reflective coating for glass
+        let (wavelength, glass): (f64, f64) = (550.0, 1.5);
+        let film = glass.sqrt();
+        let thickness = wavelength / (4.0 * film);
+        let coated = |wavelength| airy_reflectance(1.0, Complex::real(1.0), Complex::real(film), Complex::real(glass), thickness, wavelength);
+        assert!(coated(wavelength) < 1e-12);
+        assert!(coated(400.0) > 0.001);
+        assert!(coated(400.0) < fresnel_dielectric(1.0, glass));
+    }
+
+    #[test]
+    fn soap_film_is_coloured() {
+        let bubble = ThinFilm::new(Dielectric::new(1.0), Substrate::Dielectric(1.0), 400.0, 1.33);
+        let ray = Ray::new(Point::new(0.0, 0.0, 1.0), UnitVector::from(Vector::new(0.0, 0.0, -1.0)));
+        let normal = UnitVector::from(Vector::new(0.0, 0.0, 1.0));
+        let hit = HitRecord::new(ray, 1.0, normal, TextureCoordinates::new(0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        let colour = bubble.reflectance(1.0, &hit, None, false);
+        assert!(colour.x.max(colour.y).max(colour.z) > 1.5 * colour.x.min(colour.y).min(colour.z), "{:?}", colour);
+        // Each wavelength reflects by its own amount
+        let spectral = |wavelength| bubble.reflectance(1.0, &hit, Some(wavelength), false).x;
+        assert!((spectral(450.0) - spectral(600.0)).abs() > 0.01);
+        // Whatever isn't reflected passes straight through
+        let mut reflected = 0;
+        for _ in 0..1000 {
+            let reflection = bubble.random_reflection(ray.direction, normal, &hit, None, &mut rand::thread_rng()).unwrap();
+            if reflection.direction.z > 0.0 {
+                reflected += 1;
+            } else {
+                assert_eq!(reflection.direction.to_vector(), ray.direction.to_vector());
+            }
+        }
+        assert!(reflected > 0 && reflected < 500);
+    }
+
+    #[test]
+    fn coated_metal_always_reflects() {
+        let (eta, k) = (Vector::new(0.18, 0.42, 1.37), Vector::new(3.42, 2.35, 1.77));
+        let coated = ThinFilm::new(Metal::new(Vector::new(1.0, 1.0, 1.0)), Substrate::Conductor { eta, k }, 300.0, 1.5);
+        let ray = Ray::new(Point::new(0.0, 0.0, 1.0), UnitVector::from(Vector::new(0.0, 0.0, -1.0)));
+        let normal = UnitVector::from(Vector::new(0.0, 0.0, 1.0));
+        let hit = HitRecord::new(ray, 1.0, normal, TextureCoordinates::new(0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        let reflection = coated.random_reflection(ray.direction, normal, &hit, None, &mut rand::thread_rng()).unwrap();
+        assert_eq!(reflection.direction.to_vector(), Vector::new(0.0, 0.0, 1.0));
+        assert!(reflection.attenuation != Vector::new(1.0, 1.0, 1.0));
+        assert!(reflection.attenuation >= 0.0);
+    }
+}
@@ -0,0 +1,101 @@
+use super::*;
+
+/// A 3D checkerboard of cubes with side length `scale`, alternating
+/// between the `even` and `odd` textures. Because it is defined in space,
+/// the pattern is independent of the `Shape`'s texture coordinates
+pub struct SpatialChecker<E: Texture, O: Texture> {
+    scale: f64,
+    even: E,
+    odd: O,
+}
+
+impl<E: Texture, O: Texture> SpatialChecker<E, O> {
+    pub fn new(scale: f64, even: E, odd: O) -> Self {
+        assert!(scale > 0.0);
+        Self {
+            scale,
+            even,
+            odd,
+        }
+    }
+}
+
+impl<E: Texture, O: Texture> Texture for SpatialChecker<E, O> {
+    fn value(&self, point: Point, uv: TextureCoordinates) -> Vector {
+        let cell = [point.x, point.y, point.z]
+            .into_iter()
+            .map(|c| (c / self.scale).floor() as i64)
+            .sum::<i64>();
+        if cell.rem_euclid(2) == 0 {
+            self.even.value(point, uv)
+        } else {
+            self.odd.value(point, uv)
+        }
+    }
+}
+
+/// A checkerboard of `columns` x `rows` squares over the `Shape`'s texture
+/// coordinates, alternating between the `even` and `odd` textures
+pub struct UvChecker<E: Texture, O: Texture> {
+    columns: u32,
+    rows: u32,
+    even: E,
+    odd: O,
+}
+
+impl<E: Texture, O: Texture> UvChecker<E, O> {
+    pub fn new(columns: u32, rows: u32, even: E, odd: O) -> Self {
+        Self {
+            columns,
+            rows,
+            even,
+            odd,
+        }
+    }
+}
+
+impl<E: Texture, O: Texture> Texture for UvChecker<E, O> {
+    fn value(&self, point: Point, uv: TextureCoordinates) -> Vector {
+        let column = (uv.u * self.columns as f64).floor() as i64;
+        let row = (uv.v * self.rows as f64).floor() as i64;
+        if (column + row).rem_euclid(2) == 0 {
+            self.even.value(point, uv)
+        } else {
+            self.odd.value(point, uv)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Vector = Vector { x: 0.0, y: 0.0, z: 0.0 };
+    const WHITE: Vector = Vector { x: 1.0, y: 1.0, z: 1.0 };
+
+    #[test]
+    fn spatial_checker_alternates() {
+        let checker = SpatialChecker::new(0.5, WHITE, BLACK);
+        let uv = TextureCoordinates::new(0.0, 0.0);
+        assert_eq!(checker.value(Point::new(0.1, 0.1, 0.1), uv), WHITE);
+        assert_eq!(checker.value(Point::new(0.6, 0.1, 0.1), uv), BLACK);
+        assert_eq!(checker.value(Point::new(0.6, 0.6, 0.1), uv), WHITE);
+    }
+
+    #[test]
+    fn spatial_checker_continues_through_origin() {
+        let checker = SpatialChecker::new(1.0, WHITE, BLACK);
+        let uv = TextureCoordinates::new(0.0, 0.0);
+        assert_eq!(checker.value(Point::new(-0.5, 0.5, 0.5), uv), BLACK);
+        assert_eq!(checker.value(Point::new(-0.5, -0.5, 0.5), uv), WHITE);
+    }
+
+    #[test]
+    fn uv_checker_ignores_point() {
+        let checker = UvChecker::new(4, 2, WHITE, BLACK);
+        let uv = TextureCoordinates::new(0.3, 0.2);
+        assert_eq!(checker.value(Point::new(0.0, 0.0, 0.0), uv), BLACK);
+        assert_eq!(checker.value(Point::new(7.0, 0.0, 0.0), uv), BLACK);
+        assert_eq!(checker.value(Point::zero(), TextureCoordinates::new(0.3, 0.7)), WHITE);
+    }
+}
@@ -0,0 +1,183 @@
+use super::*;
+use crate::image::{
+    Image,
+    formatter::{
+        ImageDecoder,
+        ppm::PPMFormatter,
+        png::PNGDecoder,
+    },
+};
+
+use std::{fs, io, path::Path};
+
+/// How texture coordinates outside the image are mapped back onto it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureAddressing {
+    /// Tile the image infinitely
+    Wrap,
+    /// Extend the image's border texels infinitely
+    Clamp,
+}
+
+/// How the image is sampled between texel centres
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureFiltering {
+    /// Take the nearest texel
+    Nearest,
+    /// Linearly interpolate the four surrounding texels
+    Bilinear,
+}
+
+/// How the image's colours are encoded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColourEncoding {
+    Linear,
+    /// The sRGB transfer function, as used by most photos
+    Srgb,
+}
+
+impl ColourEncoding {
+    fn to_linear(self, value: f64) -> f64 {
+        match self {
+            ColourEncoding::Linear => value,
+            ColourEncoding::Srgb if value <= 0.04045 => value / 12.92,
+            ColourEncoding::Srgb => ((value + 0.055) / 1.055).powf(2.4),
+        }
+    }
+}
+
+/// A texture which wraps an image over a `Shape` according to its texture
+/// coordinates, with (0, 0) the bottom-left and (1, 1) the top-right of the image
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    // Linear colours, row by row from the top-left
+    texels: Vec<Vector>,
+    addressing: TextureAddressing,
+    filtering: TextureFiltering,
+}
+
+impl ImageTexture {
+    pub fn new(image: Image, encoding: ColourEncoding, addressing: TextureAddressing,
+        filtering: TextureFiltering) -> Self
+    {
+        let width = image.width as usize;
+        let height = image.height as usize;
+        assert!(width > 0 && height > 0);
+        let texels = image
+            .collect()
+            .into_iter()
+            .map(|pixel| Vector::from(pixel).map(|c| encoding.to_linear(c)))
+            .collect();
+        Self {
+            width,
+            height,
+            texels,
+            addressing,
+            filtering,
+        }
+    }
+
+    /// Load the texture from a PPM (.ppm) or PNG (.png) file
+    pub fn open(path: &Path, encoding: ColourEncoding, addressing: TextureAddressing,
+        filtering: TextureFiltering) -> io::Result<Self>
+    {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        let bytes = fs::read(path)?;
+        let image = match extension.as_deref() {
+            Some("ppm") => PPMFormatter::new(false).decode(&bytes)?,
+            Some("png") => PNGDecoder::new().decode(&bytes)?,
+            _ => return Err(io::Error::new(io::ErrorKind::Unsupported, "unrecognised image file extension")),
+        };
+        Ok(Self::new(image, encoding, addressing, filtering))
+    }
+
+    /// Returns the texel at column `x` and row `y`, which may lie outside the image
+    fn texel(&self, x: i64, y: i64) -> Vector {
+        let address = |i: i64, size: usize| match self.addressing {
+            TextureAddressing::Wrap => i.rem_euclid(size as i64) as usize,
+            TextureAddressing::Clamp => i.clamp(0, size as i64 - 1) as usize,
+        };
+        self.texels[address(y, self.height) * self.width + address(x, self.width)]
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, _point: Point, uv: TextureCoordinates) -> Vector {
+        // Continuous texel coordinates, with texel centres at integers
+        let x = uv.u * self.width as f64 - 0.5;
+        let y = (1.0 - uv.v) * self.height as f64 - 0.5;
+        match self.filtering {
+            TextureFiltering::Nearest => self.texel(x.round() as i64, y.round() as i64),
+            TextureFiltering::Bilinear => {
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let top = (1.0 - fx) * self.texel(x0, y0) + fx * self.texel(x0 + 1, y0);
+                let bottom = (1.0 - fx) * self.texel(x0, y0 + 1) + fx * self.texel(x0 + 1, y0 + 1);
+                (1.0 - fy) * top + fy * bottom
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::Pixel;
+
+    /// A 2x2 image, black on the left and white on the right
+    fn half_white(addressing: TextureAddressing, filtering: TextureFiltering) -> ImageTexture {
+        let image = Image::from_pixels(2, 2, &|c, _r| if c == 0 {
+            Pixel::black()
+        } else {
+            Pixel::new(255, 255, 255)
+        });
+        ImageTexture::new(image, ColourEncoding::Linear, addressing, filtering)
+    }
+
+    fn at(texture: &ImageTexture, u: f64, v: f64) -> Vector {
+        texture.value(Point::zero(), TextureCoordinates::new(u, v))
+    }
+
+    #[test]
+    fn nearest_filtering() {
+        let texture = half_white(TextureAddressing::Clamp, TextureFiltering::Nearest);
+        assert_eq!(at(&texture, 0.2, 0.5), Vector::zero());
+        assert_eq!(at(&texture, 0.8, 0.5), Vector::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn bilinear_filtering_blends_between_centres() {
+        let texture = half_white(TextureAddressing::Clamp, TextureFiltering::Bilinear);
+        assert_eq!(at(&texture, 0.5, 0.3), Vector::new(0.5, 0.5, 0.5));
+        assert_eq!(at(&texture, 0.25, 0.3), Vector::zero());
+    }
+
+    #[test]
+    fn clamp_extends_border() {
+        let texture = half_white(TextureAddressing::Clamp, TextureFiltering::Bilinear);
+        assert_eq!(at(&texture, 0.0, 0.5), Vector::zero());
+        assert_eq!(at(&texture, 1.5, 0.5), Vector::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn wrap_tiles_image() {
+        let texture = half_white(TextureAddressing::Wrap, TextureFiltering::Bilinear);
+        // Halfway between the right column and the (wrapped) left column
+        assert_eq!(at(&texture, 1.0, 0.5), Vector::new(0.5, 0.5, 0.5));
+        assert_eq!(at(&texture, 1.25, 0.5), at(&texture, 0.25, 0.5));
+    }
+
+    #[test]
+    fn srgb_decoding() {
+        let image = Image::from_pixels(1, 1, &|_c, _r| Pixel::new(255, 0, 188));
+        let texture = ImageTexture::new(image, ColourEncoding::Srgb, TextureAddressing::Wrap, TextureFiltering::Nearest);
+        let value = at(&texture, 0.5, 0.5);
+        assert_eq!((value.x, value.y), (1.0, 0.0));
+        assert!((value.z - 0.5).abs() < 0.01);
+    }
+}
@@ -0,0 +1,45 @@
+pub mod checker;
+pub mod image;
+pub mod noise;
+pub mod normal;
+
+use crate::geometry::{
+    Point,
+    Vector,
+    TextureCoordinates,
+};
+
+/// A colour (or other material parameter) which varies over a `Shape`
+pub trait Texture {
+    /// Returns the texture's value at `point` on a `Shape`, whose texture
+    /// coordinates are `uv`
+    fn value(&self, point: Point, uv: TextureCoordinates) -> Vector;
+
+    /// Returns the texture's value averaged over its channels, for
+    /// textures describing a scalar such as roughness or height
+    fn scalar(&self, point: Point, uv: TextureCoordinates) -> f64 {
+        let v = self.value(point, uv);
+        (v.x + v.y + v.z) / 3.0
+    }
+}
+
+/// A `Vector` is a texture of solid colour, taking the same value everywhere
+/// # Example
+/// ```
+/// use ray_tracing::{Point, Texture, TextureCoordinates, Vector};
+/// let red = Vector::new(1.0, 0.0, 0.0);
+/// let value = red.value(Point::new(1.0, 2.0, 3.0), TextureCoordinates::new(0.5, 0.5));
+/// assert_eq!(value, red);
+/// ```
+impl Texture for Vector {
+    fn value(&self, _point: Point, _uv: TextureCoordinates) -> Vector {
+        *self
+    }
+}
+
+/// A `f64` is a solid grey texture, convenient for scalar parameters
+impl Texture for f64 {
+    fn value(&self, _point: Point, _uv: TextureCoordinates) -> Vector {
+        Vector::new(*self, *self, *self)
+    }
+}
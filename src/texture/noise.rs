@@ -0,0 +1,209 @@
+use super::*;
+use crate::random::split_mix;
+
+/// The 12 gradients of improved Perlin noise, pointing to the edges of a cube
+const GRADIENTS: [(f64, f64, f64); 12] = [
+    (1.0, 1.0, 0.0), (-1.0, 1.0, 0.0), (1.0, -1.0, 0.0), (-1.0, -1.0, 0.0),
+    (1.0, 0.0, 1.0), (-1.0, 0.0, 1.0), (1.0, 0.0, -1.0), (-1.0, 0.0, -1.0),
+    (0.0, 1.0, 1.0), (0.0, -1.0, 1.0), (0.0, 1.0, -1.0), (0.0, -1.0, -1.0),
+];
+
+/// Gradient noise (Perlin 2002) over 3D space, whose lattice is
+/// shuffled deterministically according to a seed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Perlin {
+    permutation: [u8; 256],
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut permutation: [u8; 256] = core::array::from_fn(|i| i as u8);
+        // Fisher-Yates shuffle, driven by SplitMix64 so the
+        // permutation is stable across platforms and versions
+        let mut state = seed;
+        for i in (1..permutation.len()).rev() {
+            let j = (split_mix(&mut state) % (i as u64 + 1)) as usize;
+            permutation.swap(i, j);
+        }
+        Self { permutation }
+    }
+
+    fn hash(&self, x: i64, y: i64, z: i64) -> usize {
+        let p = |i: i64| self.permutation[(i & 255) as usize] as i64;
+        p(p(p(x) + y) + z) as usize
+    }
+
+    /// Returns the noise at `point`, which lies in [-1, 1]
+    /// and is zero at every integer lattice point
+    /// # Example
+    /// ```
+    /// use ray_tracing::{Perlin, Point};
+    /// let perlin = Perlin::new(7);
+    /// assert_eq!(perlin.noise(Point::new(1.0, -2.0, 3.0)), 0.0);
+    /// assert_eq!(perlin.noise(Point::new(0.3, 0.5, 0.1)), Perlin::new(7).noise(Point::new(0.3, 0.5, 0.1)));
+    /// ```
+    pub fn noise(&self, point: Point) -> f64 {
+        let floor = point.map(f64::floor);
+        let (x, y, z) = (floor.x as i64, floor.y as i64, floor.z as i64);
+        let f = point - floor;
+        let fade = f.map(|t| t * t * t * (t * (t * 6.0 - 15.0) + 10.0));
+        let corner = |dx: i64, dy: i64, dz: i64| {
+            let (gx, gy, gz) = GRADIENTS[self.hash(x + dx, y + dy, z + dz) % 12];
+            gx * (f.x - dx as f64) + gy * (f.y - dy as f64) + gz * (f.z - dz as f64)
+        };
+        let lerp = |a: f64, b: f64, t: f64| a + t * (b - a);
+        let face = |dz| lerp(
+            lerp(corner(0, 0, dz), corner(1, 0, dz), fade.x),
+            lerp(corner(0, 1, dz), corner(1, 1, dz), fade.x),
+            fade.y,
+        );
+        lerp(face(0), face(1), fade.z).clamp(-1.0, 1.0)
+    }
+
+    /// Fractal Brownian motion: the sum of `octaves` layers of noise, each of
+    /// double the frequency and half the amplitude of the last, normalised to [-1, 1]
+    pub fn fbm(&self, point: Point, octaves: u32) -> f64 {
+        self.octaves(point, octaves, |n| n)
+    }
+
+    /// As `fbm`, but summing the absolute value of each layer, so lies in [0, 1]
+    pub fn turbulence(&self, point: Point, octaves: u32) -> f64 {
+        self.octaves(point, octaves, f64::abs)
+    }
+
+    fn octaves<F: Fn(f64) -> f64>(&self, point: Point, octaves: u32, layer: F) -> f64 {
+        let (sum, total_amplitude) = (0..octaves.max(1))
+            .map(|i| 0.5_f64.powi(i as i32))
+            .fold((0.0, 0.0), |(sum, total), amplitude| (
+                sum + amplitude * layer(self.noise(point / amplitude)),
+                total + amplitude,
+            ));
+        sum / total_amplitude
+    }
+}
+
+/// How a `NoiseTexture` turns noise into a value in [0, 1]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoisePattern {
+    /// Fractal Brownian motion, remapped from [-1, 1]
+    Fbm,
+    /// Turbulence, giving billowing, cloud-like patterns
+    Turbulence,
+    /// Sinusoidal stripes along the z axis, distorted by turbulence
+    Marble,
+    /// Concentric rings around the z axis, distorted by fBm
+    Wood,
+}
+
+/// A procedural texture blending between two colours according to noise
+pub struct NoiseTexture {
+    perlin: Perlin,
+    pattern: NoisePattern,
+    // The spatial frequency of the (first octave of) noise
+    scale: f64,
+    octaves: u32,
+    low: Vector,
+    high: Vector,
+}
+
+impl NoiseTexture {
+    /// Create a greyscale noise texture. Textures with the same seed
+    /// (and parameters) are identical
+    pub fn new(seed: u64, pattern: NoisePattern, scale: f64, octaves: u32) -> Self {
+        Self {
+            perlin: Perlin::new(seed),
+            pattern,
+            scale,
+            octaves,
+            low: Vector::zero(),
+            high: Vector::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    /// Blend between the `low` and `high` colours rather than black and white
+    pub fn with_colours(mut self, low: Vector, high: Vector) -> Self {
+        self.low = low;
+        self.high = high;
+        self
+    }
+
+    /// Returns the noise pattern at `point`, lying in [0, 1]
+    fn intensity(&self, point: Point) -> f64 {
+        let p = self.scale * point;
+        let intensity = match self.pattern {
+            NoisePattern::Fbm => 0.5 * (1.0 + self.perlin.fbm(p, self.octaves)),
+            NoisePattern::Turbulence => self.perlin.turbulence(p, self.octaves),
+            NoisePattern::Marble => 0.5 * (1.0 + f64::sin(p.z + 10.0 * self.perlin.turbulence(p, self.octaves))),
+            NoisePattern::Wood => {
+                let rings = 8.0 * f64::hypot(p.x, p.y) + 2.0 * self.perlin.fbm(p, self.octaves);
+                rings - rings.floor()
+            },
+        };
+        intensity.clamp(0.0, 1.0)
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, point: Point, _uv: TextureCoordinates) -> Vector {
+        let t = self.intensity(point);
+        (1.0 - t) * self.low + t * self.high
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_points() -> impl Iterator<Item = Point> {
+        (0..1000).map(|i| {
+            let i = i as f64;
+            Point::new(0.37 * i, -1.13 * i + 0.5, 2.71 * (i % 17.0))
+        })
+    }
+
+    #[test]
+    fn same_seed_same_noise() {
+        let (a, b) = (Perlin::new(42), Perlin::new(42));
+        assert_eq!(a, b);
+        assert!(sample_points().all(|p| a.fbm(p, 5) == b.fbm(p, 5)));
+    }
+
+    #[test]
+    fn different_seeds_differ() {
+        let (a, b) = (Perlin::new(1), Perlin::new(2));
+        assert!(sample_points().any(|p| a.noise(p) != b.noise(p)));
+    }
+
+    #[test]
+    fn noise_is_bounded_and_varied() {
+        let perlin = Perlin::new(3);
+        let values: Vec<f64> = sample_points().map(|p| perlin.noise(p + 0.25)).collect();
+        assert!(values.iter().all(|n| (-1.0..=1.0).contains(n)));
+        assert!(values.iter().any(|n| *n > 0.1));
+        assert!(values.iter().any(|n| *n < -0.1));
+    }
+
+    #[test]
+    fn single_octave_fbm_is_noise() {
+        let perlin = Perlin::new(4);
+        assert!(sample_points().all(|p| perlin.fbm(p, 1) == perlin.noise(p)));
+    }
+
+    #[test]
+    fn turbulence_is_non_negative() {
+        let perlin = Perlin::new(5);
+        assert!(sample_points().all(|p| (0.0..=1.0).contains(&perlin.turbulence(p, 6))));
+    }
+
+    #[test]
+    fn texture_blends_colours() {
+        let low = Vector::new(1.0, 0.0, 0.0);
+        let high = Vector::new(0.0, 0.0, 1.0);
+        let texture = NoiseTexture::new(6, NoisePattern::Marble, 4.0, 7).with_colours(low, high);
+        let uv = TextureCoordinates::new(0.0, 0.0);
+        assert!(sample_points().all(|p| {
+            let v = texture.value(p, uv);
+            v.y == 0.0 && (v.x + v.z - 1.0).abs() < 1e-12
+        }));
+    }
+}
@@ -0,0 +1,172 @@
+use super::*;
+use crate::geometry::{UnitVector, shape::HitRecord};
+
+/// Adds surface detail to a `Shape` without extra geometry, by
+/// perturbing its shading normal
+pub trait NormalPerturbation {
+    /// Returns the new (outwards) shading normal at `hit`
+    fn perturb(&self, hit: &HitRecord) -> UnitVector;
+}
+
+/// A tangent-space normal map, as commonly exported by texturing tools. The
+/// texture's red, green and blue channels, remapped from [0, 1] to [-1, 1], give
+/// the normal's components along the tangent, bitangent and shading normal
+pub struct NormalMap<T: Texture> {
+    texture: T,
+    // Scales the tangential components, exaggerating (> 1) or softening (< 1) the map
+    strength: f64,
+}
+
+impl<T: Texture> NormalMap<T> {
+    pub fn new(texture: T, strength: f64) -> Self {
+        Self {
+            texture,
+            strength,
+        }
+    }
+}
+
+impl<T: Texture> NormalPerturbation for NormalMap<T> {
+    fn perturb(&self, hit: &HitRecord) -> UnitVector {
+        let m = 2.0 * self.texture.value(hit.point, hit.uv) - 1.0;
+        UnitVector::from(
+            self.strength * (m.x * hit.tangent + m.y * hit.bitangent)
+            + m.z * hit.shading_normal
+        )
+    }
+}
+
+/// A bump map, which treats a texture (averaged over its channels) as a height
+/// field over the surface, and tilts the shading normal to match its slope. Slopes
+/// are taken in world space, through the hit's `dpdu` and `dpdv`, so the relief
+/// doesn't depend on how the shape spreads its texture coordinates
+pub struct BumpMap<T: Texture> {
+    height: T,
+    // The height of the bumps corresponding to a texture value of 1
+    scale: f64,
+    // The finite difference step in texture coordinates. Spatial textures are
+    // stepped by the corresponding distance over the surface
+    delta: f64,
+}
+
+impl<T: Texture> BumpMap<T> {
+    pub fn new(height: T, scale: f64, delta: f64) -> Self {
+        assert!(delta > 0.0);
+        Self {
+            height,
+            scale,
+            delta,
+        }
+    }
+
+    fn height_at(&self, point: Point, uv: TextureCoordinates) -> f64 {
+        self.scale * self.height.scalar(point, uv)
+    }
+}
+
+impl<T: Texture> NormalPerturbation for BumpMap<T> {
+    fn perturb(&self, hit: &HitRecord) -> UnitVector {
+        // Without derivatives from the shape, texture space is taken to match world space
+        let (dpdu, dpdv) = if hit.dpdu == 0.0 || hit.dpdv == 0.0 {
+            (hit.tangent.to_vector(), hit.bitangent.to_vector())
+        } else {
+            (hit.dpdu, hit.dpdv)
+        };
+        let h = self.height_at(hit.point, hit.uv);
+        let h_u = self.height_at(
+            hit.point + self.delta * dpdu,
+            TextureCoordinates::new(hit.uv.u + self.delta, hit.uv.v),
+        );
+        let h_v = self.height_at(
+            hit.point + self.delta * dpdv,
+            TextureCoordinates::new(hit.uv.u, hit.uv.v + self.delta),
+        );
+        // The derivatives of the surface displaced along the normal by the height field
+        let n = hit.shading_normal.to_vector();
+        let displaced_dpdu = dpdu + (h_u - h) / self.delta * n;
+        let displaced_dpdv = dpdv + (h_v - h) / self.delta * n;
+        let normal = displaced_dpdu.cross(displaced_dpdv);
+        UnitVector::from(if normal.dot(n) < 0.0 { -1.0 * normal } else { normal })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{Ray, Interval, IntervalBounds, shape::{Shape, sphere::Sphere}};
+    use core::f64::consts::PI;
+
+    /// A hit on the plane z = 0, with tangent x and bitangent y
+    fn hit() -> HitRecord {
+        let ray = Ray::new(Point::new(0.0, 0.0, 1.0), UnitVector::from(Vector::new(0.0, 0.0, -1.0)));
+        HitRecord::new(
+            ray,
+            1.0,
+            UnitVector::from(Vector::new(0.0, 0.0, 1.0)),
+            TextureCoordinates::new(0.5, 0.5),
+            Vector::new(1.0, 0.0, 0.0),
+        )
+    }
+
+    /// A height field rising along u
+    struct Ramp;
+
+    impl Texture for Ramp {
+        fn value(&self, _point: Point, uv: TextureCoordinates) -> Vector {
+            Vector::new(uv.u, uv.u, uv.u)
+        }
+    }
+
+    #[test]
+    fn flat_normal_map_is_identity() {
+        let map = NormalMap::new(Vector::new(0.5, 0.5, 1.0), 1.0);
+        assert_eq!(map.perturb(&hit()), hit().shading_normal);
+    }
+
+    #[test]
+    fn normal_map_follows_tangent_frame() {
+        let map = NormalMap::new(Vector::new(1.0, 0.5, 1.0), 1.0);
+        let expected = Vector::new(1.0, 0.0, 1.0).normalise();
+        assert!((map.perturb(&hit()).to_vector() - expected).l2_norm() < 1e-12);
+        let softened = NormalMap::new(Vector::new(1.0, 0.5, 1.0), 0.0);
+        assert_eq!(softened.perturb(&hit()), hit().shading_normal);
+    }
+
+    #[test]
+    fn constant_bump_map_is_identity() {
+        let map = BumpMap::new(Vector::new(0.3, 0.3, 0.3), 2.0, 1e-3);
+        assert_eq!(map.perturb(&hit()), hit().shading_normal);
+    }
+
+    #[test]
+    fn bump_map_tilts_down_slope() {
+        let map = BumpMap::new(Ramp, 1.0, 1e-3);
+        let expected = Vector::new(-1.0, 0.0, 1.0).normalise();
+        assert!((map.perturb(&hit()).to_vector() - expected).l2_norm() < 1e-9);
+    }
+
+    /// Bumps in u and v, a 40th of the way round the sphere apart
+    struct Ripples;
+
+    impl Texture for Ripples {
+        fn value(&self, _point: Point, uv: TextureCoordinates) -> Vector {
+            let height = (80.0 * PI * uv.u).sin() + (40.0 * PI * uv.v).cos();
+            Vector::new(height, height, height)
+        }
+    }
+
+    #[test]
+    fn bump_map_relief_scales_with_its_shape() {
+        // Scaling a sphere and the height of its bumps alike leaves the bumps' slopes alone
+        let perturbed = |radius: f64| {
+            let sphere = Sphere::new(Point::zero(), radius);
+            let ray = Ray::new(Point::new(0.3 * radius, 0.4 * radius, 5.0 * radius), UnitVector::from(Vector::new(0.0, 0.0, -1.0)));
+            let hit = sphere.intersection(ray, Interval::positive_reals(IntervalBounds::Open)).unwrap();
+            (BumpMap::new(Ripples, 0.01 * radius, 1e-6).perturb(&hit), hit.shading_normal)
+        };
+        let (small, unperturbed) = perturbed(1.0);
+        let (large, _) = perturbed(3.0);
+        assert!(small.dot(unperturbed.to_vector()) < 0.99);
+        assert!((small.to_vector() - large.to_vector()).l2_norm() < 1e-6);
+    }
+}
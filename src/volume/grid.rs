@@ -0,0 +1,264 @@
+use crate::geometry::{Point, Vector};
+
+use std::{
+    io::{self, Read, Write},
+    ops::{Add, Mul},
+};
+
+/// The magic bytes at the start of every voxel file
+const MAGIC: &[u8; 4] = b"VOXL";
+
+/// A value which may be stored in a `VoxelGrid`
+pub trait Voxel: Copy + Add<Output = Self> + Mul<f64, Output = Self> {
+    /// The number of f32 channels used to store the value on disk
+    const CHANNELS: usize;
+
+    fn from_channels(channels: &[f32]) -> Self;
+
+    fn to_channels(self) -> Vec<f32>;
+}
+
+impl Voxel for f64 {
+    const CHANNELS: usize = 1;
+
+    fn from_channels(channels: &[f32]) -> Self {
+        channels[0] as f64
+    }
+
+    fn to_channels(self) -> Vec<f32> {
+        vec![self as f32]
+    }
+}
+
+impl Voxel for Vector {
+    const CHANNELS: usize = 3;
+
+    fn from_channels(channels: &[f32]) -> Self {
+        Vector::new(channels[0] as f64, channels[1] as f64, channels[2] as f64)
+    }
+
+    fn to_channels(self) -> Vec<f32> {
+        vec![self.x as f32, self.y as f32, self.z as f32]
+    }
+}
+
+/// A dense 3D grid of voxels, indexed by (x, y, z)
+///
+/// On disk a grid is stored in a simple raw binary format, with all numbers little-endian:
+/// 1. the 4 magic bytes `VOXL`
+/// 1. the number of channels per voxel as a u32
+/// 1. the x, y and z dimensions of the grid, each as a u32
+/// 1. the voxels as f32 channels, with x varying fastest and z slowest
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoxelGrid<T: Voxel> {
+    dimensions: [usize; 3],
+    voxels: Vec<T>,
+}
+
+impl<T: Voxel> VoxelGrid<T> {
+    /// Create a grid from its voxels, with x varying fastest and z slowest
+    pub fn new(dimensions: [usize; 3], voxels: Vec<T>) -> Self {
+        assert!(dimensions.iter().all(|d| *d > 0));
+        assert_eq!(voxels.len(), dimensions.iter().product::<usize>());
+        Self {
+            dimensions,
+            voxels,
+        }
+    }
+
+    /// Create a 1x1x1 grid, which takes `value` everywhere
+    pub fn uniform(value: T) -> Self {
+        Self::new([1, 1, 1], vec![value])
+    }
+
+    /// Create a grid by evaluating `voxel` at each (x, y, z) index
+    pub fn from_fn<F>(dimensions: [usize; 3], voxel: F) -> Self
+        where F: Fn(usize, usize, usize) -> T
+    {
+        let [nx, ny, nz] = dimensions;
+        let voxels = (0..nz)
+            .flat_map(|z| (0..ny).flat_map(move |y| (0..nx).map(move |x| (x, y, z))))
+            .map(|(x, y, z)| voxel(x, y, z))
+            .collect();
+        Self::new(dimensions, voxels)
+    }
+
+    pub fn dimensions(&self) -> [usize; 3] {
+        self.dimensions
+    }
+
+    pub fn voxels(&self) -> impl Iterator<Item = &T> {
+        self.voxels.iter()
+    }
+
+    /// Returns the voxel at the given index
+    pub fn voxel(&self, x: usize, y: usize, z: usize) -> T {
+        let [nx, ny, _] = self.dimensions;
+        self.voxels[x + nx * (y + ny * z)]
+    }
+
+    /// Trilinearly interpolate the grid at `local`, where the grid spans [0, 1]^3
+    /// and each voxel's value is taken to be at its centre. Points outside the
+    /// grid take the value of the nearest voxel
+    /// # Example
+    /// ```
+    /// use ray_tracing::{Point, VoxelGrid};
+    /// let grid = VoxelGrid::new([2, 1, 1], vec![0.0, 1.0]);
+    /// assert_eq!(grid.sample(Point::new(0.5, 0.5, 0.5)), 0.5);
+    /// assert_eq!(grid.sample(Point::new(0.0, 0.5, 0.5)), 0.0);
+    /// ```
+    pub fn sample(&self, local: Point) -> T {
+        let axis = |coordinate: f64, dimension: usize| {
+            let g = (coordinate * dimension as f64 - 0.5).clamp(0.0, (dimension - 1) as f64);
+            let low = (g.floor() as usize).min(dimension - 1);
+            let high = (low + 1).min(dimension - 1);
+            (low, high, g - low as f64)
+        };
+        let (x0, x1, fx) = axis(local.x, self.dimensions[0]);
+        let (y0, y1, fy) = axis(local.y, self.dimensions[1]);
+        let (z0, z1, fz) = axis(local.z, self.dimensions[2]);
+        let lerp = |a: T, b: T, f: f64| a * (1.0 - f) + b * f;
+        let face = |z| lerp(
+            lerp(self.voxel(x0, y0, z), self.voxel(x1, y0, z), fx),
+            lerp(self.voxel(x0, y1, z), self.voxel(x1, y1, z), fx),
+            fy,
+        );
+        lerp(face(z0), face(z1), fz)
+    }
+
+    /// Read a grid stored in the raw voxel format. Densities, albedos and emission
+    /// can't be negative, so neither can any channel of the grid's voxels
+    pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("missing VOXL magic bytes"))
+        }
+        let channels = read_u32(reader)? as usize;
+        if channels != T::CHANNELS {
+            return Err(invalid_data(&format!("expected {} channels, found {}", T::CHANNELS, channels)))
+        }
+        let dimensions = [read_u32(reader)? as usize, read_u32(reader)? as usize, read_u32(reader)? as usize];
+        let too_large = || invalid_data("grid is too large");
+        let count = dimensions.iter().try_fold(1_usize, |count, &dimension| count.checked_mul(dimension))
+            .ok_or_else(too_large)?;
+        if count == 0 {
+            return Err(invalid_data("grid has no voxels"))
+        }
+        let size = count.checked_mul(4 * channels).ok_or_else(too_large)?;
+        // Read no more than the file holds, rather than trusting the header's size up front
+        let mut bytes = Vec::new();
+        reader.take(size as u64).read_to_end(&mut bytes)?;
+        if bytes.len() != size {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "grid ended unexpectedly"))
+        }
+        let values: Vec<f32> = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        if values.iter().any(|value| value.is_nan() || *value < 0.0) {
+            return Err(invalid_data("grid has negative or NaN voxels"))
+        }
+        let voxels = values.chunks_exact(channels).map(T::from_channels).collect();
+        Ok(Self::new(dimensions, voxels))
+    }
+
+    /// Write the grid in the raw voxel format
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&(T::CHANNELS as u32).to_le_bytes())?;
+        for dimension in self.dimensions {
+            writer.write_all(&(dimension as u32).to_le_bytes())?;
+        }
+        for voxel in &self.voxels {
+            for channel in voxel.to_channels() {
+                writer.write_all(&channel.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl VoxelGrid<f64> {
+    /// Returns the largest value in the grid
+    pub fn max(&self) -> f64 {
+        self.voxels.iter().copied().fold(f64::MIN, f64::max)
+    }
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_fn_indexes_x_fastest() {
+        let grid = VoxelGrid::from_fn([2, 3, 4], |x, y, z| (x + 10 * y + 100 * z) as f64);
+        assert_eq!(grid.voxel(1, 2, 3), 321.0);
+        assert_eq!(grid.voxels().nth(1), Some(&1.0));
+    }
+
+    #[test]
+    fn sample_at_voxel_centre() {
+        let grid = VoxelGrid::from_fn([2, 2, 2], |x, y, z| (x + 2 * y + 4 * z) as f64);
+        assert_eq!(grid.sample(Point::new(0.75, 0.25, 0.75)), 5.0);
+    }
+
+    #[test]
+    fn sample_vector_grid() {
+        let grid = VoxelGrid::new([1, 1, 2], vec![Vector::zero(), Vector::new(1.0, 2.0, 4.0)]);
+        assert_eq!(grid.sample(Point::new(0.5, 0.5, 0.5)), Vector::new(0.5, 1.0, 2.0));
+    }
+
+    #[test]
+    fn round_trip_through_raw_format() {
+        let grid = VoxelGrid::from_fn([3, 2, 1], |x, y, _z| Vector::new(x as f64, y as f64, 0.5));
+        let mut bytes = Vec::new();
+        grid.write_to(&mut bytes).unwrap();
+        let read = VoxelGrid::<Vector>::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(read, grid);
+    }
+
+    #[test]
+    fn reading_wrong_channel_count_fails() {
+        let mut bytes = Vec::new();
+        VoxelGrid::uniform(1.0).write_to(&mut bytes).unwrap();
+        let error = VoxelGrid::<Vector>::read_from(&mut bytes.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    fn header(dimensions: [u32; 3]) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        for value in [1, dimensions[0], dimensions[1], dimensions[2]] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn reading_huge_dimensions_fails() {
+        // Overflowing the voxel count
+        let error = VoxelGrid::<f64>::read_from(&mut header([u32::MAX; 3]).as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        // Too many voxels for the bytes which follow
+        let error = VoxelGrid::<f64>::read_from(&mut header([1 << 20, 1 << 10, 1]).as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn reading_negative_density_fails() {
+        let mut bytes = Vec::new();
+        VoxelGrid::new([2, 1, 1], vec![1.0, -0.5]).write_to(&mut bytes).unwrap();
+        let error = VoxelGrid::<f64>::read_from(&mut bytes.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
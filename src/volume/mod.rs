@@ -0,0 +1,214 @@
+pub mod grid;
+
+use grid::VoxelGrid;
+
+use crate::{
+    geometry::{
+        Point,
+        Vector,
+        UnitVector,
+        Ray,
+        Interval,
+        BoundingBox,
+        TextureCoordinates,
+        shape::HitRecord,
+    },
+    surface::{Surface, ScatteredRay},
+    spectrum::at_wavelength,
+    random::RandomStream,
+};
+
+use core::f64::consts::PI;
+
+/// A participating medium (e.g. cloud or smoke) whose properties vary through
+/// space according to voxel grids stretched over a `BoundingBox`
+///
+/// Free flights through the medium are sampled by delta tracking, so a ray
+/// "intersects" the volume at a random collision point (or not at all, if it
+/// passes through). At a collision the ray is absorbed with probability
+/// 1 - albedo, contributing the emission, and otherwise scattered isotropically
+pub struct HeterogeneousVolume {
+    bounds: BoundingBox,
+    // The extinction coefficient, per unit distance
+    density: VoxelGrid<f64>,
+    // The probability of scattering (as opposed to absorption) at a collision
+    albedo: VoxelGrid<Vector>,
+    // The radiance emitted by the absorbing part of the medium
+    emission: VoxelGrid<Vector>,
+    // An upper bound for the density anywhere in the volume
+    majorant: f64,
+}
+
+impl HeterogeneousVolume {
+    pub fn new(bounds: BoundingBox, density: VoxelGrid<f64>, albedo: VoxelGrid<Vector>,
+        emission: VoxelGrid<Vector>) -> Self
+    {
+        let majorant = density.max();
+        assert!(majorant >= 0.0);
+        Self {
+            bounds,
+            density,
+            albedo,
+            emission,
+            majorant,
+        }
+    }
+
+    fn density_at(&self, point: Point) -> f64 {
+        self.density.sample(self.bounds.to_local(point))
+    }
+
+    /// Estimates the fraction of light transmitted along `ray` through the
+    /// volume in the `time_interval`, by ratio tracking
+    pub fn transmittance(&self, ray: Ray, time_interval: Interval, rng: &mut dyn RandomStream) -> f64 {
+        let window = match self.bounds.clip(ray, time_interval) {
+            Some(w) if self.majorant > 0.0 => w,
+            _ => return 1.0,
+        };
+        let mut transmittance = 1.0;
+        let mut t = window.min();
+        loop {
+            t += self.free_flight(rng);
+            if t >= window.max() {
+                return transmittance
+            }
+            transmittance *= 1.0 - self.density_at(ray.at(t)) / self.majorant;
+        }
+    }
+
+    /// Samples an exponentially distributed distance with rate `self.majorant`
+    fn free_flight(&self, rng: &mut dyn RandomStream) -> f64 {
+        -(1.0 - rng.next_uniform()).ln() / self.majorant
+    }
+}
+
+impl Surface for HeterogeneousVolume {
+    fn scatter(&self, hit: &HitRecord, _ray: Ray, wavelength: Option<f64>, rng: &mut dyn RandomStream) -> Option<ScatteredRay> {
+        let albedo = self.albedo.sample(self.bounds.to_local(hit.point));
+        Some(ScatteredRay {
+            attenuation: at_wavelength(albedo, wavelength),
+            ray: Ray::new(hit.point, isotropic_direction(rng)),
+        })
+    }
+
+    /// The returned `HitRecord` has no meaningful normal, so
+    /// takes it to point back along the ray
+    fn intersection(&self, ray: Ray, time_interval: Interval, rng: &mut dyn RandomStream) -> Option<HitRecord> {
+        if self.majorant <= 0.0 {
+            return None
+        }
+        let window = self.bounds.clip(ray, time_interval)?;
+        let mut t = window.min();
+        loop {
+            t += self.free_flight(rng);
+            if t >= window.max() {
+                return None
+            }
+            if rng.next_uniform() * self.majorant < self.density_at(ray.at(t)) {
+                return Some(HitRecord::new(
+                    ray,
+                    t,
+                    UnitVector::from(-1.0 * ray.direction),
+                    TextureCoordinates::new(0.0, 0.0),
+                    Vector::zero(),
+                ))
+            }
+        }
+    }
+
+    fn emitted(&self, hit: &HitRecord, _ray: Ray, wavelength: Option<f64>) -> Vector {
+        let local = self.bounds.to_local(hit.point);
+        at_wavelength((1.0 - self.albedo.sample(local)) * self.emission.sample(local), wavelength)
+    }
+
+    /// Scattering is isotropic, so the phase function is constant
+    fn eval(&self, hit: &HitRecord, _ray: Ray, _wi: UnitVector, wavelength: Option<f64>) -> Vector {
+        at_wavelength(self.albedo.sample(self.bounds.to_local(hit.point)) / (4.0 * PI), wavelength)
+    }
+
+    fn pdf(&self, _hit: &HitRecord, _ray: Ray, _wi: UnitVector, _wavelength: Option<f64>) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn albedo(&self, hit: &HitRecord, wavelength: Option<f64>) -> Vector {
+        at_wavelength(self.albedo.sample(self.bounds.to_local(hit.point)), wavelength)
+    }
+
+    fn is_medium(&self) -> bool {
+        true
+    }
+}
+
+/// Returns a unit vector uniformly distributed over the sphere
+fn isotropic_direction(rng: &mut dyn RandomStream) -> UnitVector {
+    let z = 1.0 - 2.0 * rng.next_uniform();
+    let rotation = 2.0 * PI * rng.next_uniform();
+    UnitVector::new(z.asin(), rotation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::IntervalBounds;
+
+    fn unit_cube() -> BoundingBox {
+        BoundingBox::new(Point::new(0.0, 0.0, 0.0), Point::new(1.0, 1.0, 1.0))
+    }
+
+    fn fog(density: f64) -> HeterogeneousVolume {
+        HeterogeneousVolume::new(
+            unit_cube(),
+            VoxelGrid::uniform(density),
+            VoxelGrid::uniform(Vector::new(0.5, 0.5, 0.5)),
+            VoxelGrid::uniform(Vector::new(2.0, 0.0, 0.0)),
+        )
+    }
+
+    fn ray_through_cube() -> Ray {
+        Ray::new(Point::new(-1.0, 0.5, 0.5), UnitVector::from(Vector::new(1.0, 0.0, 0.0)))
+    }
+
+    #[test]
+    fn empty_volume_is_never_hit() {
+        let volume = fog(0.0);
+        let window = Interval::positive_reals(IntervalBounds::Open);
+        assert_eq!(volume.intersection(ray_through_cube(), window, &mut rand::thread_rng()), None);
+        assert_eq!(volume.transmittance(ray_through_cube(), window, &mut rand::thread_rng()), 1.0);
+    }
+
+    #[test]
+    fn collisions_lie_within_bounds() {
+        let volume = fog(3.0);
+        let window = Interval::positive_reals(IntervalBounds::Open);
+        for _ in 0..1000 {
+            if let Some(hit) = volume.intersection(ray_through_cube(), window, &mut rand::thread_rng()) {
+                assert!((1.0..=2.0).contains(&hit.t));
+            }
+        }
+    }
+
+    #[test]
+    fn transmittance_matches_beer_lambert() {
+        // The density is 0 on one face of the cube and 2 on the other, so averages 1
+        let volume = HeterogeneousVolume::new(
+            unit_cube(),
+            VoxelGrid::new([2, 1, 1], vec![0.0, 2.0]),
+            VoxelGrid::uniform(Vector::zero()),
+            VoxelGrid::uniform(Vector::zero()),
+        );
+        let window = Interval::positive_reals(IntervalBounds::Open);
+        let samples = 20000;
+        let mean = (0..samples)
+            .map(|_| volume.transmittance(ray_through_cube(), window, &mut rand::thread_rng()))
+            .sum::<f64>() / samples as f64;
+        assert!((mean - (-1.0_f64).exp()).abs() < 0.02);
+    }
+
+    #[test]
+    fn emission_is_weighted_by_absorption() {
+        let volume = fog(1.0);
+        let ray = ray_through_cube();
+        let hit = HitRecord::new(ray, 1.5, UnitVector::from(-1.0 * ray.direction), TextureCoordinates::new(0.0, 0.0), Vector::zero());
+        assert_eq!(volume.emitted(&hit, ray, None), Vector::new(1.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,190 @@
+use ray_tracing::{Aov, AovIntegrator, BidirectionalPathTracer, BoxFilter, Camera, ColourEncoding, ConstantBackground, DiffuseLight, Filter, GaussianFilter, HaltonSampler, ImageTexture, Integrator, Lambertian, LanczosFilter, Metropolis, MitchellFilter, PathTracer, Point, Quad, SobolSampler, Sphere, StratifiedSampler, SurfaceSet, TentFilter, Texture, TextureAddressing, TextureCoordinates, TextureFiltering, UniformSurface, Vector};
+
+use std::path::Path;
+
+fn open(path: &Path) -> ImageTexture {
+    ImageTexture::open(path, ColourEncoding::Linear, TextureAddressing::Clamp, TextureFiltering::Nearest).unwrap()
+}
+
+#[test]
+fn aov_passes_written_alongside_beauty() {
+    let mut world = SurfaceSet::new();
+    world.add(Box::new(UniformSurface::new(
+        Sphere::new(Point::new(0.0, 0.0, -3.0), 2.0),
+        Lambertian::new(Vector::new(0.5, 0.5, 0.5)),
+    )));
+    let camera = Camera::new(4, 4, 2.0, 2.0, 1.0, 0);
+    let directory = tempfile::tempdir().unwrap();
+    let beauty = directory.path().join("beauty.ppm");
+    let normal = directory.path().join("normal.ppm");
+    let albedo = directory.path().join("albedo.ppm");
+    let normal_pass = AovIntegrator::new(Aov::Normal);
+    let albedo_pass = AovIntegrator::new(Aov::Albedo);
+    let passes: [(&dyn Integrator, &Path); 2] = [(&normal_pass, &normal), (&albedo_pass, &albedo)];
+    camera.render_passes(&world, &beauty, &passes).unwrap();
+    let centre = TextureCoordinates::new(0.4, 0.6);
+    let corner = TextureCoordinates::new(0.0, 1.0);
+    // The sky above lights the sphere
+    assert!(open(&beauty).value(Point::zero(), centre).x > 0.0);
+    // The sphere faces the camera, which looks along -z
+    let facing = open(&normal).value(Point::zero(), centre);
+    assert!(facing.z > 0.9);
+    assert_eq!(open(&normal).value(Point::zero(), corner), Vector::zero());
+    // Albedo is written without gamma correction
+    assert!((open(&albedo).value(Point::zero(), centre).x - 0.5).abs() < 0.01);
+}
+
+/// A closed box lit by a small ceiling light at `light_z`, seen from inside
+fn lit_box(light_z: f64, light_radiance: f64) -> SurfaceSet {
+    let mut world = SurfaceSet::new();
+    let walls = [
+        (Point::new(-1.0, -1.0, -3.0), Vector::new(2.0, 0.0, 0.0), Vector::new(0.0, 0.0, 4.0)),
+        (Point::new(-1.0, 1.0, -3.0), Vector::new(2.0, 0.0, 0.0), Vector::new(0.0, 0.0, 4.0)),
+        (Point::new(-1.0, -1.0, -3.0), Vector::new(0.0, 2.0, 0.0), Vector::new(0.0, 0.0, 4.0)),
+        (Point::new(1.0, -1.0, -3.0), Vector::new(0.0, 2.0, 0.0), Vector::new(0.0, 0.0, 4.0)),
+        (Point::new(-1.0, -1.0, -3.0), Vector::new(2.0, 0.0, 0.0), Vector::new(0.0, 2.0, 0.0)),
+        (Point::new(-1.0, -1.0, 1.0), Vector::new(2.0, 0.0, 0.0), Vector::new(0.0, 2.0, 0.0)),
+    ];
+    for (corner, u, v) in walls {
+        world.add(Box::new(UniformSurface::new(Quad::new(corner, u, v), Lambertian::new(Vector::new(0.6, 0.6, 0.6)))));
+    }
+    world.add(Box::new(UniformSurface::new(
+        Quad::new(Point::new(-0.25, 0.99, light_z - 0.25), Vector::new(0.5, 0.0, 0.0), Vector::new(0.0, 0.0, 0.5)),
+        DiffuseLight::new(Vector::new(light_radiance, light_radiance, light_radiance)),
+    )));
+    world
+}
+
+/// The mean brightness of an 8 by 6 image of `world` rendered by `camera`
+fn mean_brightness(world: &SurfaceSet, camera: Camera) -> f64 {
+    let directory = tempfile::tempdir().unwrap();
+    let file_name = directory.path().join("render.ppm");
+    camera.render(world, &file_name).unwrap();
+    let image = open(&file_name);
+    let (width, height) = (8, 6);
+    (0..width).flat_map(|x| (0..height).map(move |y| (x, y))).map(|(x, y)| {
+        let uv = TextureCoordinates::new((x as f64 + 0.5) / width as f64, (y as f64 + 0.5) / height as f64);
+        // Undo the gamma correction
+        image.value(Point::zero(), uv).x.powi(2)
+    }).sum::<f64>() / (width * height) as f64
+}
+
+#[test]
+fn bidirectional_render_matches_path_tracer() {
+    let world = lit_box(-1.5, 2.0);
+    // Seeded, so that the comparison is the same on every run. The path
+    // tracer's image converges faster with the Sobol sampler
+    let expected = mean_brightness(&world, Camera::new(8, 6, 2.0, 1.5, 1.0, 255).with_seed(1).with_sampler(SobolSampler::new()));
+    let actual = mean_brightness(&world, Camera::new(8, 6, 2.0, 1.5, 1.0, 255).with_seed(2).with_integrator(BidirectionalPathTracer::default()));
+    assert!((actual - expected).abs() < 0.05 * expected, "{} vs {}", actual, expected);
+}
+
+#[test]
+fn metropolis_render_matches_path_tracer() {
+    // The light is behind the camera, as pixels too bright to show would be clamped
+    // the more for the noise of Metropolis sampling, darkening its image
+    let world = lit_box(0.5, 10.0);
+    let expected = mean_brightness(&world, Camera::new(8, 6, 2.0, 1.5, 1.0, 255).with_seed(1));
+    let metropolis = Metropolis::new(PathTracer::default()).with_bootstrap_samples(20000);
+    let actual = mean_brightness(&world, Camera::new(8, 6, 2.0, 1.5, 1.0, 255).with_seed(2).with_integrator(metropolis));
+    assert!((actual - expected).abs() < 0.05 * expected, "{} vs {}", actual, expected);
+}
+
+#[test]
+fn samplers_render_matching_images() {
+    let world = lit_box(0.5, 10.0);
+    let expected = mean_brightness(&world, Camera::new(8, 6, 2.0, 1.5, 1.0, 255).with_seed(1));
+    let cameras = [
+        Camera::new(8, 6, 2.0, 1.5, 1.0, 255).with_seed(2).with_sampler(StratifiedSampler::new()),
+        Camera::new(8, 6, 2.0, 1.5, 1.0, 255).with_seed(2).with_sampler(HaltonSampler::new()),
+        Camera::new(8, 6, 2.0, 1.5, 1.0, 255).with_seed(2).with_sampler(SobolSampler::new()),
+    ];
+    for camera in cameras {
+        let actual = mean_brightness(&world, camera);
+        assert!((actual - expected).abs() < 0.08 * expected, "{} vs {}", actual, expected);
+    }
+}
+
+#[test]
+fn seeded_renders_are_reproducible() {
+    let world = lit_box(-1.5, 2.0);
+    let directory = tempfile::tempdir().unwrap();
+    let render = |camera: Camera| {
+        let file_name = directory.path().join("render.ppm");
+        camera.render(&world, &file_name).unwrap();
+        std::fs::read(file_name).unwrap()
+    };
+    let camera = |seed| Camera::new(8, 6, 2.0, 1.5, 1.0, 15).with_seed(seed);
+    let configurations: [fn(Camera) -> Camera; 4] = [
+        |camera| camera,
+        |camera| camera.with_sampler(SobolSampler::new()),
+        |camera| camera.with_integrator(BidirectionalPathTracer::default()),
+        |camera| camera.with_integrator(Metropolis::new(PathTracer::default()).with_bootstrap_samples(1000)),
+    ];
+    for (i, configure) in configurations.iter().enumerate() {
+        let golden = render(configure(camera(7)));
+        assert_eq!(render(configure(camera(7))), golden, "configuration {}", i);
+        assert_ne!(render(configure(camera(8))), golden, "configuration {}", i);
+    }
+}
+
+#[test]
+fn adaptive_sampling_spends_samples_on_noise() {
+    // A sphere lit by the sky, both of which are smooth enough to converge at once,
+    // except where pixels are split between them along the sphere's silhouette
+    let mut world = SurfaceSet::new();
+    world.add(Box::new(UniformSurface::new(
+        Sphere::new(Point::new(0.0, 0.0, -3.0), 1.0),
+        Lambertian::new(Vector::new(0.5, 0.5, 0.5)),
+    )));
+    let directory = tempfile::tempdir().unwrap();
+    let beauty = directory.path().join("beauty.ppm");
+    let heatmap = directory.path().join("heatmap.ppm");
+    let camera = Camera::new(8, 8, 2.0, 2.0, 1.0, 0)
+        .with_seed(3)
+        .with_adaptive_sampling(4, 255, 0.02)
+        .with_sample_heatmap(&heatmap);
+    camera.render(&world, &beauty).unwrap();
+    let samples = |x: f64, y: f64| {
+        let uv = TextureCoordinates::new((x + 0.5) / 8.0, (y + 0.5) / 8.0);
+        (open(&heatmap).value(Point::zero(), uv).x * 255.0).round()
+    };
+    assert_eq!(samples(0.0, 7.0), 4.0);
+    assert!(samples(2.0, 3.0) > 50.0, "{} samples", samples(2.0, 3.0));
+    assert!(open(&beauty).value(Point::zero(), TextureCoordinates::new(0.5, 0.5)).x > 0.0);
+}
+
+/// The linear brightness across the middle row of an image of a light filling
+/// the left half of the view, rendered through `filter`
+fn edge_profile(filter: impl Filter + 'static) -> Vec<f64> {
+    let mut world = SurfaceSet::new();
+    world.set_background(Box::new(ConstantBackground::new(Vector::zero())));
+    world.add(Box::new(UniformSurface::new(
+        Quad::new(Point::new(-2.0, -2.0, -1.0), Vector::new(2.0, 0.0, 0.0), Vector::new(0.0, 4.0, 0.0)),
+        DiffuseLight::new(Vector::new(0.25, 0.25, 0.25)),
+    )));
+    let directory = tempfile::tempdir().unwrap();
+    let file_name = directory.path().join("render.ppm");
+    Camera::new(8, 4, 2.0, 1.0, 1.0, 63).with_seed(5).with_filter(filter).render(&world, &file_name).unwrap();
+    let image = open(&file_name);
+    (0..8).map(|x| image.value(Point::zero(), TextureCoordinates::new((x as f64 + 0.5) / 8.0, 0.375)).x.powi(2)).collect()
+}
+
+#[test]
+fn filters_blur_edges_across_pixels() {
+    let sharp = edge_profile(BoxFilter::default());
+    assert!(sharp[..4].iter().all(|brightness| (brightness - 0.25).abs() < 0.01), "{:?}", sharp);
+    assert!(sharp[4..].iter().all(|brightness| *brightness == 0.0), "{:?}", sharp);
+    let gaussian = edge_profile(GaussianFilter::default());
+    for blurred in [edge_profile(TentFilter::default()), gaussian.clone()] {
+        // Away from the edge, and at the image's borders, the light is unchanged
+        assert!((blurred[0] - 0.25).abs() < 0.01, "{:?}", blurred);
+        assert_eq!(blurred[7], 0.0);
+        assert!(blurred[3] < 0.24 && blurred[4] > 0.01, "{:?}", blurred);
+    }
+    // Sharpening filters' negative lobes keep the edge crisper than the Gaussian's
+    for sharpened in [edge_profile(MitchellFilter::default()), edge_profile(LanczosFilter::default())] {
+        assert!((sharpened[0] - 0.25).abs() < 0.01, "{:?}", sharpened);
+        assert!(sharpened[4] > 0.01 && sharpened[4] < gaussian[4], "{:?} vs {:?}", sharpened, gaussian);
+    }
+}
@@ -1,6 +1,6 @@
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{Read, Seek, SeekFrom, Write};
 
-use ray_tracing::{Pixel, Image, ImageFormatter};
+use ray_tracing::{Background, ColourEncoding, EnvironmentMap, Image, ImageFormatter, ImageTexture, PPMFormatter, Pixel, Point, Texture, TextureAddressing, TextureCoordinates, TextureFiltering, UnitVector, Vector};
 
 #[test]
 fn u32_leading_zeros() {
@@ -41,3 +41,30 @@ fn writes_to_file_faithfully() {
     let expected: Vec<u8> = dummy_formatter.get_bytes(image2).flatten().collect();
     assert_eq!(actual, expected);
 }
+
+#[test]
+fn image_texture_opens_ppm_file() {
+    let image = Image::from_pixels(1, 2, &|c, _r| Pixel::new(255 * c as u8, 0, 0));
+    let mut tmpfile = tempfile::Builder::new().suffix(".ppm").tempfile().unwrap();
+    image.write_to_file(tmpfile.as_file_mut(), &mut PPMFormatter::new(false)).unwrap();
+    let texture = ImageTexture::open(
+        tmpfile.path(),
+        ColourEncoding::Srgb,
+        TextureAddressing::Clamp,
+        TextureFiltering::Nearest,
+    ).unwrap();
+    let right = texture.value(Point::zero(), TextureCoordinates::new(0.9, 0.5));
+    assert_eq!(right, Vector::new(1.0, 0.0, 0.0));
+}
+
+#[test]
+fn environment_map_opens_pfm_file() {
+    // A 1x2 map, dark above and bright below
+    let floats = [5.0f32, 5.0, 5.0, 0.5, 0.5, 0.5];
+    let mut tmpfile = tempfile::Builder::new().suffix(".pfm").tempfile().unwrap();
+    tmpfile.write_all(b"PF\n1 2\n-1.0\n").unwrap();
+    tmpfile.write_all(&floats.iter().flat_map(|f| f.to_le_bytes()).collect::<Vec<u8>>()).unwrap();
+    let map = EnvironmentMap::open(tmpfile.path()).unwrap();
+    assert_eq!(map.radiance(UnitVector::from(Vector::new(0.0, 1.0, 0.0))), Vector::new(0.5, 0.5, 0.5));
+    assert_eq!(map.radiance(UnitVector::from(Vector::new(0.0, -1.0, 0.0))), Vector::new(5.0, 5.0, 5.0));
+}
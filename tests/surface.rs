@@ -1,26 +1,28 @@
-use ray_tracing::{Interval, IntervalBounds, Material, Point, Ray, Reflection, Shape, SurfaceSet, UniformSurface, Vector, UnitVector};
+use ray_tracing::{HitRecord, Interval, IntervalBounds, Lambertian, Material, Metal, NormalMap, Point, Principled, RandomStream, Ray, Reflection, RoughConductor, RoughDielectric, SeededStream, Shape, SurfaceSet, TextureCoordinates, UniformSurface, Vector, UnitVector};
 
 struct DummyShape {
     border: f64,
 }
 
 impl Shape for DummyShape {
-    fn intersection(&self, _ray: Ray, time_interval: Interval) -> Option<f64> {
+    fn intersection(&self, ray: Ray, time_interval: Interval) -> Option<HitRecord> {
         time_interval
             .contains(self.border)
-            .then_some(self.border)
-    }
-
-    fn outwards_normal(&self, _point: Point) -> UnitVector {
-        // Vector::new(1.0, 0.0, 0.0)
-        UnitVector::new(0.0, 0.0)
+            .then(|| HitRecord::new(
+                ray,
+                self.border,
+                // Vector::new(1.0, 0.0, 0.0)
+                UnitVector::new(0.0, 0.0),
+                TextureCoordinates::new(0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+            ))
     }
 }
 
 struct DummyMaterial {}
 
 impl Material for DummyMaterial {
-    fn random_reflection(&self, _ray_direction: UnitVector, rebound_normal: UnitVector, _entering_surface: impl Fn() -> bool) -> Option<Reflection> {
+    fn random_reflection(&self, _ray_direction: UnitVector, rebound_normal: UnitVector, _hit: &HitRecord, _wavelength: Option<f64>, _rng: &mut dyn RandomStream) -> Option<Reflection> {
         Some(Reflection {
             attenuation: Vector::zero(),
             direction: rebound_normal,
@@ -32,33 +34,35 @@ type DummySurface = UniformSurface<DummyShape, DummyMaterial>;
 
 
 #[test]
-fn normal_against_ray_in_direction_of_outwards_normal() {
+fn rebound_normal_in_direction_of_outwards_normal() {
     let shape = DummyShape {
         border: 3.0,
     };
-    let origin = Point::new(0.0, 0.0, 0.0);
     let ray = Ray {
-        origin,
+        origin: Point::new(0.0, 0.0, 0.0),
         direction: UnitVector::from(Vector::new(2.0, 3.0, 4.0)),
     };
+    let hit = shape.intersection(ray, Interval::positive_reals(IntervalBounds::Open)).unwrap();
+    assert!(!hit.front_face);
     assert_eq!(
-        shape.normal_against_ray(origin, ray).to_vector(),
+        hit.rebound_normal().to_vector(),
         Vector::new(-1.0, 0.0, 0.0),
     );
 }
 
 #[test]
-fn normal_against_ray_in_opposite_direction_of_outwards_normal() {
+fn rebound_normal_in_opposite_direction_of_outwards_normal() {
     let shape = DummyShape {
         border: 3.0,
     };
-    let origin = Point::new(0.0, 0.0, 0.0);
     let ray = Ray {
-        origin,
+        origin: Point::new(0.0, 0.0, 0.0),
         direction: UnitVector::from(Vector::new(-2.0, 3.0, 4.0)),
     };
+    let hit = shape.intersection(ray, Interval::positive_reals(IntervalBounds::Open)).unwrap();
+    assert!(hit.front_face);
     assert_eq!(
-        shape.normal_against_ray(origin, ray).to_vector(),
+        hit.rebound_normal().to_vector(),
         Vector::new(1.0, 0.0, 0.0),
     );
 }
@@ -79,8 +83,80 @@ fn surface_set_intersection_returns_first() {
         direction: UnitVector::from(Vector::new(1.0, 0.0, 0.0)),
     };
     let surface_set_intersection = surface_set
-        .intersection(ray, Interval::positive_reals(IntervalBounds::Open))
+        .intersection(ray, Interval::positive_reals(IntervalBounds::Open), &mut SeededStream::new(0))
         .unwrap();
     assert_eq!(surface_set_intersection.t, 2.0);
+    assert_eq!(surface_set_intersection.hit.point, Point::new(2.0, 0.0, 0.0));
     assert_eq!(surface_set_intersection.surfaces.len(), 1);
 }
+
+#[test]
+fn normal_map_perturbs_rebound_normal() {
+    let shape = DummyShape {
+        border: 3.0,
+    };
+    // Tilts the normal (1, 0, 0) towards the tangent (0, 1, 0)
+    let normal_map = NormalMap::new(Vector::new(1.0, 0.5, 1.0), 1.0);
+    let mut surface_set = SurfaceSet::new();
+    surface_set.add(Box::new(DummySurface::new(shape, DummyMaterial {}).with_normal_perturbation(normal_map)));
+    let ray = Ray {
+        origin: Point::new(0.0, 0.0, 0.0),
+        direction: UnitVector::from(Vector::new(-1.0, 0.0, 0.0)),
+    };
+    let rng = &mut SeededStream::new(0);
+    let intersection = surface_set
+        .intersection(ray, Interval::positive_reals(IntervalBounds::Open), rng)
+        .unwrap();
+    let scattered = intersection.surfaces[0].scatter(&intersection.hit, ray, None, rng).unwrap();
+    let expected = Vector::new(1.0, 1.0, 0.0).normalise();
+    assert!((scattered.ray.direction.to_vector() - expected).l2_norm() < 1e-12);
+}
+
+/// Checks each sampled reflection's attenuation is the material's
+/// evaluation in that direction, divided by its density
+fn assert_sampling_matches_evaluation(material: &dyn Material) {
+    let shape = DummyShape {
+        border: 1.0,
+    };
+    let ray = Ray {
+        origin: Point::new(0.0, 1.0, 1.0),
+        direction: UnitVector::from(Vector::new(1.0, -1.0, -1.0)),
+    };
+    let hit = shape.intersection(ray, Interval::positive_reals(IntervalBounds::Open)).unwrap();
+    let wo = UnitVector::from(-1.0 * ray.direction);
+    let rng = &mut SeededStream::new(0);
+    for _ in 0..1000 {
+        if let Some(reflection) = material.random_reflection(ray.direction, hit.rebound_normal(), &hit, None, rng) {
+            let eval = material.eval(reflection.direction, wo, hit.rebound_normal(), &hit, None);
+            let pdf = material.pdf(reflection.direction, wo, hit.rebound_normal(), &hit, None);
+            assert!(pdf > 0.0);
+            assert!((eval / pdf - reflection.attenuation).l2_norm() < 1e-6);
+        }
+    }
+}
+
+#[test]
+fn materials_evaluate_consistently_with_sampling() {
+    let colour = Vector::new(0.9, 0.5, 0.2);
+    assert_sampling_matches_evaluation(&Lambertian::new(colour));
+    assert_sampling_matches_evaluation(&RoughConductor::from_f0(colour, 0.4));
+    assert_sampling_matches_evaluation(&RoughDielectric::new(1.5, 0.3));
+    assert_sampling_matches_evaluation(&Principled::new(colour).with_clearcoat(1.0).with_transmission(0.5));
+}
+
+#[test]
+fn mirror_has_no_finite_bsdf() {
+    let shape = DummyShape {
+        border: 1.0,
+    };
+    let ray = Ray {
+        origin: Point::new(0.0, 0.0, 0.0),
+        direction: UnitVector::from(Vector::new(1.0, 0.0, 0.0)),
+    };
+    let hit = shape.intersection(ray, Interval::positive_reals(IntervalBounds::Open)).unwrap();
+    let metal = Metal::new(Vector::new(1.0, 1.0, 1.0));
+    let reflection = metal.random_reflection(ray.direction, hit.rebound_normal(), &hit, None, &mut SeededStream::new(0)).unwrap();
+    let wo = UnitVector::from(-1.0 * ray.direction);
+    assert_eq!(metal.eval(reflection.direction, wo, hit.rebound_normal(), &hit, None), Vector::zero());
+    assert_eq!(metal.pdf(reflection.direction, wo, hit.rebound_normal(), &hit, None), 0.0);
+}